@@ -0,0 +1,27 @@
+use std::{fs, path::Path};
+
+/// Checked-in ABI the bindings are generated from.
+const ABI: &str = "abi/R0VMHelios.json";
+/// Artifact written by `forge build` in `contracts/`.
+const ARTIFACT: &str = "../contracts/out/R0VMHelios.sol/R0VMHelios.json";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", ABI);
+    println!("cargo:rerun-if-changed={}", ARTIFACT);
+
+    let abi: serde_json::Value = serde_json::from_slice(&fs::read(ABI).unwrap())
+        .unwrap_or_else(|e| panic!("Invalid ABI in {}: {}", ABI, e));
+
+    // Fail when the contract has been rebuilt with a different ABI than the bindings use, so
+    // the bindings can't silently drift from the contract.
+    if Path::new(ARTIFACT).exists() {
+        let artifact: serde_json::Value = serde_json::from_slice(&fs::read(ARTIFACT).unwrap())
+            .unwrap_or_else(|e| panic!("Invalid artifact in {}: {}", ARTIFACT, e));
+        if artifact["abi"] != abi {
+            panic!(
+                "bindings/{} is out of date with contracts/out, update it with `jq .abi contracts/out/R0VMHelios.sol/R0VMHelios.json > bindings/{}`",
+                ABI, ABI
+            );
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Bindings to the `R0VMHelios` contract, generated from its ABI in `abi/R0VMHelios.json`.
+//!
+//! The guest commits [`R0VMHelios::ProofOutputs`] as its journal, and the operator decodes it and
+//! calls the contract with the same definitions, so both always match the deployed contract.
+
+#[cfg(not(feature = "rpc"))]
+alloy_sol_types::sol!(
+    #[allow(missing_docs)]
+    R0VMHelios,
+    "abi/R0VMHelios.json"
+);
+
+#[cfg(feature = "rpc")]
+alloy::sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    R0VMHelios,
+    "abi/R0VMHelios.json"
+);
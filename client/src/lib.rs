@@ -0,0 +1,333 @@
+//! Client for reading the state proven by an `R0VMHelios` light client contract.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use r0vm_helios_client::HeliosClient;
+//! use std::time::Duration;
+//!
+//! let client = HeliosClient::connect("https://...", "0x...".parse()?).await?;
+//! let state = client.fresh_verified_state(Duration::from_secs(3600)).await?;
+//! let value = client
+//!     .storage_at(state.head, "0x...".parse()?, Default::default())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use alloy::eips::BlockId;
+use alloy::providers::{Provider, ProviderBuilder, RootProvider};
+use alloy::transports::http::{Client, Http};
+use alloy::transports::Transport;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use r0vm_helios_bindings::R0VMHelios;
+use R0VMHelios::R0VMHeliosInstance;
+
+/// Beacon chain parameters the contract is deployed with, read once on connecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParams {
+    pub genesis_time: u64,
+    pub seconds_per_slot: u64,
+    pub slots_per_period: u64,
+}
+
+/// The latest head proven to the contract, with the state stored for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifiedState {
+    /// Beacon slot of the head.
+    pub head: u64,
+    /// Beacon block header root of the head.
+    pub header: B256,
+    pub execution_state_root: B256,
+    pub sync_committee_period: u64,
+    /// Hash of the sync committee of `sync_committee_period`.
+    pub sync_committee: B256,
+    /// Unix timestamp of the head's slot.
+    pub timestamp: u64,
+    /// Destination chain block the state was read at.
+    pub block_number: u64,
+}
+
+/// A `HeadUpdate` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadUpdated {
+    pub slot: u64,
+    pub root: B256,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<B256>,
+}
+
+/// A `StorageSlotVerified` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageSlotVerified {
+    /// Head the value was proven at, see [`HeliosClient::storage_at`].
+    pub head: u64,
+    pub contract_address: Address,
+    pub key: B256,
+    pub value: B256,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<B256>,
+}
+
+/// The contract's latest head is older than the caller accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stale {
+    pub head: u64,
+    pub age: Duration,
+    pub max_age: Duration,
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "head {} is {:?} old, more than the accepted {:?}",
+            self.head, self.age, self.max_age
+        )
+    }
+}
+
+impl std::error::Error for Stale {}
+
+/// Reads the proven state of an `R0VMHelios` contract.
+pub struct HeliosClient<T, P> {
+    contract: R0VMHeliosInstance<T, P>,
+    params: ChainParams,
+}
+
+impl HeliosClient<Http<Client>, RootProvider<Http<Client>>> {
+    /// Connect to the contract at `address` over the HTTP JSON-RPC endpoint `rpc_url`.
+    pub async fn connect(rpc_url: &str, address: Address) -> Result<Self> {
+        let rpc_url = rpc_url
+            .parse()
+            .with_context(|| format!("Invalid RPC URL {}", rpc_url))?;
+        Self::new(address, ProviderBuilder::new().on_http(rpc_url)).await
+    }
+}
+
+impl<T: Transport + Clone, P: Provider<T>> HeliosClient<T, P> {
+    /// Read the contract at `address` through `provider`.
+    pub async fn new(address: Address, provider: P) -> Result<Self> {
+        let contract = R0VMHelios::new(address, provider);
+        let params = ChainParams {
+            genesis_time: to_u64(contract.GENESIS_TIME().call().await?._0)?,
+            seconds_per_slot: to_u64(contract.SECONDS_PER_SLOT().call().await?._0)?,
+            slots_per_period: to_u64(contract.SLOTS_PER_PERIOD().call().await?._0)?,
+        };
+        Ok(Self { contract, params })
+    }
+
+    pub fn address(&self) -> Address {
+        *self.contract.address()
+    }
+
+    pub fn params(&self) -> ChainParams {
+        self.params
+    }
+
+    /// The contract instance, for calls this client doesn't wrap.
+    pub fn contract(&self) -> &R0VMHeliosInstance<T, P> {
+        &self.contract
+    }
+
+    /// The latest head proven to the contract.
+    pub async fn head(&self) -> Result<u64> {
+        to_u64(self.contract.head().call().await?._0)
+    }
+
+    /// The latest head and the state stored for it, all read at the same block.
+    pub async fn latest_verified_state(&self) -> Result<VerifiedState> {
+        let block_number = self.contract.provider().get_block_number().await?;
+        let block = BlockId::number(block_number);
+
+        let head = self.contract.head().block(block).call().await?._0;
+        let header = self.contract.headers(head).block(block).call().await?._0;
+        let execution_state_root = self
+            .contract
+            .executionStateRoots(head)
+            .block(block)
+            .call()
+            .await?
+            ._0;
+        let head = to_u64(head)?;
+        let sync_committee_period = head / self.params.slots_per_period;
+        let sync_committee = self
+            .contract
+            .syncCommittees(U256::from(sync_committee_period))
+            .block(block)
+            .call()
+            .await?
+            ._0;
+
+        Ok(VerifiedState {
+            head,
+            header,
+            execution_state_root,
+            sync_committee_period,
+            sync_committee,
+            timestamp: self.slot_timestamp(head),
+            block_number,
+        })
+    }
+
+    /// Like [`Self::latest_verified_state`], but fails with [`Stale`] if the head is older than
+    /// `max_age`.
+    pub async fn fresh_verified_state(&self, max_age: Duration) -> Result<VerifiedState> {
+        let state = self.latest_verified_state().await?;
+        let age = self.age(state.head);
+        if age > max_age {
+            return Err(Stale {
+                head: state.head,
+                age,
+                max_age,
+            }
+            .into());
+        }
+        Ok(state)
+    }
+
+    /// Wait until the contract has been updated to `slot` or past it, polling once per slot.
+    /// Fails if it hasn't within `timeout`.
+    pub async fn wait_for_head(&self, slot: u64, timeout: Duration) -> Result<VerifiedState> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let state = self.latest_verified_state().await?;
+            if state.head >= slot {
+                return Ok(state);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                anyhow::bail!(
+                    "Timed out after {:?} waiting for head {}, the contract is at {}",
+                    timeout,
+                    slot,
+                    state.head
+                );
+            }
+            let poll = Duration::from_secs(self.params.seconds_per_slot);
+            tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + poll)).await;
+        }
+    }
+
+    /// Beacon block header root proven for `slot`, if any.
+    pub async fn header(&self, slot: u64) -> Result<Option<B256>> {
+        let header = self.contract.headers(U256::from(slot)).call().await?._0;
+        Ok((!header.is_zero()).then_some(header))
+    }
+
+    /// Execution state root proven for `slot`, if any.
+    pub async fn execution_state_root(&self, slot: u64) -> Result<Option<B256>> {
+        let root = self
+            .contract
+            .executionStateRoots(U256::from(slot))
+            .call()
+            .await?
+            ._0;
+        Ok((!root.is_zero()).then_some(root))
+    }
+
+    /// Value of storage `slot` of `address`, as proven with the update to the head `block`.
+    ///
+    /// Values are stored by the beacon slot of the head they were proven at, not by execution
+    /// block number. Slots that weren't proven at `block` read as zero.
+    pub async fn storage_at(&self, block: u64, address: Address, slot: B256) -> Result<B256> {
+        Ok(self
+            .contract
+            .getStorageSlot(U256::from(block), address, slot)
+            .call()
+            .await?
+            ._0)
+    }
+
+    /// Unix timestamp of `slot`.
+    pub fn slot_timestamp(&self, slot: u64) -> u64 {
+        self.params.genesis_time + slot * self.params.seconds_per_slot
+    }
+
+    /// Time since `slot`, zero if it is in the future.
+    pub fn age(&self, slot: u64) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Duration::from_secs(now.saturating_sub(self.slot_timestamp(slot)))
+    }
+
+    /// Stream the `HeadUpdate` events emitted from now on.
+    pub async fn head_updates(&self) -> Result<impl Stream<Item = Result<HeadUpdated>>> {
+        let poller = self.contract.HeadUpdate_filter().watch().await?;
+        Ok(poller.into_stream().map(|event| {
+            let (event, log) = event?;
+            Ok(HeadUpdated {
+                slot: to_u64(event.slot)?,
+                root: event.root,
+                block_number: log.block_number,
+                transaction_hash: log.transaction_hash,
+            })
+        }))
+    }
+
+    /// Stream the `StorageSlotVerified` events emitted from now on.
+    pub async fn storage_verifications(
+        &self,
+    ) -> Result<impl Stream<Item = Result<StorageSlotVerified>>> {
+        let poller = self.contract.StorageSlotVerified_filter().watch().await?;
+        Ok(poller.into_stream().map(|event| {
+            let (event, log) = event?;
+            Ok(StorageSlotVerified {
+                head: to_u64(event.head)?,
+                contract_address: event.contractAddress,
+                key: event.key,
+                value: event.value,
+                block_number: log.block_number,
+                transaction_hash: log.transaction_hash,
+            })
+        }))
+    }
+}
+
+/// Key of storage `slot` of `address` proven at the head `block`, as computed by the contract's
+/// `computeStorageKey`.
+pub fn storage_key(block: u64, address: Address, slot: B256) -> B256 {
+    keccak256(
+        [
+            U256::from(block).to_be_bytes::<32>().as_slice(),
+            address.as_slice(),
+            slot.as_slice(),
+        ]
+        .concat(),
+    )
+}
+
+fn to_u64(value: U256) -> Result<u64> {
+    value
+        .try_into()
+        .with_context(|| format!("{} doesn't fit in a u64", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::sol_types::SolValue;
+
+    #[test]
+    fn storage_key_matches_compute_storage_key() {
+        let (block, address, slot) = (
+            7_654_321,
+            Address::repeat_byte(0x42),
+            B256::with_last_byte(1),
+        );
+
+        // `computeStorageKey` hashes `abi.encodePacked(blockNumber, contractAddress, slot)`.
+        let packed = (U256::from(block), address, slot).abi_encode_packed();
+        assert_eq!(packed.len(), 32 + 20 + 32);
+        assert_eq!(storage_key(block, address, slot), keccak256(packed));
+        assert_ne!(
+            storage_key(block + 1, address, slot),
+            storage_key(block, address, slot)
+        );
+    }
+}
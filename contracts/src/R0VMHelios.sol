@@ -299,4 +299,16 @@ contract R0VMHelios is AccessControlEnumerable {
     {
         return storageValues[computeStorageKey(blockNumber, contractAddress, slot)];
     }
+
+    /// @notice Decodes the committed journal of a proof
+    /// @dev Exposes `ProofOutputs` in the ABI, from which the operator and guest bindings are generated
+    /// @param journalData The committed journal of the Risc0 Proof
+    /// @return The outputs committed by the proof
+    function decodeProofOutputs(bytes calldata journalData)
+        external
+        pure
+        returns (ProofOutputs memory)
+    {
+        return abi.decode(journalData, (ProofOutputs));
+    }
 }
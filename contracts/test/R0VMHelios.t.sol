@@ -154,6 +154,29 @@ contract R0VMHeliosTest is Test {
         assertEq(helios.getStorageSlot(blockNumber, contractAddress, slot), value);
     }
 
+    function testDecodeProofOutputs() public view {
+        R0VMHelios.StorageSlot[] memory slots = new R0VMHelios.StorageSlot[](1);
+        slots[0] = R0VMHelios.StorageSlot({
+            key: bytes32(uint256(456)),
+            value: bytes32(uint256(789)),
+            contractAddress: address(0xabc)
+        });
+        R0VMHelios.ProofOutputs memory po = R0VMHelios.ProofOutputs({
+            executionStateRoot: bytes32(uint256(11)),
+            newHeader: bytes32(uint256(10)),
+            nextSyncCommitteeHash: bytes32(uint256(12)),
+            newHead: INITIAL_HEAD + 100,
+            prevHeader: INITIAL_HEADER,
+            prevHead: INITIAL_HEAD,
+            syncCommitteeHash: INITIAL_SYNC_COMMITTEE_HASH,
+            startSyncCommitteeHash: INITIAL_SYNC_COMMITTEE_HASH,
+            slots: slots
+        });
+
+        R0VMHelios.ProofOutputs memory decoded = helios.decodeProofOutputs(abi.encode(po));
+        assertEq(abi.encode(decoded), abi.encode(po));
+    }
+
     function testFixedUpdaters() public {
         // Create array with multiple updaters
         address[] memory updatersArray = new address[](3);
@@ -0,0 +1,58 @@
+use alloy_primitives::Bytes;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// `contracts/out`, where `forge build` writes the contract artifacts.
+pub fn contracts_out_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../contracts/out")
+}
+
+/// Run `forge build` in `contracts/`, unless `contracts/out` has already been built.
+///
+/// Needs foundry, and the contracts' submodules checked out.
+pub fn build_contracts() -> Result<()> {
+    // Tests running in parallel build once.
+    static BUILD: Mutex<()> = Mutex::new(());
+    let _build = BUILD.lock().unwrap_or_else(|e| e.into_inner());
+
+    if contracts_out_dir().join("R0VMHelios.sol").exists() {
+        return Ok(());
+    }
+    let contracts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../contracts");
+    let status = Command::new("forge")
+        .arg("build")
+        .current_dir(&contracts_dir)
+        .status()
+        .context("Failed to run `forge build`, install foundry to build the contracts")?;
+    if !status.success() {
+        anyhow::bail!(
+            "`forge build` in {} exited with {}, check out the submodules with `git submodule update --init --recursive`",
+            contracts_dir.display(),
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Creation bytecode of `contract` from its forge artifact, without constructor arguments.
+///
+/// Builds the contracts first if they haven't been.
+pub fn bytecode(contract: &str) -> Result<Bytes> {
+    build_contracts()?;
+    let path = contracts_out_dir()
+        .join(format!("{}.sol", contract))
+        .join(format!("{}.json", contract));
+    let artifact: Value = serde_json::from_slice(
+        &fs::read(&path)
+            .with_context(|| format!("Failed to read {}, run `forge build`", path.display()))?,
+    )?;
+    artifact["bytecode"]["object"]
+        .as_str()
+        .with_context(|| format!("No bytecode in {}", path.display()))?
+        .parse()
+        .with_context(|| format!("Invalid bytecode in {}", path.display()))
+}
@@ -0,0 +1,539 @@
+use alloy::consensus::{Transaction, TxEnvelope};
+use alloy::eips::eip2718::Decodable2718;
+use alloy::rpc::types::{Filter, TransactionRequest};
+use alloy_primitives::{keccak256, logs_bloom, Address, Bloom, Bytes, Log, TxKind, B256, U256};
+use anyhow::{Context, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use reqwest::Url;
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{AccountInfo, ExecutionResult, Output, SpecId, TxEnv};
+use revm::{Database, Evm};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Base fee of every block, in wei.
+pub const BASE_FEE: u128 = 1_000_000_000;
+/// Priority fee the node suggests, in wei.
+pub const PRIORITY_FEE: u128 = 1_000_000_000;
+const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+const BLOCK_TIME_SECS: u64 = 12;
+
+struct Block {
+    number: u64,
+    hash: B256,
+    parent_hash: B256,
+    timestamp: u64,
+    transactions: Vec<B256>,
+}
+
+struct Receipt {
+    block_number: u64,
+    block_hash: B256,
+    from: Address,
+    to: Option<Address>,
+    contract_address: Option<Address>,
+    gas_used: u64,
+    effective_gas_price: u128,
+    success: bool,
+    logs: Vec<Log>,
+    /// Index of the first log in the chain's log list.
+    first_log_index: usize,
+    tx_type: u8,
+}
+
+struct IndexedLog {
+    log: Log,
+    block_number: u64,
+    block_hash: B256,
+    tx_hash: B256,
+    log_index: u64,
+}
+
+struct Chain {
+    db: CacheDB<EmptyDB>,
+    chain_id: u64,
+    blocks: Vec<Block>,
+    receipts: HashMap<B256, Receipt>,
+    logs: Vec<IndexedLog>,
+}
+
+/// An error response of the JSON-RPC shim.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Bytes>,
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            code: -32000,
+            message: format!("{:#}", e),
+            data: None,
+        }
+    }
+}
+
+/// A single-node EVM chain running in-process, standing in for a destination chain.
+///
+/// Every transaction is mined in a block of its own as soon as it is received. The node serves
+/// the subset of the Ethereum JSON-RPC API the operator and the bindings use. Calls always run
+/// against the latest state, whatever block they ask for.
+#[derive(Clone)]
+pub struct EvmNode {
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl EvmNode {
+    /// Start a chain with ID `chain_id` whose genesis block has the Unix timestamp `timestamp`.
+    /// Each block after it is `BLOCK_TIME_SECS` later.
+    pub fn new(chain_id: u64, timestamp: u64) -> Self {
+        let genesis = Block {
+            number: 0,
+            hash: block_hash(chain_id, 0),
+            parent_hash: B256::ZERO,
+            timestamp,
+            transactions: Vec::new(),
+        };
+        Self {
+            chain: Arc::new(Mutex::new(Chain {
+                db: CacheDB::new(EmptyDB::default()),
+                chain_id,
+                blocks: vec![genesis],
+                receipts: HashMap::new(),
+                logs: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn fund(&self, address: Address, balance: U256) {
+        let mut chain = self.chain.lock().unwrap();
+        let mut info = chain.db.basic(address).unwrap().unwrap_or_default();
+        info.balance = balance;
+        chain.db.insert_account_info(address, info);
+    }
+
+    /// Deploy `code` from `from` without a signed transaction, and return the contract address.
+    pub fn deploy(&self, from: Address, code: Bytes) -> Result<Address> {
+        let mut chain = self.chain.lock().unwrap();
+        let tx = TxEnv {
+            caller: from,
+            gas_limit: BLOCK_GAS_LIMIT,
+            transact_to: TxKind::Create,
+            data: code,
+            ..Default::default()
+        };
+        match chain.execute(tx, true)? {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => {
+                chain.mine(Vec::new());
+                Ok(address)
+            }
+            result => Err(anyhow::anyhow!("Deployment failed: {:?}", result)),
+        }
+    }
+
+    /// Mine an empty block.
+    pub fn mine(&self) -> u64 {
+        self.chain.lock().unwrap().mine(Vec::new())
+    }
+
+    /// Serve the JSON-RPC API on a local port, and mine an empty block every `block_interval` so
+    /// that transactions gather confirmations. Returns the URL of the API.
+    pub async fn spawn(&self, block_interval: Duration) -> Result<Url> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?).parse()?;
+
+        let app = Router::new()
+            .route("/", post(handle_rpc))
+            .with_state(self.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let node = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(block_interval).await;
+                node.mine();
+            }
+        });
+        Ok(url)
+    }
+
+    fn handle(&self, request: Value) -> Value {
+        let id = request["id"].clone();
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = request["params"].clone();
+
+        let result = self.chain.lock().unwrap().dispatch(method, &params);
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => {
+                let mut error = json!({ "code": e.code, "message": e.message });
+                if let Some(data) = e.data {
+                    error["data"] = json!(data);
+                }
+                json!({ "jsonrpc": "2.0", "id": id, "error": error })
+            }
+        }
+    }
+}
+
+async fn handle_rpc(State(node): State<EvmNode>, Json(body): Json<Value>) -> Json<Value> {
+    match body {
+        Value::Array(requests) => Json(Value::Array(
+            requests
+                .into_iter()
+                .map(|request| node.handle(request))
+                .collect(),
+        )),
+        request => Json(node.handle(request)),
+    }
+}
+
+impl Chain {
+    fn latest(&self) -> &Block {
+        self.blocks.last().unwrap()
+    }
+
+    /// Run `tx` in the next block, committing its changes if `commit` is set. Uncommitted calls
+    /// run with a zero base fee, so they don't need a funded caller.
+    fn execute(&mut self, tx: TxEnv, commit: bool) -> Result<ExecutionResult> {
+        let chain_id = self.chain_id;
+        let number = self.blocks.len() as u64;
+        let timestamp = self.latest().timestamp + BLOCK_TIME_SECS;
+        let basefee = if commit { BASE_FEE } else { 0 };
+
+        let mut evm = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(SpecId::CANCUN)
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+            .modify_block_env(|block| {
+                block.number = U256::from(number);
+                block.timestamp = U256::from(timestamp);
+                block.basefee = U256::from(basefee);
+                block.gas_limit = U256::from(BLOCK_GAS_LIMIT);
+            })
+            .modify_tx_env(|env| *env = tx)
+            .build();
+
+        let result = if commit {
+            evm.transact_commit()
+        } else {
+            evm.transact().map(|result| result.result)
+        };
+        result.map_err(|e| anyhow::anyhow!("Transaction is invalid: {:?}", e))
+    }
+
+    fn mine(&mut self, transactions: Vec<B256>) -> u64 {
+        let parent = self.latest();
+        let number = parent.number + 1;
+        let block = Block {
+            number,
+            hash: block_hash(self.chain_id, number),
+            parent_hash: parent.hash,
+            timestamp: parent.timestamp + BLOCK_TIME_SECS,
+            transactions,
+        };
+        self.blocks.push(block);
+        number
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "eth_chainId" => Ok(quantity(self.chain_id)),
+            "net_version" => Ok(json!(self.chain_id.to_string())),
+            "eth_blockNumber" => Ok(quantity(self.latest().number)),
+            "eth_gasPrice" => Ok(quantity(BASE_FEE + PRIORITY_FEE)),
+            "eth_maxPriorityFeePerGas" => Ok(quantity(PRIORITY_FEE)),
+            "eth_feeHistory" => Ok(self.fee_history(params)?),
+            "eth_getBalance" => {
+                let info = self.account(param(params, 0)?)?;
+                Ok(json!(info.balance))
+            }
+            "eth_getTransactionCount" => {
+                let info = self.account(param(params, 0)?)?;
+                Ok(quantity(info.nonce))
+            }
+            "eth_getCode" => {
+                let info = self.account(param(params, 0)?)?;
+                let code = info
+                    .code
+                    .map(|code| code.original_bytes())
+                    .unwrap_or_default();
+                Ok(json!(code))
+            }
+            "eth_call" => {
+                let output = self.call(param(params, 0)?)?.1;
+                Ok(json!(output))
+            }
+            "eth_estimateGas" => {
+                let gas_used = self.call(param(params, 0)?)?.0;
+                Ok(quantity(gas_used + gas_used / 3))
+            }
+            "eth_sendRawTransaction" => {
+                let raw: Bytes = param(params, 0)?;
+                Ok(json!(self.send_raw_transaction(&raw)?))
+            }
+            "eth_getTransactionReceipt" => {
+                let tx_hash: B256 = param(params, 0)?;
+                Ok(self.receipt(tx_hash).unwrap_or(Value::Null))
+            }
+            "eth_getBlockByNumber" => {
+                let tag: String = param(params, 0)?;
+                let number = match tag.as_str() {
+                    "latest" | "pending" | "safe" | "finalized" => self.latest().number,
+                    "earliest" => 0,
+                    number => parse_quantity(number)?,
+                };
+                Ok(self
+                    .blocks
+                    .get(number as usize)
+                    .map(block_json)
+                    .unwrap_or(Value::Null))
+            }
+            "eth_getLogs" => {
+                let filter: Filter = param(params, 0)?;
+                Ok(self.logs(&filter))
+            }
+            _ => Err(RpcError {
+                code: -32601,
+                message: format!("Method {} not supported", method),
+                data: None,
+            }),
+        }
+    }
+
+    fn account(&mut self, address: Address) -> Result<AccountInfo, RpcError> {
+        Ok(self.db.basic(address).unwrap().unwrap_or_default())
+    }
+
+    /// Run the call `request` against the latest state, returning its gas used and output.
+    fn call(&mut self, request: TransactionRequest) -> Result<(u64, Bytes), RpcError> {
+        let tx = TxEnv {
+            caller: request.from.unwrap_or_default(),
+            gas_limit: request.gas.unwrap_or(BLOCK_GAS_LIMIT),
+            transact_to: request.to.unwrap_or(TxKind::Create),
+            value: request.value.unwrap_or_default(),
+            data: request.input.input().cloned().unwrap_or_default(),
+            ..Default::default()
+        };
+        match self.execute(tx, false)? {
+            ExecutionResult::Success {
+                gas_used, output, ..
+            } => Ok((gas_used, output.into_data())),
+            ExecutionResult::Revert { output, .. } => Err(RpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(output),
+            }),
+            ExecutionResult::Halt { reason, .. } => Err(RpcError {
+                code: -32000,
+                message: format!("execution halted: {:?}", reason),
+                data: None,
+            }),
+        }
+    }
+
+    fn send_raw_transaction(&mut self, raw: &[u8]) -> Result<B256, RpcError> {
+        let envelope = TxEnvelope::decode_2718(&mut &raw[..])
+            .map_err(|e| anyhow::anyhow!("Invalid transaction: {}", e))?;
+        let from = envelope
+            .recover_signer()
+            .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))?;
+        let tx_hash = *envelope.tx_hash();
+
+        let tx = TxEnv {
+            caller: from,
+            gas_limit: envelope.gas_limit(),
+            gas_price: U256::from(envelope.max_fee_per_gas()),
+            gas_priority_fee: envelope.max_priority_fee_per_gas().map(U256::from),
+            transact_to: envelope.kind(),
+            value: envelope.value(),
+            data: envelope.input().clone(),
+            nonce: Some(envelope.nonce()),
+            chain_id: envelope.chain_id(),
+            ..Default::default()
+        };
+        let result = self.execute(tx, true)?;
+
+        let number = self.mine(vec![tx_hash]);
+        let block_hash = self.latest().hash;
+        let effective_gas_price = envelope
+            .max_fee_per_gas()
+            .min(BASE_FEE + envelope.max_priority_fee_per_gas().unwrap_or(0));
+        let (success, gas_used, logs, contract_address) = match result {
+            ExecutionResult::Success {
+                gas_used,
+                logs,
+                output,
+                ..
+            } => {
+                let contract_address = match output {
+                    Output::Create(_, address) => address,
+                    Output::Call(_) => None,
+                };
+                (true, gas_used, logs, contract_address)
+            }
+            ExecutionResult::Revert { gas_used, .. } | ExecutionResult::Halt { gas_used, .. } => {
+                (false, gas_used, Vec::new(), None)
+            }
+        };
+
+        let first_log_index = self.logs.len();
+        for log in &logs {
+            self.logs.push(IndexedLog {
+                log: log.clone(),
+                block_number: number,
+                block_hash,
+                tx_hash,
+                log_index: (self.logs.len() - first_log_index) as u64,
+            });
+        }
+        self.receipts.insert(
+            tx_hash,
+            Receipt {
+                block_number: number,
+                block_hash,
+                from,
+                to: envelope.to(),
+                contract_address,
+                gas_used,
+                effective_gas_price,
+                success,
+                logs,
+                first_log_index,
+                tx_type: envelope.tx_type() as u8,
+            },
+        );
+        Ok(tx_hash)
+    }
+
+    fn receipt(&self, tx_hash: B256) -> Option<Value> {
+        let receipt = self.receipts.get(&tx_hash)?;
+        let logs: Vec<Value> = self.logs
+            [receipt.first_log_index..receipt.first_log_index + receipt.logs.len()]
+            .iter()
+            .map(log_json)
+            .collect();
+        Some(json!({
+            "transactionHash": tx_hash,
+            "transactionIndex": "0x0",
+            "blockHash": receipt.block_hash,
+            "blockNumber": quantity(receipt.block_number),
+            "from": receipt.from,
+            "to": receipt.to,
+            "contractAddress": receipt.contract_address,
+            "cumulativeGasUsed": quantity(receipt.gas_used),
+            "gasUsed": quantity(receipt.gas_used),
+            "effectiveGasPrice": quantity(receipt.effective_gas_price),
+            "logs": logs,
+            "logsBloom": logs_bloom(receipt.logs.iter()),
+            "status": quantity(receipt.success as u64),
+            "type": quantity(receipt.tx_type),
+        }))
+    }
+
+    fn logs(&self, filter: &Filter) -> Value {
+        let latest = self.latest().number;
+        let from_block = filter.get_from_block().unwrap_or(latest);
+        let to_block = filter.get_to_block().unwrap_or(latest);
+
+        let logs: Vec<Value> = self
+            .logs
+            .iter()
+            .filter(|entry| (from_block..=to_block).contains(&entry.block_number))
+            .filter(|entry| filter.address.matches(&entry.log.address))
+            .filter(|entry| {
+                filter.topics.iter().enumerate().all(|(i, topic)| {
+                    topic.is_empty()
+                        || entry
+                            .log
+                            .topics()
+                            .get(i)
+                            .is_some_and(|value| topic.matches(value))
+                })
+            })
+            .map(log_json)
+            .collect();
+        Value::Array(logs)
+    }
+
+    fn fee_history(&self, params: &Value) -> Result<Value, RpcError> {
+        let block_count = match &params[0] {
+            Value::String(count) => parse_quantity(count)?,
+            count => count.as_u64().unwrap_or(1),
+        };
+        let percentiles = params[2].as_array().map_or(0, Vec::len);
+
+        let newest = self.latest().number;
+        let block_count = block_count.min(newest + 1);
+        Ok(json!({
+            "oldestBlock": quantity(newest + 1 - block_count),
+            "baseFeePerGas": vec![quantity(BASE_FEE); block_count as usize + 1],
+            "gasUsedRatio": vec![0.5; block_count as usize],
+            "reward": vec![vec![quantity(PRIORITY_FEE); percentiles]; block_count as usize],
+        }))
+    }
+}
+
+fn block_hash(chain_id: u64, number: u64) -> B256 {
+    keccak256([chain_id.to_be_bytes(), number.to_be_bytes()].concat())
+}
+
+fn block_json(block: &Block) -> Value {
+    json!({
+        "number": quantity(block.number),
+        "hash": block.hash,
+        "parentHash": block.parent_hash,
+        "timestamp": quantity(block.timestamp),
+        "gasLimit": quantity(BLOCK_GAS_LIMIT),
+        "gasUsed": "0x0",
+        "baseFeePerGas": quantity(BASE_FEE),
+        "miner": Address::ZERO,
+        "difficulty": "0x0",
+        "totalDifficulty": "0x0",
+        "extraData": "0x",
+        "nonce": "0x0000000000000000",
+        "mixHash": B256::ZERO,
+        "sha3Uncles": B256::ZERO,
+        "stateRoot": B256::ZERO,
+        "transactionsRoot": B256::ZERO,
+        "receiptsRoot": B256::ZERO,
+        "logsBloom": Bloom::ZERO,
+        "size": "0x0",
+        "uncles": [],
+        "transactions": block.transactions,
+    })
+}
+
+fn log_json(entry: &IndexedLog) -> Value {
+    json!({
+        "address": entry.log.address,
+        "topics": entry.log.topics(),
+        "data": entry.log.data.data,
+        "blockNumber": quantity(entry.block_number),
+        "blockHash": entry.block_hash,
+        "transactionHash": entry.tx_hash,
+        "transactionIndex": "0x0",
+        "logIndex": quantity(entry.log_index),
+        "removed": false,
+    })
+}
+
+fn quantity(value: impl Into<u128>) -> Value {
+    json!(format!("{:#x}", value.into()))
+}
+
+fn parse_quantity(value: &str) -> Result<u64, RpcError> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    Ok(u64::from_str_radix(digits, 16).with_context(|| format!("Invalid quantity {}", value))?)
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    Ok(serde_json::from_value(params[index].clone())
+        .with_context(|| format!("Invalid parameter {}", index))?)
+}
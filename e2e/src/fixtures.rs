@@ -0,0 +1,189 @@
+use alloy_primitives::keccak256;
+use anyhow::{Context, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use log::{info, warn};
+use r0vm_helios_mock_beacon::MockBeacon;
+use r0vm_helios_synthetic::ExecutionState;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Upstream nodes a [`FixtureServer`] records from.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub consensus_rpc: String,
+    pub execution_rpc: String,
+}
+
+struct Fixtures {
+    dir: PathBuf,
+    upstream: Option<Upstream>,
+    /// Answers `eth_getProof` instead of the fixtures, for a synthetic chain.
+    execution_state: Option<ExecutionState>,
+    client: reqwest::Client,
+}
+
+/// Replays recorded beacon API and execution JSON-RPC responses from a fixture directory.
+///
+/// The beacon API is served at the root of the server's URL by a [`MockBeacon`] with the
+/// fixtures in `beacon/`, and the execution JSON-RPC API at `/execution`, with responses stored
+/// by method and parameters in `execution/`. When recording, requests are forwarded to the
+/// upstream nodes and their responses stored, so running a client against a recording server
+/// once captures exactly the fixtures it needs.
+///
+/// The fixtures of a synthetic chain are written with
+/// [`SyntheticChain::write_fixtures`](r0vm_helios_synthetic::SyntheticChain::write_fixtures), and
+/// its execution state proven on demand.
+pub struct FixtureServer {
+    beacon: MockBeacon,
+    fixtures: Arc<Fixtures>,
+}
+
+impl FixtureServer {
+    /// Serve the fixtures in `dir`.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self::new(MockBeacon::replay(dir.join("beacon")), dir, None, None)
+    }
+
+    /// Forward requests to `upstream`, and store the responses in `dir`.
+    pub fn record(dir: impl Into<PathBuf>, upstream: Upstream) -> Self {
+        let dir = dir.into();
+        let beacon = MockBeacon::record(dir.join("beacon"), upstream.consensus_rpc.clone());
+        Self::new(beacon, dir, Some(upstream), None)
+    }
+
+    /// Serve the beacon fixtures of a synthetic chain in `dir`, and prove storage from
+    /// `execution_state`, the state every execution payload of the chain commits to.
+    pub fn synthetic(dir: impl Into<PathBuf>, execution_state: ExecutionState) -> Self {
+        let dir = dir.into();
+        Self::new(
+            MockBeacon::replay(dir.join("beacon")),
+            dir,
+            None,
+            Some(execution_state),
+        )
+    }
+
+    fn new(
+        beacon: MockBeacon,
+        dir: PathBuf,
+        upstream: Option<Upstream>,
+        execution_state: Option<ExecutionState>,
+    ) -> Self {
+        Self {
+            beacon,
+            fixtures: Arc::new(Fixtures {
+                dir,
+                upstream,
+                execution_state,
+                client: reqwest::Client::new(),
+            }),
+        }
+    }
+
+    /// The mock beacon node serving the beacon API, to inject faults into.
+    pub fn beacon(&self) -> &MockBeacon {
+        &self.beacon
+    }
+
+    /// Serve on a local port, and return the URL of the beacon API.
+    pub async fn spawn(&self) -> Result<Url> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?).parse()?;
+
+        let app = Router::new()
+            .route("/execution", post(execution))
+            .with_state(self.fixtures.clone())
+            .merge(self.beacon.router());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(url)
+    }
+}
+
+/// URL of the execution JSON-RPC API of the fixture server at `url`.
+pub fn execution_url(url: &Url) -> Url {
+    url.join("execution").unwrap()
+}
+
+async fn execution(
+    State(fixtures): State<Arc<Fixtures>>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    let id = request["id"].clone();
+    match fixtures.execution(&request).await {
+        Ok(mut response) => {
+            response["id"] = id;
+            Json(response)
+        }
+        Err(e) => {
+            warn!("{:#}", e);
+            Json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32000, "message": format!("{:#}", e) },
+            }))
+        }
+    }
+}
+
+impl Fixtures {
+    async fn execution(&self, request: &Value) -> Result<Value> {
+        let method = request["method"].as_str().context("Missing method")?;
+        if let Some(state) = &self.execution_state {
+            return synthetic_execution(state, method, &request["params"]);
+        }
+
+        let params = serde_json::to_vec(&request["params"])?;
+        let file = self.dir.join("execution").join(format!(
+            "{}-{}.json",
+            method,
+            &keccak256(params).to_string()[2..18]
+        ));
+        let Some(upstream) = &self.upstream else {
+            return read_fixture(&file)
+                .with_context(|| format!("No execution fixture for {} {}", method, request));
+        };
+
+        let mut response: Value = self
+            .client
+            .post(&upstream.execution_rpc)
+            .json(request)
+            .send()
+            .await?
+            .json()
+            .await?;
+        response["id"] = Value::Null;
+        info!("Recorded {}", method);
+        write_fixture(&file, &response)?;
+        Ok(response)
+    }
+}
+
+/// Answer an execution JSON-RPC request from a synthetic execution state, which is the same at
+/// every block.
+fn synthetic_execution(state: &ExecutionState, method: &str, params: &Value) -> Result<Value> {
+    let result = match method {
+        "eth_getProof" => {
+            let address = serde_json::from_value(params[0].clone()).context("Invalid address")?;
+            let slots: Vec<_> =
+                serde_json::from_value(params[1].clone()).context("Invalid storage keys")?;
+            state.proof_response(address, &slots)
+        }
+        _ => anyhow::bail!("Synthetic chains don't serve {}", method),
+    };
+    Ok(json!({ "jsonrpc": "2.0", "id": Value::Null, "result": result }))
+}
+
+fn read_fixture<T: for<'de> Deserialize<'de>>(file: &Path) -> Result<T> {
+    Ok(serde_json::from_slice(&fs::read(file)?)?)
+}
+
+fn write_fixture(file: &Path, value: &impl Serialize) -> Result<()> {
+    fs::create_dir_all(file.parent().unwrap())?;
+    fs::write(file, serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
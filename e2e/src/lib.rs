@@ -0,0 +1,37 @@
+//! Harness for end-to-end tests of the operator: an in-process EVM chain as the destination, and
+//! a server replaying synthetic or recorded beacon node responses as the source.
+
+use alloy::sol_types::{SolConstructor, SolValue};
+use alloy_primitives::{Address, FixedBytes};
+use anyhow::Result;
+use r0vm_helios_bindings::R0VMHelios;
+
+pub mod artifacts;
+pub mod evm;
+pub mod fixtures;
+
+/// Selector the mock verifier expects fake receipts to be encoded with, as
+/// `risc0_ethereum_contracts::encode_seal` does in dev mode.
+pub const MOCK_SELECTOR: [u8; 4] = [0xff; 4];
+
+/// Deploy a `RiscZeroMockVerifier` and an `R0VMHelios` contract using it from the forge
+/// artifacts, and return the address of the light client.
+pub fn deploy_light_client(
+    node: &evm::EvmNode,
+    deployer: Address,
+    mut params: R0VMHelios::InitParams,
+) -> Result<Address> {
+    let verifier_code = [
+        artifacts::bytecode("RiscZeroMockVerifier")?.to_vec(),
+        FixedBytes::<4>::from(MOCK_SELECTOR).abi_encode(),
+    ]
+    .concat();
+    params.verifier = node.deploy(deployer, verifier_code.into())?;
+
+    let helios_code = [
+        artifacts::bytecode("R0VMHelios")?.to_vec(),
+        R0VMHelios::constructorCall { params }.abi_encode(),
+    ]
+    .concat();
+    node.deploy(deployer, helios_code.into())
+}
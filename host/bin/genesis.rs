@@ -1,9 +1,10 @@
-use alloy::signers::local::PrivateKeySigner;
 use alloy_primitives::{Address, B256};
 use anyhow::Result;
 /// Generate genesis parameters for light client contract
 use clap::Parser;
 use r0vm_helios_methods::R0VM_HELIOS_GUEST_ID;
+use r0vm_helios_script::consistency::{SECONDS_PER_SLOT, SLOTS_PER_EPOCH, SLOTS_PER_PERIOD};
+use r0vm_helios_script::secrets::signer_from_env;
 use r0vm_helios_script::{get_checkpoint, get_client, get_latest_checkpoint};
 use risc0_zkvm::Digest;
 use serde::{Deserialize, Serialize};
@@ -57,12 +58,12 @@ pub async fn main() -> Result<()> {
 
     let checkpoint;
     if let Some(temp_slot) = args.slot {
-        checkpoint = get_checkpoint(temp_slot).await;
+        checkpoint = get_checkpoint(temp_slot).await?;
     } else {
-        checkpoint = get_latest_checkpoint().await;
+        checkpoint = get_latest_checkpoint().await?;
     }
 
-    let helios_client = get_client(checkpoint).await;
+    let helios_client = get_client(checkpoint).await?;
     let finalized_header = helios_client
         .store
         .finalized_header
@@ -77,9 +78,6 @@ pub async fn main() -> Result<()> {
         .tree_hash_root();
     let genesis_time = helios_client.config.chain.genesis_time;
     let genesis_root = helios_client.config.chain.genesis_root;
-    const SECONDS_PER_SLOT: u64 = 12;
-    const SLOTS_PER_EPOCH: u64 = 32;
-    const SLOTS_PER_PERIOD: u64 = SLOTS_PER_EPOCH * 256;
     let source_chain_id: u64 = match env::var("SOURCE_CHAIN_ID") {
         Ok(val) => val.parse().unwrap(),
         Err(_) => {
@@ -121,9 +119,8 @@ pub async fn main() -> Result<()> {
         B256::from_slice(Digest::from(R0VM_HELIOS_GUEST_ID).as_bytes()).to_string();
     genesis_config.verifier = Address::ZERO.to_string();
 
-    // Get the account associated with the private key.
-    let private_key = env::var("PRIVATE_KEY").unwrap();
-    let signer: PrivateKeySigner = private_key.parse().expect("Failed to parse private key");
+    // Get the account associated with the deployer key.
+    let signer = signer_from_env("")?;
     let deployer_address = signer.address();
 
     // Attempt using the GUARDIAN_ADDRESS, otherwise default to the address derived from the private key.
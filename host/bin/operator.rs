@@ -1,305 +1,409 @@
-use alloy::providers::Provider;
-use alloy::{
-    network::EthereumWallet, primitives::Address, providers::ProviderBuilder,
-    signers::local::PrivateKeySigner, sol,
-};
-use alloy_primitives::{B256, U256};
+use alloy::sol_types::SolValue;
+use alloy_primitives::B256;
 use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use helios_consensus_core::calc_sync_period;
 use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
-use helios_ethereum::consensus::Inner;
-use helios_ethereum::rpc::http_rpc::HttpRpc;
-use helios_ethereum::rpc::ConsensusRpc;
 use log::{error, info};
-use r0vm_helios_methods::R0VM_HELIOS_GUEST_ELF;
-use r0vm_helios_primitives::types::{ContractStorage, ProofInputs};
+use r0vm_helios_methods::R0VM_HELIOS_GUEST_ID;
+use r0vm_helios_primitives::types::ProofOutputs;
+use r0vm_helios_script::archive::ProofArchive;
+use r0vm_helios_script::config::OperatorConfig;
+use r0vm_helios_script::consistency::{check_destination, ExpectedParams};
+use r0vm_helios_script::db::JobStage;
+use r0vm_helios_script::events::FinalityEvents;
+use r0vm_helios_script::pipeline::{self, archive_dir, receipt_kind_from_env, OperatorContext};
+use r0vm_helios_script::prover::{
+    self, prove_async, prover_from_env, DevModeProver, HeliosProver, ReceiptKind,
+};
+use r0vm_helios_script::status::{serve_http, AppState};
 use r0vm_helios_script::*;
-use reqwest::Url;
-use risc0_zkvm::{default_prover, ExecutorEnv, ProverOpts, Receipt};
+use risc0_zkvm::{Digest, Receipt};
 use std::env;
-use std::time::Duration;
-use tree_hash::TreeHash;
-
-struct R0VMHeliosOperator {
-    wallet: EthereumWallet,
-    rpc_url: Url,
-    contract_address: Address,
-    relayer_address: Address,
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+/// Cancel `shutdown` on SIGINT or SIGTERM.
+async fn wait_for_signal(shutdown: CancellationToken) {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down"),
+        _ = sigterm.recv() => info!("Received SIGTERM, shutting down"),
+    }
+    shutdown.cancel();
 }
 
-sol! {
-    #[allow(missing_docs)]
-    #[sol(rpc)]
-    contract R0VMHelios {
-        bytes32 public immutable GENESIS_VALIDATORS_ROOT;
-        uint256 public immutable GENESIS_TIME;
-        uint256 public immutable SECONDS_PER_SLOT;
-        uint256 public immutable SLOTS_PER_PERIOD;
-        uint32 public immutable SOURCE_CHAIN_ID;
-        uint256 public head;
-        mapping(uint256 => bytes32) public syncCommittees;
-        mapping(uint256 => bytes32) public executionStateRoots;
-        mapping(uint256 => bytes32) public headers;
-        mapping(bytes32 => bytes32) public storageValues;
-        bytes32 public heliosImageID;
-        address public verifier;
-
-        struct StorageSlot {
-            bytes32 key;
-            bytes32 value;
-            address contractAddress;
-        }
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Relay light client updates to the R0VMHelios contracts.")]
+pub struct OperatorArgs {
+    /// TOML config file. Defaults to `OPERATOR_CONFIG`. Environment variables override it.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
 
-        struct ProofOutputs {
-            bytes32 executionStateRoot;
-            bytes32 newHeader;
-            bytes32 nextSyncCommitteeHash;
-            uint256 newHead;
-            bytes32 prevHeader;
-            uint256 prevHead;
-            bytes32 syncCommitteeHash;
-            bytes32 startSyncCommitteeHash;
-            StorageSlot[] slots;
-        }
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run the operator. This is the default.
+    Run,
+    /// Relay a single round of updates and exit, for cron jobs. Fails if any update fails.
+    Once,
+    /// Print the head of every destination contract against the source chain's finalized head.
+    Status,
+    /// Fetch the inputs of the next update and execute the guest on them, without proving or
+    /// relaying.
+    DryRun {
+        /// Head to build the update from. Defaults to the head of the first destination.
+        #[arg(long)]
+        head: Option<u64>,
+    },
+    /// Fetch the inputs of the next update from a contract head and write them to a file, for
+    /// proving on a machine without RPC access.
+    FetchInputs {
+        /// Head to prove the update from. Defaults to the head of the first destination.
+        #[arg(long)]
+        head: Option<u64>,
+        /// File to write the CBOR encoded `ProofInputs` to.
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Prove the inputs written by `fetch-inputs` and write the receipt to a file.
+    Prove {
+        /// File holding the CBOR encoded `ProofInputs`.
+        #[arg(long)]
+        inputs: PathBuf,
+        /// File to write the CBOR encoded receipt to.
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Relay a receipt written by `prove` to the destinations of its job.
+    Relay {
+        /// File holding the CBOR encoded receipt.
+        #[arg(long)]
+        receipt: PathBuf,
+    },
+    /// Re-verify every archived proof and check that they form an unbroken chain of headers.
+    VerifyArchive {
+        /// Archive to verify. Defaults to `ARCHIVE_DIR`.
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
 
-        event HeadUpdate(uint256 indexed slot, bytes32 indexed root);
-        event SyncCommitteeUpdate(uint256 indexed period, bytes32 indexed root);
-        event StorageSlotVerified(uint256 indexed slot, bytes32 indexed key, bytes32 value, address contractAddress);
+fn main() -> Result<()> {
+    // The local prover proves in a child process running this binary, which must not start the
+    // async runtime.
+    prover::run_if_prover_child();
+    tokio::runtime::Runtime::new()?.block_on(operator())
+}
 
-        function update(bytes calldata seal, bytes calldata journalData, uint256 head) external;
-        function getSyncCommitteePeriod(uint256 slot) internal view returns (uint256);
-        function getCurrentSlot() internal view returns (uint256);
-        function getCurrentEpoch() internal view returns (uint256);
-        function computeStorageKey(uint256 blockNumber, address contractAddress, bytes32 slot) public pure returns (bytes32);
-        function getStorageSlot(uint256 blockNumber, address contractAddress, bytes32 slot) external view returns (bytes32);
+async fn operator() -> Result<()> {
+    env::set_var("RUST_LOG", "info");
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let args = OperatorArgs::parse();
+
+    let config = args
+        .config
+        .or_else(|| env::var("OPERATOR_CONFIG").ok().map(PathBuf::from));
+    if let Some(config) = config {
+        OperatorConfig::load(&config)?.apply_to_env();
     }
-}
 
-impl R0VMHeliosOperator {
-    pub async fn new() -> Self {
-        dotenv::dotenv().ok();
-
-        let rpc_url = env::var("DEST_RPC_URL")
-            .expect("DEST_RPC_URL not set")
-            .parse()
-            .unwrap();
-
-        let private_key = env::var("PRIVATE_KEY").expect("PRIVATE_KEY not set");
-        let contract_address: Address = env::var("CONTRACT_ADDRESS")
-            .expect("CONTRACT_ADDRESS not set")
-            .parse()
-            .unwrap();
-        let signer: PrivateKeySigner = private_key.parse().expect("Failed to parse private key");
-        let relayer_address = signer.address();
-        let wallet = EthereumWallet::from(signer);
-
-        Self {
-            wallet,
-            rpc_url,
-            contract_address,
-            relayer_address,
-        }
+    match args.command.unwrap_or(Command::Run) {
+        Command::Run => run(false).await,
+        Command::Once => run(true).await,
+        Command::Status => status().await,
+        Command::DryRun { head } => dry_run(head).await,
+        Command::FetchInputs { head, out } => fetch_inputs(head, out).await,
+        Command::Prove { inputs, out } => prove_inputs(inputs, out),
+        Command::Relay { receipt } => relay_receipt(receipt).await,
+        Command::VerifyArchive { dir } => verify_archive(dir.unwrap_or_else(archive_dir)),
     }
+}
 
-    /// Fetch values and generate an 'update' proof for the R0VM Helios contract.
-    async fn request_update(
-        &self,
-        mut client: Inner<MainnetConsensusSpec, HttpRpc>,
-    ) -> Result<Option<Receipt>> {
-        // Fetch required values.
-        let provider = ProviderBuilder::new().on_http(self.rpc_url.clone());
-        let contract = R0VMHelios::new(self.contract_address, &provider);
-        let head: u64 = contract
-            .head()
-            .call()
-            .await
-            .unwrap()
-            .head
-            .try_into()
-            .unwrap();
-        let period: u64 = contract
-            .getSyncCommitteePeriod(U256::from(head))
-            .call()
-            .await
-            .unwrap()
-            ._0
-            .try_into()
-            .unwrap();
-        let contract_next_sync_committee = contract
-            .syncCommittees(U256::from(period + 1))
-            .call()
-            .await
-            .unwrap()
-            ._0;
-
-        // Setup client.
-        let mut sync_committee_updates = get_updates(&client).await;
-        let finality_update = client.rpc.get_finality_update().await.unwrap();
-
-        // Check if contract is up to date
-        let latest_block = finality_update.finalized_header().beacon().slot;
-        if latest_block <= head {
-            info!("Contract is up to date. Nothing to update.");
-            return Ok(None);
-        }
+/// Fetch the inputs of an update from `head` and record them as a job, like the fetch stage does,
+/// writing them to `out` as well.
+///
+/// The job is addressed to every destination whose contract is at `head`.
+async fn fetch_inputs(head: Option<u64>, out: PathBuf) -> Result<()> {
+    let ctx = OperatorContext::new(CancellationToken::new(), 0)?;
+    let (head, names) = destinations_at(&ctx, head).await?;
+    let destination = ctx.destination(&names[0]).unwrap();
+
+    let mut client = ctx.bootstrap_from_contract(destination, head).await?;
+    let Some(job) = ctx.request_update(&mut client, destination, names).await? else {
+        return Ok(());
+    };
+
+    fs::write(&out, ctx.db.load_inputs(&job)?)?;
+    info!(
+        "Wrote inputs of job {} to {}. Prove them with `prove --inputs {}`",
+        job.id,
+        out.display(),
+        out.display()
+    );
+    Ok(())
+}
 
-        // Optimization:
-        // Skip processing update inside program if next_sync_committee is already stored in contract.
-        // We must still apply the update locally to "sync" the helios client, this is due to
-        // next_sync_committee not being stored when the helios client is bootstrapped.
-        if !sync_committee_updates.is_empty() {
-            let next_sync_committee = B256::from_slice(
-                sync_committee_updates[0]
-                    .next_sync_committee()
-                    .tree_hash_root()
-                    .as_ref(),
-            );
-
-            if contract_next_sync_committee == next_sync_committee {
-                println!("Applying optimization, skipping update");
-                let temp_update = sync_committee_updates.remove(0);
-
-                client.verify_update(&temp_update).unwrap(); // Panics if not valid
-                client.apply_update(&temp_update);
-            }
+/// The destinations whose contract is at `head`, which defaults to the head of the first
+/// destination.
+async fn destinations_at(ctx: &OperatorContext, head: Option<u64>) -> Result<(u64, Vec<String>)> {
+    let mut names = Vec::new();
+    let mut from_head = head;
+    for destination in &ctx.destinations {
+        let contract_head = ctx.contract_head(destination).await?;
+        if *from_head.get_or_insert(contract_head) == contract_head {
+            names.push(destination.name.clone());
         }
+    }
 
-        // Create program inputs
-        let expected_current_slot = client.expected_current_slot();
-        let inputs = ProofInputs {
-            sync_committee_updates,
-            finality_update,
-            expected_current_slot,
-            store: client.store.clone(),
-            genesis_root: client.config.chain.genesis_root,
-            forks: client.config.forks.clone(),
-            contract_storage_slots: ContractStorage {
-                address: todo!(),
-                expected_value: todo!(),
-                mpt_proof: todo!(),
-                storage_slots: todo!(),
-            },
-        };
-        let encoded_proof_inputs = serde_cbor::to_vec(&inputs)?;
-
-        // Generate proof.
-        let proof = tokio::task::spawn_blocking(move || {
-            let env = ExecutorEnv::builder()
-                .write_frame(&encoded_proof_inputs)
-                .build()?;
-            default_prover().prove_with_opts(env, R0VM_HELIOS_GUEST_ELF, &ProverOpts::groth16())
-        })
-        .await
-        .unwrap()
-        .context("proving failed")?;
-
-        info!("Attempting to update to new head block: {:?}", latest_block);
-        Ok(Some(proof.receipt))
+    let head = from_head.context("No destinations configured")?;
+    if names.is_empty() {
+        anyhow::bail!("No destination contract is at head {}", head);
     }
+    Ok((head, names))
+}
 
-    /// Relay an update proof to the R0VM Helios contract.
-    async fn relay_update(&self, proof: Receipt, head: u64) -> Result<()> {
-        let seal = risc0_ethereum_contracts::encode_seal(&proof)?;
-
-        let wallet_filler = ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(self.wallet.clone())
-            .on_http(self.rpc_url.clone());
-        let contract = R0VMHelios::new(self.contract_address, wallet_filler.clone());
-
-        let nonce = wallet_filler
-            .get_transaction_count(self.relayer_address)
-            .await?;
-
-        // Wait for 3 required confirmations with a timeout of 60 seconds.
-        const NUM_CONFIRMATIONS: u64 = 3;
-        const TIMEOUT_SECONDS: u64 = 60;
-        let receipt = contract
-            .update(
-                seal.into(),
-                proof.journal.bytes.into(),
-                head.try_into().unwrap(),
-            )
-            .nonce(nonce)
-            .send()
-            .await?
-            .with_required_confirmations(NUM_CONFIRMATIONS)
-            .with_timeout(Some(Duration::from_secs(TIMEOUT_SECONDS)))
-            .get_receipt()
-            .await?;
-
-        // If status is false, it reverted.
-        if !receipt.status() {
-            error!("Transaction reverted!");
-            return Err(anyhow::anyhow!("Transaction reverted!"));
-        }
+/// Fetch the inputs of the next update from `head` and execute the guest on them, without
+/// proving, relaying or recording a job.
+async fn dry_run(head: Option<u64>) -> Result<()> {
+    let ctx = OperatorContext::new(CancellationToken::new(), 0)?;
+    let (head, names) = destinations_at(&ctx, head).await?;
+    let destination = ctx.destination(&names[0]).unwrap();
+
+    let mut client = ctx.bootstrap_from_contract(destination, head).await?;
+    let Some(update) = ctx.fetch_update(&mut client, destination).await? else {
+        return Ok(());
+    };
+    info!(
+        "Fetched inputs for update from head {} to {} for {}, executing",
+        update.from_head,
+        update.target_head,
+        names.join(", ")
+    );
+
+    let proof = prove_async(
+        Arc::new(DevModeProver),
+        update.encoded_proof_inputs,
+        ReceiptKind::default(),
+        CancellationToken::new(),
+    )
+    .await?;
+    let outputs = ProofOutputs::abi_decode(&proof.receipt.journal.bytes, true)?;
+    if let Some(stats) = proof.stats {
+        info!(
+            "Executed in {} cycles ({} user cycles, {} segments)",
+            stats.total_cycles, stats.user_cycles, stats.segments
+        );
+    }
+    info!(
+        "Update would move the contract from head {} ({:?}) to {} ({:?})",
+        outputs.prevHead, outputs.prevHeader, outputs.newHead, outputs.newHeader
+    );
+    if outputs.nextSyncCommitteeHash != B256::ZERO {
+        info!(
+            "Update would store next sync committee {:?}",
+            outputs.nextSyncCommitteeHash
+        );
+    }
+    info!("Update proves {} storage slots", outputs.slots.len());
+    Ok(())
+}
+
+/// Print the head of every destination contract against the source chain's finalized head.
+async fn status() -> Result<()> {
+    let ctx = OperatorContext::new(CancellationToken::new(), 0)?;
 
+    let source_head = get_latest_finalized_slot().await?;
+    ctx.status.write().unwrap().source_finalized_head = Some(source_head);
+
+    for destination in &ctx.destinations {
+        let head = ctx.contract_head(destination).await?;
+        ctx.refresh_status(destination, head).await?;
         info!(
-            "Successfully updated to new head block! Tx hash: {:?}",
-            receipt.transaction_hash
+            "{}: contract head {}, {} slots ({} sync committee periods) behind the source chain",
+            destination.name,
+            head,
+            source_head.saturating_sub(head),
+            calc_sync_period::<MainnetConsensusSpec>(source_head)
+                .saturating_sub(calc_sync_period::<MainnetConsensusSpec>(head))
         );
+    }
 
-        Ok(())
+    let status = ctx.status.read().unwrap().clone();
+    println!("{}", serde_json::to_string_pretty(&status)?);
+    Ok(())
+}
+
+/// Prove the encoded `ProofInputs` in `inputs` with the configured prover backend, and write the
+/// receipt to `out`.
+///
+/// Only the prover backend is configured from the environment, so this runs without RPC access.
+fn prove_inputs(inputs: PathBuf, out: PathBuf) -> Result<()> {
+    let prover = prover_from_env()?;
+    let receipt_kind = receipt_kind_from_env()?;
+    let encoded_proof_inputs = fs::read(&inputs)
+        .with_context(|| format!("Failed to read inputs from {}", inputs.display()))?;
+
+    info!(
+        "Proving {} with a {} receipt",
+        inputs.display(),
+        receipt_kind
+    );
+    let proof = prover.prove(
+        &encoded_proof_inputs,
+        receipt_kind,
+        &CancellationToken::new(),
+    )?;
+    if let Some(stats) = proof.stats {
+        info!("Proved in {} cycles", stats.total_cycles);
     }
 
-    /// Start the operator.
-    async fn run(&mut self, loop_delay_mins: u64) -> Result<()> {
-        info!("Starting R0VM Helios operator");
-
-        loop {
-            let provider = ProviderBuilder::new().on_http(self.rpc_url.clone());
-            let contract = R0VMHelios::new(self.contract_address, provider);
-
-            // Get the current slot from the contract
-            let slot = contract
-                .head()
-                .call()
-                .await
-                .unwrap_or_else(|e| {
-                    panic!("Failed to get head. Are you sure the R0VMHelios is deployed to address: {:?}? Error: {:?}", self.contract_address, e)
-                })
-                .head
-                .try_into()
-                .unwrap();
-
-            // Fetch the checkpoint at that slot
-            let checkpoint = get_checkpoint(slot).await;
-
-            // Get the client from the checkpoint
-            let client = get_client(checkpoint).await;
-
-            // Request an update
-            match self.request_update(client).await {
-                Ok(Some(proof)) => {
-                    self.relay_update(proof, slot).await?;
-                }
-                Ok(None) => {
-                    // Contract is up to date. Nothing to update.
-                }
-                Err(e) => {
-                    error!("Header range request failed: {}", e);
-                }
-            };
-
-            info!("Sleeping for {:?} minutes", loop_delay_mins);
-            tokio::time::sleep(tokio::time::Duration::from_secs(60 * loop_delay_mins)).await;
-        }
+    fs::write(&out, serde_cbor::to_vec(&proof.receipt)?)?;
+    info!("Wrote receipt to {}", out.display());
+    Ok(())
+}
+
+/// Relay the receipt in `path` to the destinations of the job it proves, like the relay stage
+/// does.
+///
+/// The job is looked up by the heads the receipt's journal commits to, so its inputs must have
+/// been fetched with `fetch-inputs` against the same `STATE_DIR`.
+async fn relay_receipt(path: PathBuf) -> Result<()> {
+    let shutdown = CancellationToken::new();
+    tokio::spawn(wait_for_signal(shutdown.clone()));
+    let ctx = Arc::new(OperatorContext::new(shutdown, 0)?);
+
+    let receipt: Receipt = serde_cbor::from_slice(
+        &fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?,
+    )?;
+    receipt
+        .verify(R0VM_HELIOS_GUEST_ID)
+        .context("Receipt doesn't verify against the guest image ID")?;
+    let outputs = ProofOutputs::abi_decode(&receipt.journal.bytes, true)?;
+    let (from_head, target_head): (u64, u64) = (outputs.prevHead.to(), outputs.newHead.to());
+
+    let mut job = ctx
+        .db
+        .unfinished_jobs()?
+        .into_iter()
+        .find(|job| job.from_head == from_head && job.target_head == target_head)
+        .with_context(|| {
+            format!(
+                "No unfinished job updates head {} to {}",
+                from_head, target_head
+            )
+        })?;
+    if matches!(job.stage, JobStage::Fetched | JobStage::Proving) {
+        ctx.db.save_receipt(&mut job, &receipt)?;
+    }
+
+    let relayed = pipeline::relay(ctx.clone(), job.clone(), receipt).await;
+
+    for name in &job.destinations {
+        let relay = ctx.db.relay(&job, name)?;
+        info!("{}: {:?}", name, relay.state);
     }
+    relayed
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    env::set_var("RUST_LOG", "info");
-    dotenv::dotenv().ok();
-    env_logger::init();
+/// Re-verify the proofs archived in `dir` against the current guest image ID.
+fn verify_archive(dir: PathBuf) -> Result<()> {
+    let archive = ProofArchive::open(&dir)?;
+    let image_id = Digest::from(R0VM_HELIOS_GUEST_ID);
+    info!(
+        "Verifying proofs in {} against image ID {}",
+        dir.display(),
+        image_id
+    );
+
+    let report = archive.verify(image_id)?;
+    for proof in &report.verified {
+        info!(
+            "{}: verified update from {} to {}",
+            proof.id, proof.record.from_head, proof.record.target_head
+        );
+    }
+    for (id, reason) in &report.failed {
+        error!("{}: {}", id, reason);
+    }
+    for gap in &report.gaps {
+        error!(
+            "Gap on {}: a proof ends at head {} ({:?}), the next starts from head {} ({:?})",
+            gap.destination, gap.end_head, gap.end_header, gap.next_head, gap.next_header
+        );
+    }
+
+    info!(
+        "{} proofs verified, {} failed, {} gaps",
+        report.verified.len(),
+        report.failed.len(),
+        report.gaps.len()
+    );
+    if !report.is_ok() {
+        anyhow::bail!("Archive verification failed");
+    }
+    Ok(())
+}
 
+/// Run the operator until shutdown or, if `once` is set, until a single round of updates has been
+/// relayed.
+async fn run(once: bool) -> Result<()> {
     let loop_delay_mins = env::var("LOOP_DELAY_MINS")
         .unwrap_or("5".to_string())
         .parse()?;
 
-    let mut operator = R0VMHeliosOperator::new().await;
-    loop {
-        if let Err(e) = operator.run(loop_delay_mins).await {
-            error!("Error running operator: {}", e);
-        }
+    let http_addr: SocketAddr = env::var("HTTP_ADDR")
+        .unwrap_or("127.0.0.1:9090".to_string())
+        .parse()?;
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn(wait_for_signal(shutdown.clone()));
+
+    let ctx = Arc::new(OperatorContext::new(shutdown.clone(), loop_delay_mins)?);
+
+    // Refuse to relay to contracts deployed for another network or guest program.
+    let expected = ExpectedParams::from_env()?;
+    for destination in &ctx.destinations {
+        check_destination(destination, &expected).await?;
+    }
+
+    let server = (!once).then(|| {
+        tokio::spawn(serve_http(
+            http_addr,
+            AppState {
+                metrics: ctx.metrics.clone(),
+                status: ctx.status.clone(),
+            },
+            shutdown.clone(),
+        ))
+    });
+
+    // Wake up on finality events rather than only polling every LOOP_DELAY_MINS.
+    let finality = if env::var("BEACON_EVENTS").is_ok_and(|enabled| enabled == "true") && !once {
+        let url = match env::var("BEACON_EVENTS_URL") {
+            Ok(url) => url,
+            Err(_) => secrets::require_secret("SOURCE_CONSENSUS_RPC_URL")?,
+        };
+        Some(FinalityEvents::subscribe(&url, shutdown.clone())?)
+    } else {
+        None
+    };
+
+    let result = pipeline::run(ctx, once, finality).await;
+
+    // Make sure the HTTP server stops even if the pipeline failed.
+    shutdown.cancel();
+    if let Some(server) = server {
+        server.await?;
     }
+    info!("Operator stopped");
+    result
 }
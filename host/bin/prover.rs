@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::{command, Parser};
+use r0vm_helios_script::prover::{
+    run_if_prover_child, serve_directory, serve_socket, DevModeProver, HeliosProver, LocalProver,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Serve proof requests from an operator on a separate proving machine.")]
+pub struct ProverArgs {
+    /// Unix socket to listen on, for operators using `PROVER=socket`.
+    #[arg(long, conflicts_with = "dir", required_unless_present = "dir")]
+    pub socket: Option<PathBuf>,
+    /// Shared directory to watch, for operators using `PROVER=directory`.
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+    /// Execute the guest and return fake receipts instead of proving.
+    #[arg(long)]
+    pub dev_mode: bool,
+}
+
+fn main() -> Result<()> {
+    run_if_prover_child();
+    std::env::set_var("RUST_LOG", "info");
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let args = ProverArgs::parse();
+
+    let prover: Box<dyn HeliosProver> = if args.dev_mode {
+        Box::new(DevModeProver)
+    } else {
+        Box::new(LocalProver)
+    };
+
+    match (args.socket, args.dir) {
+        (Some(socket), _) => serve_socket(&socket, prover.as_ref()),
+        (None, Some(dir)) => serve_directory(&dir, prover.as_ref(), Duration::from_secs(5)),
+        (None, None) => unreachable!("clap requires --socket or --dir"),
+    }
+}
@@ -9,7 +9,7 @@ use clap::{command, Parser};
 use helios_ethereum::rpc::ConsensusRpc;
 use r0vm_helios_methods::R0VM_HELIOS_GUEST_ELF;
 use r0vm_helios_primitives::types::{ContractStorage, ProofInputs, StorageSlot};
-use r0vm_helios_script::{get_checkpoint, get_client, get_latest_checkpoint, get_updates};
+use r0vm_helios_script::{get_checkpoint, get_client, get_latest_checkpoint, get_updates, secrets};
 use risc0_zkvm::{default_prover, ExecutorEnv};
 
 #[derive(Parser, Debug, Clone)]
@@ -26,14 +26,14 @@ async fn main() -> Result<()> {
 
     // Get the current slot from the contract or fetch the latest checkpoint
     let checkpoint = if let Some(slot) = args.slot {
-        get_checkpoint(slot).await
+        get_checkpoint(slot).await?
     } else {
-        get_latest_checkpoint().await
+        get_latest_checkpoint().await?
     };
 
     // Setup client.
-    let helios_client = get_client(checkpoint).await;
-    let sync_committee_updates = get_updates(&helios_client).await;
+    let helios_client = get_client(checkpoint).await?;
+    let sync_committee_updates = get_updates(&helios_client).await?;
     let finality_update = helios_client.rpc.get_finality_update().await.unwrap();
 
     let expected_current_slot = helios_client.expected_current_slot();
@@ -52,7 +52,7 @@ async fn main() -> Result<()> {
     let expected_value = address!("c186fA914353c44b2E33eBE05f21846F1048bEda");
 
     // Setup execution RPC client
-    let execution_rpc = std::env::var("SOURCE_EXECUTION_RPC_URL").unwrap();
+    let execution_rpc = secrets::require_secret("SOURCE_EXECUTION_RPC_URL").unwrap();
     let provider = ProviderBuilder::new().on_http(execution_rpc.parse()?);
 
     // Get the proof using eth_getProof
@@ -63,12 +63,12 @@ async fn main() -> Result<()> {
 
     let inputs = ProofInputs {
         sync_committee_updates,
-        finality_update,
+        finality_update: Some(finality_update),
         expected_current_slot,
         store: helios_client.store,
         genesis_root: helios_client.config.chain.genesis_root,
         forks: helios_client.config.forks.clone(),
-        contract_storage_slots: ContractStorage {
+        contract_storage_slots: Some(ContractStorage {
             address: contract_address,
             expected_value: TrieAccount {
                 nonce: proof.nonce,
@@ -82,7 +82,7 @@ async fn main() -> Result<()> {
                 expected_value: U256::from_be_slice(expected_value.as_slice()),
                 mpt_proof: proof.storage_proof[0].proof.clone(),
             }],
-        },
+        }),
     };
 
     let info = tokio::task::spawn_blocking(move || {
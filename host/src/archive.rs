@@ -0,0 +1,376 @@
+use crate::db::{unix_now, UpdateJob};
+use crate::store::write_atomic;
+use alloy::sol_types::SolValue;
+use alloy_primitives::{B256, U256};
+use anyhow::{Context, Result};
+use r0vm_helios_primitives::types::ProofOutputs;
+use risc0_zkvm::{sha::Impl, sha::Sha256, Digest, Receipt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// What the archive records about a proof, besides its artifacts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveRecord {
+    pub from_head: u64,
+    pub target_head: u64,
+    /// Image ID the proof was generated for.
+    pub image_id: B256,
+    /// The update transaction that confirmed the proof, by destination.
+    pub relays: BTreeMap<String, B256>,
+    pub archived_at: u64,
+}
+
+/// A proof that passed re-verification.
+#[derive(Clone)]
+pub struct VerifiedProof {
+    pub id: String,
+    pub record: ArchiveRecord,
+    pub outputs: ProofOutputs,
+}
+
+/// A break in the chain of proofs relayed to a destination: no archived proof starts where the
+/// previous one ended.
+#[derive(Debug, Clone)]
+pub struct ArchiveGap {
+    pub destination: String,
+    /// Head and header the previous proof ended at.
+    pub end_head: u64,
+    pub end_header: B256,
+    /// Head and header the next proof starts from.
+    pub next_head: u64,
+    pub next_header: B256,
+}
+
+/// Result of re-verifying every proof in the archive.
+#[derive(Default)]
+pub struct ArchiveReport {
+    /// Verified proofs, ordered by the head they start from.
+    pub verified: Vec<VerifiedProof>,
+    /// Proofs that failed verification, with the reason.
+    pub failed: Vec<(String, String)>,
+    pub gaps: Vec<ArchiveGap>,
+}
+
+impl ArchiveReport {
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty() && self.gaps.is_empty()
+    }
+}
+
+/// Content-addressed archive of every proof the operator relayed, for audits and incident
+/// response.
+///
+/// Each proof is stored under the SHA-256 digest of its journal:
+///
+/// ```text
+/// <root>/<journal digest>/
+///   inputs.cbor
+///   receipt.cbor
+///   journal.bin
+///   record.json
+/// ```
+pub struct ProofArchive {
+    root: PathBuf,
+}
+
+impl ProofArchive {
+    /// Open the archive at `root`, creating it if it doesn't exist.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)
+            .with_context(|| format!("failed to create archive dir {}", root.display()))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Archive the proof of `job` with its encoded inputs, and the transactions that relayed it.
+    ///
+    /// Archiving the same proof again adds `relays` to those already recorded.
+    pub fn archive(
+        &self,
+        job: &UpdateJob,
+        encoded_inputs: &[u8],
+        receipt: &Receipt,
+        image_id: Digest,
+        relays: BTreeMap<String, B256>,
+    ) -> Result<String> {
+        let journal = &receipt.journal.bytes;
+        let id = Impl::hash_bytes(journal).to_string();
+        let dir = self.root.join(&id);
+
+        let mut record = match self.record(&id) {
+            Ok(record) => record,
+            Err(_) => {
+                write_atomic(&dir.join("inputs.cbor"), encoded_inputs)?;
+                write_atomic(&dir.join("receipt.cbor"), &serde_cbor::to_vec(receipt)?)?;
+                write_atomic(&dir.join("journal.bin"), journal)?;
+                ArchiveRecord {
+                    from_head: job.from_head,
+                    target_head: job.target_head,
+                    image_id: B256::from_slice(image_id.as_bytes()),
+                    relays: BTreeMap::new(),
+                    archived_at: unix_now(),
+                }
+            }
+        };
+        record.relays.extend(relays);
+        write_atomic(
+            &dir.join("record.json"),
+            &serde_json::to_vec_pretty(&record)?,
+        )?;
+
+        Ok(id)
+    }
+
+    pub fn record(&self, id: &str) -> Result<ArchiveRecord> {
+        Ok(serde_json::from_slice(&fs::read(
+            self.root.join(id).join("record.json"),
+        )?)?)
+    }
+
+    pub fn load_inputs(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.root.join(id).join("inputs.cbor"))?)
+    }
+
+    pub fn load_receipt(&self, id: &str) -> Result<Receipt> {
+        Ok(serde_cbor::from_slice(&fs::read(
+            self.root.join(id).join("receipt.cbor"),
+        )?)?)
+    }
+
+    /// IDs of every archived proof.
+    pub fn ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.join("record.json").exists() {
+                if let Some(id) = path.file_name().and_then(|name| name.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Re-verify every archived receipt against `image_id`, and check that the chain of
+    /// `prevHeader`/`newHeader` the proofs relayed to each destination commit to has no gaps.
+    pub fn verify(&self, image_id: Digest) -> Result<ArchiveReport> {
+        let mut report = ArchiveReport::default();
+        for id in self.ids()? {
+            match self.verify_proof(&id, image_id) {
+                Ok(proof) => report.verified.push(proof),
+                Err(e) => report.failed.push((id, format!("{:#}", e))),
+            }
+        }
+
+        report
+            .verified
+            .sort_by_key(|proof| (proof.record.from_head, proof.record.target_head));
+
+        // Destinations at different heads are relayed different proofs, so each destination has
+        // its own chain.
+        let mut chains: BTreeMap<&str, Vec<&ProofOutputs>> = BTreeMap::new();
+        for proof in &report.verified {
+            for destination in proof.record.relays.keys() {
+                chains.entry(destination).or_default().push(&proof.outputs);
+            }
+        }
+        let mut gaps = Vec::new();
+        for (destination, chain) in chains {
+            for pair in chain.windows(2) {
+                let (previous, next) = (pair[0], pair[1]);
+                if next.prevHead != previous.newHead || next.prevHeader != previous.newHeader {
+                    gaps.push(ArchiveGap {
+                        destination: destination.to_string(),
+                        end_head: previous.newHead.to(),
+                        end_header: previous.newHeader,
+                        next_head: next.prevHead.to(),
+                        next_header: next.prevHeader,
+                    });
+                }
+            }
+        }
+        report.gaps = gaps;
+
+        Ok(report)
+    }
+
+    fn verify_proof(&self, id: &str, image_id: Digest) -> Result<VerifiedProof> {
+        let record = self.record(id)?;
+        let receipt = self.load_receipt(id)?;
+        let journal = fs::read(self.root.join(id).join("journal.bin"))?;
+
+        if Impl::hash_bytes(&journal).to_string() != id {
+            anyhow::bail!("journal doesn't match its digest");
+        }
+        if receipt.journal.bytes != journal {
+            anyhow::bail!("receipt commits to a different journal");
+        }
+        receipt
+            .verify(image_id)
+            .context("receipt verification failed")?;
+
+        let outputs = ProofOutputs::abi_decode(&journal, true)?;
+        if outputs.prevHead != U256::from(record.from_head)
+            || outputs.newHead != U256::from(record.target_head)
+        {
+            anyhow::bail!(
+                "journal proves {} to {}, record says {} to {}",
+                outputs.prevHead,
+                outputs.newHead,
+                record.from_head,
+                record.target_head
+            );
+        }
+
+        Ok(VerifiedProof {
+            id: id.to_string(),
+            record,
+            outputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::JobStage;
+    use risc0_zkvm::{FakeReceipt, InnerReceipt, ReceiptClaim};
+
+    const IMAGE_ID: [u32; 8] = [1; 8];
+
+    fn header(head: u64) -> B256 {
+        B256::from(U256::from(head))
+    }
+
+    fn journal(from_head: u64, target_head: u64, prev_header: B256) -> Vec<u8> {
+        ProofOutputs {
+            executionStateRoot: B256::ZERO,
+            newHeader: header(target_head),
+            nextSyncCommitteeHash: B256::ZERO,
+            newHead: U256::from(target_head),
+            prevHeader: prev_header,
+            prevHead: U256::from(from_head),
+            syncCommitteeHash: B256::ZERO,
+            startSyncCommitteeHash: B256::ZERO,
+            slots: vec![],
+        }
+        .abi_encode()
+    }
+
+    fn receipt(image_id: [u32; 8], journal: Vec<u8>) -> Receipt {
+        let claim = ReceiptClaim::ok(image_id, journal.clone());
+        Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal)
+    }
+
+    fn archive(
+        archive: &ProofArchive,
+        from_head: u64,
+        target_head: u64,
+        destinations: &[&str],
+    ) -> String {
+        let job = UpdateJob {
+            id: format!("{}-{}", from_head, target_head),
+            from_head,
+            target_head,
+            destinations: destinations.iter().map(|name| name.to_string()).collect(),
+            stage: JobStage::Confirmed,
+            error: None,
+            created_at: 0,
+            updated_at: 0,
+        };
+        let relays = destinations
+            .iter()
+            .map(|name| (name.to_string(), B256::repeat_byte(1)))
+            .collect();
+        archive
+            .archive(
+                &job,
+                b"inputs",
+                &receipt(IMAGE_ID, journal(from_head, target_head, header(from_head))),
+                Digest::from(IMAGE_ID),
+                relays,
+            )
+            .unwrap()
+    }
+
+    fn open() -> (tempfile::TempDir, ProofArchive) {
+        // Fake receipts only verify in dev mode.
+        std::env::set_var("RISC0_DEV_MODE", "1");
+        let dir = tempfile::tempdir().unwrap();
+        let archive = ProofArchive::open(dir.path()).unwrap();
+        (dir, archive)
+    }
+
+    #[test]
+    fn chains_proofs_per_destination() {
+        let (_dir, proofs) = open();
+        archive(&proofs, 50, 200, &["a"]);
+        archive(&proofs, 100, 200, &["b"]);
+        archive(&proofs, 200, 300, &["a", "b"]);
+
+        let report = proofs.verify(Digest::from(IMAGE_ID)).unwrap();
+        assert_eq!(report.verified.len(), 3);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn reports_gaps() {
+        let (_dir, proofs) = open();
+        archive(&proofs, 100, 200, &["a", "b"]);
+        archive(&proofs, 200, 300, &["a"]);
+        archive(&proofs, 400, 500, &["a", "b"]);
+
+        let report = proofs.verify(Digest::from(IMAGE_ID)).unwrap();
+        assert!(report.failed.is_empty());
+        let gaps: Vec<_> = report
+            .gaps
+            .iter()
+            .map(|gap| (gap.destination.as_str(), gap.end_head, gap.next_head))
+            .collect();
+        assert_eq!(gaps, vec![("a", 300, 400), ("b", 200, 400)]);
+        assert_eq!(report.gaps[0].end_header, header(300));
+        assert_eq!(report.gaps[0].next_header, header(400));
+    }
+
+    #[test]
+    fn reports_tampered_proofs() {
+        let (_dir, proofs) = open();
+        let journal_id = archive(&proofs, 100, 200, &["a"]);
+        let receipt_id = archive(&proofs, 200, 300, &["a"]);
+        archive(&proofs, 300, 400, &["a"]);
+
+        fs::write(
+            proofs.root().join(&journal_id).join("journal.bin"),
+            journal(100, 201, header(100)),
+        )
+        .unwrap();
+        fs::write(
+            proofs.root().join(&receipt_id).join("receipt.cbor"),
+            serde_cbor::to_vec(&receipt([2; 8], journal(200, 300, header(200)))).unwrap(),
+        )
+        .unwrap();
+
+        let report = proofs.verify(Digest::from(IMAGE_ID)).unwrap();
+        assert_eq!(report.verified.len(), 1);
+        assert!(report.gaps.is_empty());
+        let reason = |id: &str| {
+            report
+                .failed
+                .iter()
+                .find(|(failed, _)| failed == id)
+                .map(|(_, reason)| reason.clone())
+                .unwrap()
+        };
+        assert_eq!(reason(&journal_id), "journal doesn't match its digest");
+        assert!(reason(&receipt_id).starts_with("receipt verification failed"));
+    }
+}
@@ -0,0 +1,90 @@
+use helios_consensus_core::{consensus_spec::MainnetConsensusSpec, types::Update};
+
+/// Default number of sync committee periods a single proof may advance the light client by.
+pub const DEFAULT_MAX_PERIODS_PER_PROOF: usize = 4;
+
+/// The sync committee updates to prove in the next job.
+#[derive(Debug, PartialEq, Eq)]
+pub enum UpdatePlan<U = Update<MainnetConsensusSpec>> {
+    /// Apply the updates and the latest finality update, advancing to the finalized head.
+    Finalize(Vec<U>),
+    /// Apply only these updates, advancing to the finalized header of the last one. The rest of
+    /// the backlog is left to the following proofs.
+    CatchUp(Vec<U>),
+}
+
+/// Plan the next proof for a light client that still has to apply `updates`.
+///
+/// If the client is more than `max_periods` sync committee periods behind, proving every update
+/// at once may exceed the prover's memory or take too long, so the backlog is split: the next proof
+/// applies the first `max_periods` updates only, and the finality update is left for the last
+/// proof of the sequence.
+///
+/// The contract rejects a proof that doesn't advance its head, which a chunk whose updates lack a
+/// supermajority doesn't. `advances` tells whether applying a chunk advances the finalized head;
+/// a chunk that doesn't is extended with the following updates until it does. If no chunk short
+/// of the whole backlog does, every update is proven along with the finality update.
+pub fn plan_update<U>(
+    mut updates: Vec<U>,
+    max_periods: usize,
+    mut advances: impl FnMut(&[U]) -> bool,
+) -> UpdatePlan<U> {
+    if max_periods == 0 || updates.len() <= max_periods {
+        return UpdatePlan::Finalize(updates);
+    }
+
+    match (max_periods..updates.len()).find(|&len| advances(&updates[..len])) {
+        Some(len) => {
+            updates.truncate(len);
+            UpdatePlan::CatchUp(updates)
+        }
+        None => UpdatePlan::Finalize(updates),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finalizes_short_backlogs() {
+        assert_eq!(
+            plan_update(vec![1, 2, 3], 4, |_| true),
+            UpdatePlan::Finalize(vec![1, 2, 3])
+        );
+        assert_eq!(
+            plan_update(vec![1, 2, 3, 4], 4, |_| true),
+            UpdatePlan::Finalize(vec![1, 2, 3, 4])
+        );
+        assert_eq!(
+            plan_update(vec![1, 2, 3, 4, 5], 0, |_| true),
+            UpdatePlan::Finalize(vec![1, 2, 3, 4, 5])
+        );
+    }
+
+    #[test]
+    fn truncates_long_backlogs_to_max_periods() {
+        assert_eq!(
+            plan_update((1..=10).collect(), 4, |_| true),
+            UpdatePlan::CatchUp(vec![1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn merges_chunks_that_do_not_advance() {
+        // Only the update for period 6 has a supermajority.
+        let advances = |updates: &[u64]| updates.contains(&6);
+        assert_eq!(
+            plan_update((1..=10).collect(), 4, advances),
+            UpdatePlan::CatchUp(vec![1, 2, 3, 4, 5, 6])
+        );
+    }
+
+    #[test]
+    fn finalizes_when_no_chunk_advances() {
+        assert_eq!(
+            plan_update((1..=6).collect(), 4, |_| false),
+            UpdatePlan::Finalize(vec![1, 2, 3, 4, 5, 6])
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use alloy_primitives::{Address, B256};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{env, fs, path::Path, path::PathBuf};
+
+/// Operator configuration file.
+///
+/// Every parameter corresponds to one of the environment variables the operator reads, and is
+/// only applied if the environment doesn't override it, see [`OperatorConfig::apply_to_env`].
+///
+/// ```toml
+/// loop_delay_mins = 5
+///
+/// [source]
+/// chain_id = 1
+/// consensus_rpc_url = "https://..."
+/// execution_rpc_url = "https://..."
+/// storage_contract_address = "0x..."
+///
+/// [prover]
+/// backend = "local"
+/// receipt_kind = "groth16"
+///
+/// [[destinations]]
+/// name = "sepolia"
+/// rpc_url = "https://..."
+/// contract_address = "0x..."
+/// keystore_path = "/etc/operator/relayer.json"
+/// keystore_password_file = "/run/secrets/relayer-password"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorConfig {
+    /// `LOOP_DELAY_MINS`
+    pub loop_delay_mins: Option<u64>,
+    /// `STATE_DIR`
+    pub state_dir: Option<PathBuf>,
+    /// `ARCHIVE_DIR`
+    pub archive_dir: Option<PathBuf>,
+    /// `HTTP_ADDR`
+    pub http_addr: Option<String>,
+    /// `SHUTDOWN_TIMEOUT_SECS`
+    pub shutdown_timeout_secs: Option<u64>,
+    /// `PROOF_MAX_LAG_SLOTS`
+    pub proof_max_lag_slots: Option<u64>,
+    /// `MAX_PERIODS_PER_PROOF`
+    pub max_periods_per_proof: Option<usize>,
+    /// `BEACON_EVENTS`
+    pub beacon_events: Option<bool>,
+    /// `BEACON_EVENTS_URL`
+    pub beacon_events_url: Option<String>,
+    /// `VERIFIER_ADDRESS`
+    pub verifier_address: Option<Address>,
+    #[serde(default)]
+    pub source: SourceConfig,
+    #[serde(default)]
+    pub prover: ProverConfig,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub coordination: CoordinationConfig,
+    /// `DEST_<N>_*`, numbered from 1 in the order they appear.
+    #[serde(default)]
+    pub destinations: Vec<DestinationConfig>,
+}
+
+/// The source chain the light client syncs from.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    /// `SOURCE_CHAIN_ID`
+    pub chain_id: Option<u64>,
+    /// `SOURCE_CONSENSUS_RPC_URL`
+    pub consensus_rpc_url: Option<String>,
+    /// `SOURCE_EXECUTION_RPC_URL`
+    pub execution_rpc_url: Option<String>,
+    /// `STORAGE_CONTRACT_ADDRESS`
+    pub storage_contract_address: Option<Address>,
+    /// `STORAGE_SLOTS`
+    pub storage_slots: Option<Vec<B256>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProverConfig {
+    /// `PROVER`
+    pub backend: Option<String>,
+    /// `RECEIPT_KIND`
+    pub receipt_kind: Option<String>,
+    /// `PROVER_SOCKET_PATH`
+    pub socket_path: Option<PathBuf>,
+    /// `PROVER_DIR`
+    pub dir: Option<PathBuf>,
+    /// `PROVER_TIMEOUT_SECS`
+    pub timeout_secs: Option<u64>,
+}
+
+/// When updates are relayed, see `UpdatePolicy`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// `UPDATE_TRIGGERS`
+    pub triggers: Option<Vec<String>>,
+    /// `UPDATE_INTERVAL_SLOTS`
+    pub interval_slots: Option<u64>,
+    /// `UPDATE_MAX_GAS_PRICE`
+    pub max_gas_price: Option<u128>,
+    /// `UPDATE_DEADLINE_MARGIN_SECS`
+    pub deadline_margin_secs: Option<u64>,
+}
+
+/// How operators sharing the updater role take turns, see `Coordinator`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CoordinationConfig {
+    /// `COORDINATION`
+    pub mode: Option<String>,
+    /// `COORDINATION_ID`
+    pub id: Option<String>,
+    /// `COORDINATION_LOCK_FILE`
+    pub lock_file: Option<PathBuf>,
+    /// `COORDINATION_LEASE_SECS`
+    pub lease_secs: Option<u64>,
+    /// `COORDINATION_UPDATERS`
+    pub updaters: Option<Vec<Address>>,
+    /// `COORDINATION_ROTATION_SLOTS`
+    pub rotation_slots: Option<u64>,
+    /// `COORDINATION_TAKEOVER_SECS`
+    pub takeover_secs: Option<u64>,
+}
+
+/// A destination contract, see `Destination`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DestinationConfig {
+    pub name: Option<String>,
+    pub rpc_url: Option<String>,
+    pub contract_address: Option<Address>,
+    pub private_key: Option<String>,
+    pub private_key_file: Option<PathBuf>,
+    pub keystore_path: Option<PathBuf>,
+    pub keystore_password: Option<String>,
+    pub keystore_password_file: Option<PathBuf>,
+    pub gas_limit: Option<u64>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub max_fee_per_gas_cap: Option<u128>,
+    pub max_priority_fee_per_gas_cap: Option<u128>,
+    pub fee_bump_percent: Option<u128>,
+    pub stall_timeout_secs: Option<u64>,
+    pub relay_timeout_secs: Option<u64>,
+}
+
+impl OperatorConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Set the environment variable of every parameter in the file, unless the environment
+    /// overrides it, see [`overriding_vars`].
+    pub fn apply_to_env(&self) {
+        // Decide before setting anything, so the file's parameters don't override each other.
+        let vars: Vec<_> = self
+            .vars()
+            .into_iter()
+            .filter(|(var, _)| !overriding_vars(var).iter().any(|var| is_set(var)))
+            .collect();
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+    }
+
+    /// Environment variables and their values, for every parameter set in the file.
+    fn vars(&self) -> Vec<(String, String)> {
+        let mut vars = Vars::default();
+        vars.push("LOOP_DELAY_MINS", &self.loop_delay_mins);
+        vars.push_path("STATE_DIR", &self.state_dir);
+        vars.push_path("ARCHIVE_DIR", &self.archive_dir);
+        vars.push("HTTP_ADDR", &self.http_addr);
+        vars.push("SHUTDOWN_TIMEOUT_SECS", &self.shutdown_timeout_secs);
+        vars.push("PROOF_MAX_LAG_SLOTS", &self.proof_max_lag_slots);
+        vars.push("MAX_PERIODS_PER_PROOF", &self.max_periods_per_proof);
+        vars.push("BEACON_EVENTS", &self.beacon_events);
+        vars.push("BEACON_EVENTS_URL", &self.beacon_events_url);
+        vars.push("VERIFIER_ADDRESS", &self.verifier_address);
+
+        let source = &self.source;
+        vars.push("SOURCE_CHAIN_ID", &source.chain_id);
+        vars.push("SOURCE_CONSENSUS_RPC_URL", &source.consensus_rpc_url);
+        vars.push("SOURCE_EXECUTION_RPC_URL", &source.execution_rpc_url);
+        vars.push("STORAGE_CONTRACT_ADDRESS", &source.storage_contract_address);
+        if let Some(slots) = &source.storage_slots {
+            let slots: Vec<String> = slots.iter().map(ToString::to_string).collect();
+            vars.push("STORAGE_SLOTS", &Some(slots.join(",")));
+        }
+
+        let prover = &self.prover;
+        vars.push("PROVER", &prover.backend);
+        vars.push("RECEIPT_KIND", &prover.receipt_kind);
+        vars.push_path("PROVER_SOCKET_PATH", &prover.socket_path);
+        vars.push_path("PROVER_DIR", &prover.dir);
+        vars.push("PROVER_TIMEOUT_SECS", &prover.timeout_secs);
+
+        let policy = &self.policy;
+        vars.push(
+            "UPDATE_TRIGGERS",
+            &policy.triggers.as_ref().map(|t| t.join(",")),
+        );
+        vars.push("UPDATE_INTERVAL_SLOTS", &policy.interval_slots);
+        vars.push("UPDATE_MAX_GAS_PRICE", &policy.max_gas_price);
+        vars.push("UPDATE_DEADLINE_MARGIN_SECS", &policy.deadline_margin_secs);
+
+        let coordination = &self.coordination;
+        vars.push("COORDINATION", &coordination.mode);
+        vars.push("COORDINATION_ID", &coordination.id);
+        vars.push_path("COORDINATION_LOCK_FILE", &coordination.lock_file);
+        vars.push("COORDINATION_LEASE_SECS", &coordination.lease_secs);
+        if let Some(updaters) = &coordination.updaters {
+            let updaters: Vec<String> = updaters.iter().map(ToString::to_string).collect();
+            vars.push("COORDINATION_UPDATERS", &Some(updaters.join(",")));
+        }
+        vars.push("COORDINATION_ROTATION_SLOTS", &coordination.rotation_slots);
+        vars.push("COORDINATION_TAKEOVER_SECS", &coordination.takeover_secs);
+
+        for (index, destination) in self.destinations.iter().enumerate() {
+            let prefix = format!("DEST_{}_", index + 1);
+            let var = |name: &str| format!("{}{}", prefix, name);
+            vars.push(&var("NAME"), &destination.name);
+            vars.push(&var("RPC_URL"), &destination.rpc_url);
+            vars.push(&var("CONTRACT_ADDRESS"), &destination.contract_address);
+            vars.push(&var("PRIVATE_KEY"), &destination.private_key);
+            vars.push_path(&var("PRIVATE_KEY_FILE"), &destination.private_key_file);
+            vars.push_path(&var("KEYSTORE_PATH"), &destination.keystore_path);
+            vars.push(&var("KEYSTORE_PASSWORD"), &destination.keystore_password);
+            vars.push_path(
+                &var("KEYSTORE_PASSWORD_FILE"),
+                &destination.keystore_password_file,
+            );
+            vars.push(&var("GAS_LIMIT"), &destination.gas_limit);
+            vars.push(&var("MAX_FEE_PER_GAS"), &destination.max_fee_per_gas);
+            vars.push(
+                &var("MAX_PRIORITY_FEE_PER_GAS"),
+                &destination.max_priority_fee_per_gas,
+            );
+            vars.push(
+                &var("MAX_FEE_PER_GAS_CAP"),
+                &destination.max_fee_per_gas_cap,
+            );
+            vars.push(
+                &var("MAX_PRIORITY_FEE_PER_GAS_CAP"),
+                &destination.max_priority_fee_per_gas_cap,
+            );
+            vars.push(&var("FEE_BUMP_PERCENT"), &destination.fee_bump_percent);
+            vars.push(&var("STALL_TIMEOUT_SECS"), &destination.stall_timeout_secs);
+            vars.push(&var("RELAY_TIMEOUT_SECS"), &destination.relay_timeout_secs);
+        }
+
+        vars.0
+    }
+}
+
+/// Parameters of a destination's relayer key, which `signer_from_env` reads one of.
+const KEY_VARS: [&str; 5] = [
+    "PRIVATE_KEY",
+    "PRIVATE_KEY_FILE",
+    "KEYSTORE_PATH",
+    "KEYSTORE_PASSWORD",
+    "KEYSTORE_PASSWORD_FILE",
+];
+
+/// Environment variables that override the file's `var` when set.
+///
+/// Besides `var` itself, some variables override a group of parameters, where applying only part
+/// of them would mix the file and the environment: the legacy `DEST_RPC_URL` and
+/// `CONTRACT_ADDRESS` override every numbered destination, which would otherwise take precedence
+/// over them, and a destination's `PRIVATE_KEY` or `KEYSTORE_PATH` overrides every key parameter
+/// of that destination.
+fn overriding_vars(var: &str) -> Vec<String> {
+    let mut vars = vec![var.strip_suffix("_FILE").unwrap_or(var).to_string()];
+    let numbered = var
+        .strip_prefix("DEST_")
+        .and_then(|rest| rest.split_once('_'))
+        .filter(|(index, _)| index.parse::<usize>().is_ok());
+    if let Some((index, name)) = numbered {
+        vars.push("DEST_RPC_URL".to_string());
+        vars.push("CONTRACT_ADDRESS".to_string());
+        if KEY_VARS.contains(&name) {
+            vars.push(format!("DEST_{}_PRIVATE_KEY", index));
+            vars.push(format!("DEST_{}_KEYSTORE_PATH", index));
+        }
+    }
+    vars
+}
+
+/// Whether `var` is set, either directly or through its `_FILE` variant.
+fn is_set(var: &str) -> bool {
+    env::var_os(var).is_some() || env::var_os(format!("{}_FILE", var)).is_some()
+}
+
+#[derive(Default)]
+struct Vars(Vec<(String, String)>);
+
+impl Vars {
+    fn push(&mut self, var: &str, value: &Option<impl ToString>) {
+        if let Some(value) = value {
+            self.0.push((var.to_string(), value.to_string()));
+        }
+    }
+
+    fn push_path(&mut self, var: &str, value: &Option<PathBuf>) {
+        self.push(var, &value.as_ref().map(|path| path.display()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const CONFIG: &str = r#"
+        loop_delay_mins = 5
+        state_dir = "/var/lib/operator"
+
+        [source]
+        chain_id = 1
+        storage_slots = [
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "0x0000000000000000000000000000000000000000000000000000000000000002",
+        ]
+
+        [policy]
+        triggers = ["interval", "slots"]
+
+        [[destinations]]
+        name = "sepolia"
+        rpc_url = "https://sepolia"
+        keystore_path = "/etc/operator/relayer.json"
+        keystore_password_file = "/run/secrets/relayer-password"
+
+        [[destinations]]
+        rpc_url = "https://holesky"
+        private_key = "0x01"
+        gas_limit = 500000
+    "#;
+
+    /// Serializes the tests, which share the process environment, and clears the variables they
+    /// read.
+    fn clear_env() -> MutexGuard<'static, ()> {
+        let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for (var, _) in env::vars() {
+            let base = var.strip_suffix("_FILE").unwrap_or(&var);
+            if var.starts_with("DEST_")
+                || [
+                    "LOOP_DELAY_MINS",
+                    "STATE_DIR",
+                    "SOURCE_CHAIN_ID",
+                    "STORAGE_SLOTS",
+                    "UPDATE_TRIGGERS",
+                    "CONTRACT_ADDRESS",
+                    "PRIVATE_KEY",
+                ]
+                .contains(&base)
+            {
+                env::remove_var(var);
+            }
+        }
+        guard
+    }
+
+    fn config() -> OperatorConfig {
+        toml::from_str(CONFIG).unwrap()
+    }
+
+    fn var(var: &str) -> Option<String> {
+        env::var(var).ok()
+    }
+
+    #[test]
+    fn maps_parameters_to_vars() {
+        let vars = config().vars();
+        let value = |var: &str| {
+            vars.iter()
+                .find(|(name, _)| name == var)
+                .map(|(_, value)| value.as_str())
+        };
+
+        assert_eq!(value("LOOP_DELAY_MINS"), Some("5"));
+        assert_eq!(value("STATE_DIR"), Some("/var/lib/operator"));
+        assert_eq!(value("SOURCE_CHAIN_ID"), Some("1"));
+        assert_eq!(
+            value("STORAGE_SLOTS"),
+            Some(
+                "0x0000000000000000000000000000000000000000000000000000000000000001,\
+                 0x0000000000000000000000000000000000000000000000000000000000000002"
+            )
+        );
+        assert_eq!(value("UPDATE_TRIGGERS"), Some("interval,slots"));
+        assert_eq!(value("DEST_1_NAME"), Some("sepolia"));
+        assert_eq!(value("DEST_1_RPC_URL"), Some("https://sepolia"));
+        assert_eq!(
+            value("DEST_1_KEYSTORE_PATH"),
+            Some("/etc/operator/relayer.json")
+        );
+        assert_eq!(
+            value("DEST_1_KEYSTORE_PASSWORD_FILE"),
+            Some("/run/secrets/relayer-password")
+        );
+        assert_eq!(value("DEST_2_NAME"), None);
+        assert_eq!(value("DEST_2_PRIVATE_KEY"), Some("0x01"));
+        assert_eq!(value("DEST_2_GAS_LIMIT"), Some("500000"));
+        assert_eq!(value("HTTP_ADDR"), None);
+    }
+
+    #[test]
+    fn applies_parameters_the_environment_doesnt_set() {
+        let _env = clear_env();
+        env::set_var("LOOP_DELAY_MINS", "1");
+        env::set_var("DEST_2_RPC_URL_FILE", "/run/secrets/holesky-rpc");
+
+        config().apply_to_env();
+        assert_eq!(var("LOOP_DELAY_MINS").as_deref(), Some("1"));
+        assert_eq!(var("SOURCE_CHAIN_ID").as_deref(), Some("1"));
+        assert_eq!(var("DEST_1_RPC_URL").as_deref(), Some("https://sepolia"));
+        assert_eq!(var("DEST_2_RPC_URL"), None);
+        assert_eq!(var("DEST_2_GAS_LIMIT").as_deref(), Some("500000"));
+    }
+
+    #[test]
+    fn environment_key_overrides_the_files_key() {
+        let _env = clear_env();
+        env::set_var("DEST_1_PRIVATE_KEY", "0x02");
+        env::set_var("DEST_2_KEYSTORE_PATH", "/etc/operator/holesky.json");
+
+        config().apply_to_env();
+        assert_eq!(var("DEST_1_RPC_URL").as_deref(), Some("https://sepolia"));
+        assert_eq!(var("DEST_1_PRIVATE_KEY").as_deref(), Some("0x02"));
+        assert_eq!(var("DEST_1_KEYSTORE_PATH"), None);
+        assert_eq!(var("DEST_1_KEYSTORE_PASSWORD_FILE"), None);
+        assert_eq!(var("DEST_2_PRIVATE_KEY"), None);
+        assert_eq!(var("DEST_2_GAS_LIMIT").as_deref(), Some("500000"));
+    }
+
+    #[test]
+    fn files_key_parameters_apply_together() {
+        let _env = clear_env();
+
+        config().apply_to_env();
+        assert_eq!(
+            var("DEST_1_KEYSTORE_PATH").as_deref(),
+            Some("/etc/operator/relayer.json")
+        );
+        assert_eq!(
+            var("DEST_1_KEYSTORE_PASSWORD_FILE").as_deref(),
+            Some("/run/secrets/relayer-password")
+        );
+        assert_eq!(var("DEST_2_PRIVATE_KEY").as_deref(), Some("0x01"));
+    }
+
+    #[test]
+    fn legacy_destination_overrides_the_files_destinations() {
+        let _env = clear_env();
+        env::set_var("DEST_RPC_URL", "https://mainnet");
+        env::set_var("CONTRACT_ADDRESS", Address::ZERO.to_string());
+        env::set_var("PRIVATE_KEY", "0x03");
+
+        config().apply_to_env();
+        assert_eq!(var("LOOP_DELAY_MINS").as_deref(), Some("5"));
+        assert!(
+            env::vars().all(|(var, _)| !var.starts_with("DEST_1_") && !var.starts_with("DEST_2_"))
+        );
+        assert_eq!(var("DEST_RPC_URL").as_deref(), Some("https://mainnet"));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::contract::R0VMHelios;
+use crate::destination::Destination;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy_primitives::{Address, B256, U256};
+use anyhow::{Context, Result};
+use helios_ethereum::config::networks::Network;
+use r0vm_helios_methods::R0VM_HELIOS_GUEST_ID;
+use risc0_zkvm::Digest;
+use std::{env, fmt};
+
+pub const SECONDS_PER_SLOT: u64 = 12;
+pub const SLOTS_PER_EPOCH: u64 = 32;
+pub const SLOTS_PER_PERIOD: u64 = SLOTS_PER_EPOCH * 256;
+
+/// The network parameters and guest program a light client contract is deployed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractParams {
+    pub genesis_validators_root: B256,
+    pub genesis_time: U256,
+    pub seconds_per_slot: U256,
+    pub slots_per_period: U256,
+    pub slots_per_epoch: U256,
+    pub source_chain_id: U256,
+    pub helios_image_id: B256,
+    pub verifier: Address,
+}
+
+/// A parameter of a deployed contract that differs from the operator's configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: &'static str,
+    pub expected: String,
+    pub deployed: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, deployed {}",
+            self.name, self.expected, self.deployed
+        )
+    }
+}
+
+/// The parameters the contracts are expected to be deployed with.
+#[derive(Debug, Clone)]
+pub struct ExpectedParams {
+    pub genesis_validators_root: B256,
+    pub genesis_time: u64,
+    pub source_chain_id: u64,
+    pub helios_image_id: B256,
+    /// Only compared if `VERIFIER_ADDRESS` is set. Otherwise the verifier only has to be deployed.
+    pub verifier: Option<Address>,
+}
+
+impl ExpectedParams {
+    /// The parameters of the helios network config for `SOURCE_CHAIN_ID`, the image ID of the
+    /// guest program the operator proves with, and the optional `VERIFIER_ADDRESS`.
+    pub fn from_env() -> Result<Self> {
+        let source_chain_id: u64 = env::var("SOURCE_CHAIN_ID")
+            .context("SOURCE_CHAIN_ID not set")?
+            .parse()
+            .context("Failed to parse SOURCE_CHAIN_ID")?;
+        let network = Network::from_chain_id(source_chain_id)
+            .map_err(|e| anyhow::anyhow!("Unsupported SOURCE_CHAIN_ID: {}", e))?;
+        let chain = network.to_base_config().chain;
+
+        let verifier = env::var("VERIFIER_ADDRESS")
+            .ok()
+            .map(|verifier| verifier.parse())
+            .transpose()
+            .context("Failed to parse VERIFIER_ADDRESS")?;
+
+        Ok(Self {
+            genesis_validators_root: chain.genesis_root,
+            genesis_time: chain.genesis_time,
+            source_chain_id,
+            helios_image_id: B256::from_slice(Digest::from(R0VM_HELIOS_GUEST_ID).as_bytes()),
+            verifier,
+        })
+    }
+
+    /// The parameters of `deployed` that differ from these.
+    pub fn diff(&self, deployed: &ContractParams) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut compare = |name, expected: String, deployed: String| {
+            if expected != deployed {
+                mismatches.push(Mismatch {
+                    name,
+                    expected,
+                    deployed,
+                });
+            }
+        };
+
+        compare(
+            "GENESIS_VALIDATORS_ROOT",
+            self.genesis_validators_root.to_string(),
+            deployed.genesis_validators_root.to_string(),
+        );
+        compare(
+            "GENESIS_TIME",
+            self.genesis_time.to_string(),
+            deployed.genesis_time.to_string(),
+        );
+        compare(
+            "SECONDS_PER_SLOT",
+            SECONDS_PER_SLOT.to_string(),
+            deployed.seconds_per_slot.to_string(),
+        );
+        compare(
+            "SLOTS_PER_PERIOD",
+            SLOTS_PER_PERIOD.to_string(),
+            deployed.slots_per_period.to_string(),
+        );
+        compare(
+            "SLOTS_PER_EPOCH",
+            SLOTS_PER_EPOCH.to_string(),
+            deployed.slots_per_epoch.to_string(),
+        );
+        compare(
+            "SOURCE_CHAIN_ID",
+            self.source_chain_id.to_string(),
+            deployed.source_chain_id.to_string(),
+        );
+        compare(
+            "heliosImageID",
+            self.helios_image_id.to_string(),
+            deployed.helios_image_id.to_string(),
+        );
+        if let Some(verifier) = self.verifier {
+            compare(
+                "verifier",
+                verifier.to_string(),
+                deployed.verifier.to_string(),
+            );
+        }
+        mismatches
+    }
+}
+
+/// Read the parameters `destination`'s contract is deployed with.
+pub async fn read_params(destination: &Destination) -> Result<ContractParams> {
+    let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+    let contract = R0VMHelios::new(destination.contract_address, &provider);
+
+    Ok(ContractParams {
+        genesis_validators_root: contract.GENESIS_VALIDATORS_ROOT().call().await?._0,
+        genesis_time: contract.GENESIS_TIME().call().await?._0,
+        seconds_per_slot: contract.SECONDS_PER_SLOT().call().await?._0,
+        slots_per_period: contract.SLOTS_PER_PERIOD().call().await?._0,
+        slots_per_epoch: contract.SLOTS_PER_EPOCH().call().await?._0,
+        source_chain_id: contract.SOURCE_CHAIN_ID().call().await?._0,
+        helios_image_id: contract.heliosImageID().call().await?._0,
+        verifier: contract.verifier().call().await?._0,
+    })
+}
+
+/// Check that `destination`'s contract is deployed for the configured source chain and guest
+/// program, failing with every parameter that differs otherwise.
+pub async fn check_destination(destination: &Destination, expected: &ExpectedParams) -> Result<()> {
+    let context = || {
+        format!(
+            "Failed to read the parameters of contract {} on {}",
+            destination.contract_address, destination.name
+        )
+    };
+    let deployed = read_params(destination).await.with_context(context)?;
+
+    let mut mismatches = expected.diff(&deployed);
+    if expected.verifier.is_none() {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let code = provider
+            .get_code_at(deployed.verifier)
+            .await
+            .with_context(context)?;
+        if code.is_empty() {
+            mismatches.push(Mismatch {
+                name: "verifier",
+                expected: "a deployed contract".to_string(),
+                deployed: format!("{} without code", deployed.verifier),
+            });
+        }
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    let diff: Vec<String> = mismatches
+        .iter()
+        .map(|mismatch| format!("  {}", mismatch))
+        .collect();
+    anyhow::bail!(
+        "Contract {} on {} doesn't match the operator's configuration:\n{}",
+        destination.contract_address,
+        destination.name,
+        diff.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> ExpectedParams {
+        ExpectedParams {
+            genesis_validators_root: B256::repeat_byte(1),
+            genesis_time: 1_606_824_023,
+            source_chain_id: 1,
+            helios_image_id: B256::repeat_byte(2),
+            verifier: Some(Address::repeat_byte(3)),
+        }
+    }
+
+    /// The parameters of a contract deployed as `expected` describes.
+    fn deployed(expected: &ExpectedParams) -> ContractParams {
+        ContractParams {
+            genesis_validators_root: expected.genesis_validators_root,
+            genesis_time: U256::from(expected.genesis_time),
+            seconds_per_slot: U256::from(SECONDS_PER_SLOT),
+            slots_per_period: U256::from(SLOTS_PER_PERIOD),
+            slots_per_epoch: U256::from(SLOTS_PER_EPOCH),
+            source_chain_id: U256::from(expected.source_chain_id),
+            helios_image_id: expected.helios_image_id,
+            verifier: expected.verifier.unwrap(),
+        }
+    }
+
+    fn names(mismatches: &[Mismatch]) -> Vec<&'static str> {
+        mismatches.iter().map(|mismatch| mismatch.name).collect()
+    }
+
+    #[test]
+    fn matches_identical_params() {
+        let expected = expected();
+        assert!(expected.diff(&deployed(&expected)).is_empty());
+    }
+
+    #[test]
+    fn reports_each_mismatched_field() {
+        let expected = expected();
+        let fields: [(&str, fn(&mut ContractParams)); 8] = [
+            ("GENESIS_VALIDATORS_ROOT", |params| {
+                params.genesis_validators_root = B256::ZERO
+            }),
+            ("GENESIS_TIME", |params| {
+                params.genesis_time += U256::from(1)
+            }),
+            ("SECONDS_PER_SLOT", |params| {
+                params.seconds_per_slot = U256::from(6)
+            }),
+            ("SLOTS_PER_PERIOD", |params| {
+                params.slots_per_period = U256::from(512)
+            }),
+            ("SLOTS_PER_EPOCH", |params| {
+                params.slots_per_epoch = U256::from(8)
+            }),
+            ("SOURCE_CHAIN_ID", |params| {
+                params.source_chain_id = U256::from(11_155_111)
+            }),
+            ("heliosImageID", |params| {
+                params.helios_image_id = B256::ZERO
+            }),
+            ("verifier", |params| params.verifier = Address::ZERO),
+        ];
+
+        for (name, change) in fields {
+            let mut deployed = deployed(&expected);
+            change(&mut deployed);
+            assert_eq!(names(&expected.diff(&deployed)), vec![name]);
+        }
+    }
+
+    #[test]
+    fn reports_every_mismatch_with_values() {
+        let expected = expected();
+        let mut deployed = deployed(&expected);
+        deployed.source_chain_id = U256::from(17_000);
+        deployed.helios_image_id = B256::ZERO;
+
+        let mismatches = expected.diff(&deployed);
+        assert_eq!(names(&mismatches), vec!["SOURCE_CHAIN_ID", "heliosImageID"]);
+        assert_eq!(
+            mismatches[0],
+            Mismatch {
+                name: "SOURCE_CHAIN_ID",
+                expected: "1".to_string(),
+                deployed: "17000".to_string(),
+            }
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "SOURCE_CHAIN_ID: expected 1, deployed 17000"
+        );
+    }
+
+    #[test]
+    fn ignores_verifier_unless_configured() {
+        let mut expected = expected();
+        let mut deployed = deployed(&expected);
+        deployed.verifier = Address::ZERO;
+        expected.verifier = None;
+        assert!(expected.diff(&deployed).is_empty());
+    }
+}
@@ -0,0 +1,12 @@
+//! The `R0VMHelios` contract, and the errors of the RISC Zero verifier it calls.
+
+pub use r0vm_helios_bindings::R0VMHelios;
+
+use alloy::sol;
+
+sol! {
+    #[allow(missing_docs)]
+    interface IRiscZeroVerifier {
+        error VerificationFailed();
+    }
+}
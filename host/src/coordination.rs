@@ -0,0 +1,518 @@
+use crate::contract::R0VMHelios;
+use crate::db::unix_now;
+use crate::destination::{parse_optional, Destination};
+use crate::store::write_atomic;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy_primitives::Address;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tokio_util::sync::CancellationToken;
+
+/// How operators sharing the updater role decide which of them relays updates.
+#[derive(Debug, Clone)]
+pub enum CoordinationMode {
+    /// Every operator relays updates. Duplicate updates are skipped once the contract is past
+    /// their target.
+    None,
+    /// The operator holding the lease in a shared lock file relays updates.
+    Lock { path: PathBuf, lease: Duration },
+    /// Updaters take turns: the update from `head` is relayed by
+    /// `updaters[head / rotation_slots % updaters.len()]`.
+    Rotation {
+        updaters: Vec<Address>,
+        rotation_slots: u64,
+    },
+}
+
+/// This operator's role in relaying an update to a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Leader,
+    /// The leader hasn't updated the contract for `stalled_for`, so this operator takes over.
+    Takeover {
+        stalled_for: Duration,
+    },
+    Standby,
+}
+
+/// Another updater advanced the contract to or past the target of an update before it was
+/// relayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Superseded {
+    pub contract_head: u64,
+    pub target_head: u64,
+}
+
+impl fmt::Display for Superseded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "contract head {} is already at or past the update's target {}",
+            self.contract_head, self.target_head
+        )
+    }
+}
+
+impl std::error::Error for Superseded {}
+
+/// A lease in the shared lock file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Lease {
+    owner: String,
+    expires_at: u64,
+}
+
+/// When the last `HeadUpdate` event of a destination was seen.
+struct HeadWatch {
+    next_block: u64,
+    seen_at: Instant,
+}
+
+/// Coordinates operators relaying to the same contracts, so that a single one proves and relays
+/// each update.
+///
+/// Standby operators watch the contracts' `HeadUpdate` events, and take over if the leader hasn't
+/// updated a contract for `takeover_after`.
+pub struct Coordinator {
+    pub mode: CoordinationMode,
+    /// Identifies this operator in the lock file.
+    pub id: String,
+    pub takeover_after: Duration,
+    watches: Mutex<HashMap<String, HeadWatch>>,
+}
+
+impl Coordinator {
+    pub fn new(mode: CoordinationMode, id: String, takeover_after: Duration) -> Self {
+        Self {
+            mode,
+            id,
+            takeover_after,
+            watches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Read the coordination settings from `COORDINATION` and the `COORDINATION_*` variables.
+    pub fn from_env() -> Result<Self> {
+        let mode = match env::var("COORDINATION").as_deref() {
+            Err(_) | Ok("none") => CoordinationMode::None,
+            Ok("lock") => CoordinationMode::Lock {
+                path: env::var("COORDINATION_LOCK_FILE")
+                    .context("COORDINATION_LOCK_FILE not set")?
+                    .into(),
+                lease: Duration::from_secs(
+                    parse_optional("COORDINATION_LEASE_SECS")?.unwrap_or(300),
+                ),
+            },
+            Ok("rotation") => {
+                let updaters = env::var("COORDINATION_UPDATERS")
+                    .context("COORDINATION_UPDATERS not set")?
+                    .split(',')
+                    .map(|updater| updater.trim().parse())
+                    .collect::<Result<Vec<Address>, _>>()
+                    .context("Failed to parse COORDINATION_UPDATERS")?;
+                if updaters.is_empty() {
+                    anyhow::bail!("COORDINATION_UPDATERS is empty");
+                }
+                CoordinationMode::Rotation {
+                    updaters,
+                    rotation_slots: parse_optional("COORDINATION_ROTATION_SLOTS")?.unwrap_or(8192),
+                }
+            }
+            Ok(mode) => anyhow::bail!(
+                "Unknown coordination mode {}, expected none, lock or rotation",
+                mode
+            ),
+        };
+
+        let id = env::var("COORDINATION_ID")
+            .or_else(|_| {
+                env::var("HOSTNAME").map(|host| format!("{}-{}", host, std::process::id()))
+            })
+            .unwrap_or_else(|_| format!("operator-{}", std::process::id()));
+
+        let takeover_after =
+            Duration::from_secs(parse_optional("COORDINATION_TAKEOVER_SECS")?.unwrap_or(3600));
+        Ok(Self::new(mode, id, takeover_after))
+    }
+
+    /// This operator's role in relaying the update from `head` to `destination`.
+    pub async fn role(&self, destination: &Destination, head: u64) -> Result<Role> {
+        let leader = match &self.mode {
+            CoordinationMode::None => return Ok(Role::Leader),
+            CoordinationMode::Lock { .. } => self.holds_lock()?,
+            CoordinationMode::Rotation {
+                updaters,
+                rotation_slots,
+            } => {
+                updaters[turn(head, *rotation_slots, updaters.len())] == destination.relayer_address
+            }
+        };
+
+        // Keep watching the contract while leading, so a standby period starts from fresh.
+        let stalled_for = self.time_since_head_update(destination).await?;
+        if leader {
+            Ok(Role::Leader)
+        } else if stalled_for >= self.takeover_after {
+            Ok(Role::Takeover { stalled_for })
+        } else {
+            Ok(Role::Standby)
+        }
+    }
+
+    /// Time since a `HeadUpdate` event of `destination` was last seen, or since it was first
+    /// watched.
+    async fn time_since_head_update(&self, destination: &Destination) -> Result<Duration> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let latest = provider.get_block_number().await?;
+        let from_block = self
+            .watches
+            .lock()
+            .unwrap()
+            .get(&destination.name)
+            .map_or(latest, |watch| watch.next_block);
+
+        let updated = if from_block <= latest {
+            let contract = R0VMHelios::new(destination.contract_address, &provider);
+            !contract
+                .HeadUpdate_filter()
+                .from_block(from_block)
+                .to_block(latest)
+                .query()
+                .await?
+                .is_empty()
+        } else {
+            false
+        };
+
+        let mut watches = self.watches.lock().unwrap();
+        let watch = watches
+            .entry(destination.name.clone())
+            .or_insert_with(|| HeadWatch {
+                next_block: latest + 1,
+                seen_at: Instant::now(),
+            });
+        if updated {
+            watch.seen_at = Instant::now();
+        }
+        watch.next_block = watch.next_block.max(latest + 1);
+        Ok(watch.seen_at.elapsed())
+    }
+
+    /// Whether this operator holds an unexpired lease in the lock file.
+    fn holds_lock(&self) -> Result<bool> {
+        let CoordinationMode::Lock { path, .. } = &self.mode else {
+            return Ok(false);
+        };
+        Ok(read_lease(path)?
+            .is_some_and(|lease| lease.owner == self.id && lease.expires_at > unix_now()))
+    }
+
+    /// Acquire the lease in the lock file once it is free or expired, and renew it while held,
+    /// until `shutdown`. The lease is released on shutdown.
+    pub async fn hold_lock(&self, shutdown: CancellationToken) {
+        let CoordinationMode::Lock { path, lease } = &self.mode else {
+            return;
+        };
+
+        let mut leading = false;
+        loop {
+            match self.try_acquire(path, *lease) {
+                Ok(acquired) if acquired != leading => {
+                    leading = acquired;
+                    if leading {
+                        info!("Acquired the lock in {}, leading", path.display());
+                    } else {
+                        info!("Lost the lock in {}, standing by", path.display());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to renew the lock in {}: {:#}", path.display(), e),
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(*lease / 3) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        if leading {
+            if let Err(e) = self.release(path, *lease) {
+                warn!("Failed to release the lock in {}: {:#}", path.display(), e);
+            }
+        }
+    }
+
+    /// Take or renew the lease unless another operator holds it. Returns whether this operator
+    /// holds it.
+    fn try_acquire(&self, path: &Path, lease: Duration) -> Result<bool> {
+        // Another operator is taking or renewing the lease. Keep the one held, if any.
+        let Some(_guard) = LeaseGuard::acquire(path, lease)? else {
+            return self.holds_lock();
+        };
+
+        let now = unix_now();
+        if let Some(current) = read_lease(path)? {
+            if current.owner != self.id && current.expires_at > now {
+                return Ok(false);
+            }
+        }
+        let lease = Lease {
+            owner: self.id.clone(),
+            expires_at: now + lease.as_secs(),
+        };
+        write_atomic(path, &serde_json::to_vec(&lease)?)?;
+        Ok(true)
+    }
+
+    /// Remove the lease if this operator holds it.
+    fn release(&self, path: &Path, lease: Duration) -> Result<()> {
+        let Some(_guard) = LeaseGuard::acquire(path, lease)? else {
+            // The lease expires on its own.
+            return Ok(());
+        };
+        if read_lease(path)?.is_some_and(|lease| lease.owner == self.id) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// The operator whose turn it is to relay the update from `head`, among `updaters` operators
+/// taking turns every `rotation_slots` slots.
+fn turn(head: u64, rotation_slots: u64, updaters: usize) -> usize {
+    (head / rotation_slots) as usize % updaters
+}
+
+/// Exclusive access to the lease in a lock file, held while the file `<lock file>.guard` exists.
+///
+/// Creating the guard file fails if it already exists, so a single operator at a time reads and
+/// writes the lease.
+struct LeaseGuard {
+    path: PathBuf,
+}
+
+impl LeaseGuard {
+    /// Create the guard of the lock file at `lock_path`. Returns `None` if another operator holds
+    /// it, removing it if it is older than `stale_after`, as left by an operator that crashed.
+    fn acquire(lock_path: &Path, stale_after: Duration) -> Result<Option<Self>> {
+        let mut path = OsString::from(lock_path.as_os_str());
+        path.push(".guard");
+        let path = PathBuf::from(path);
+
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => Ok(Some(Self { path })),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let modified = match fs::metadata(&path) {
+                    Ok(metadata) => metadata.modified()?,
+                    // Released in the meantime.
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+                if modified.elapsed().unwrap_or_default() > stale_after {
+                    Self::remove_stale(&path, modified)?;
+                }
+                Ok(None)
+            }
+            Err(e) => Err(e).with_context(|| format!("Failed to create {}", path.display())),
+        }
+    }
+
+    /// Remove the guard at `path`, found stale with the modification time `modified`.
+    ///
+    /// Another operator may have removed the stale guard and created its own since, so the guard
+    /// is first moved to a name no other operator uses, and put back unless it is the stale one.
+    fn remove_stale(path: &Path, modified: SystemTime) -> Result<()> {
+        static REMOVALS: AtomicUsize = AtomicUsize::new(0);
+        let mut stale = OsString::from(path.as_os_str());
+        stale.push(format!(
+            ".stale-{}-{}",
+            process::id(),
+            REMOVALS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let stale = PathBuf::from(stale);
+
+        match fs::rename(path, &stale) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to move {}", path.display())),
+        }
+        if fs::metadata(&stale)?.modified()? == modified {
+            warn!("Removed stale lock guard {}", path.display());
+        } else {
+            // Fails if yet another guard was created in the meantime, which then wins.
+            let _ = fs::hard_link(&stale, path);
+        }
+        fs::remove_file(&stale)?;
+        Ok(())
+    }
+}
+
+impl Drop for LeaseGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn read_lease(path: &Path) -> Result<Option<Lease>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const LEASE: Duration = Duration::from_secs(300);
+
+    fn coordinator(path: &Path, id: &str) -> Coordinator {
+        let mode = CoordinationMode::Lock {
+            path: path.to_path_buf(),
+            lease: LEASE,
+        };
+        Coordinator::new(mode, id.to_string(), Duration::from_secs(3600))
+    }
+
+    #[test]
+    fn acquires_free_and_expired_leases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leader.lock");
+        let a = coordinator(&path, "a");
+        let b = coordinator(&path, "b");
+
+        assert!(!a.holds_lock().unwrap());
+        assert!(a.try_acquire(&path, LEASE).unwrap());
+        assert!(a.holds_lock().unwrap());
+
+        // The lease is held until it expires, and renewed by its holder.
+        assert!(!b.try_acquire(&path, LEASE).unwrap());
+        assert!(!b.holds_lock().unwrap());
+        assert!(a.try_acquire(&path, LEASE).unwrap());
+
+        let expired = Lease {
+            owner: "a".to_string(),
+            expires_at: unix_now() - 1,
+        };
+        fs::write(&path, serde_json::to_vec(&expired).unwrap()).unwrap();
+        assert!(!a.holds_lock().unwrap());
+        assert!(b.try_acquire(&path, LEASE).unwrap());
+        assert!(b.holds_lock().unwrap());
+        assert!(!a.try_acquire(&path, LEASE).unwrap());
+
+        // Only the holder releases the lease.
+        a.release(&path, LEASE).unwrap();
+        assert!(b.holds_lock().unwrap());
+        b.release(&path, LEASE).unwrap();
+        assert!(!path.exists());
+        assert!(a.try_acquire(&path, LEASE).unwrap());
+    }
+
+    #[test]
+    fn a_single_operator_wins_a_race() {
+        const OPERATORS: usize = 16;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leader.lock");
+        let barrier = Arc::new(Barrier::new(OPERATORS));
+
+        let racers: Vec<_> = (0..OPERATORS)
+            .map(|i| {
+                let (path, barrier) = (path.clone(), barrier.clone());
+                thread::spawn(move || {
+                    let coordinator = coordinator(&path, &format!("operator-{}", i));
+                    barrier.wait();
+                    coordinator.try_acquire(&path, LEASE).unwrap()
+                })
+            })
+            .collect();
+        let winners = racers
+            .into_iter()
+            .map(|racer| racer.join().unwrap())
+            .filter(|won| *won)
+            .count();
+        assert_eq!(winners, 1);
+
+        let lease = read_lease(&path).unwrap().unwrap();
+        assert!(lease.owner.starts_with("operator-"));
+    }
+
+    #[test]
+    fn waits_for_a_busy_guard() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leader.lock");
+        let a = coordinator(&path, "a");
+
+        let guard = LeaseGuard::acquire(&path, LEASE).unwrap().unwrap();
+        assert!(!a.try_acquire(&path, LEASE).unwrap());
+        drop(guard);
+        assert!(a.try_acquire(&path, LEASE).unwrap());
+
+        // A guard left behind by a crashed operator is removed once stale.
+        std::mem::forget(LeaseGuard::acquire(&path, LEASE).unwrap().unwrap());
+        thread::sleep(Duration::from_millis(10));
+        assert!(LeaseGuard::acquire(&path, Duration::ZERO)
+            .unwrap()
+            .is_none());
+        assert!(LeaseGuard::acquire(&path, LEASE).unwrap().is_some());
+    }
+
+    #[test]
+    fn keeps_a_guard_that_replaced_a_stale_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leader.lock");
+        let guard_path = dir.path().join("leader.lock.guard");
+
+        // Left behind by a crashed operator an hour ago.
+        std::mem::forget(LeaseGuard::acquire(&path, LEASE).unwrap().unwrap());
+        let stale = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&guard_path)
+            .unwrap()
+            .set_modified(stale)
+            .unwrap();
+
+        // Both operators find the guard stale, but the first removes it and takes a new one
+        // before the second gets to remove it.
+        assert!(LeaseGuard::acquire(&path, LEASE).unwrap().is_none());
+        let guard = LeaseGuard::acquire(&path, LEASE).unwrap().unwrap();
+        LeaseGuard::remove_stale(&guard_path, stale).unwrap();
+
+        assert!(guard_path.exists());
+        assert!(LeaseGuard::acquire(&path, LEASE).unwrap().is_none());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        drop(guard);
+        assert!(LeaseGuard::acquire(&path, LEASE).unwrap().is_some());
+    }
+
+    #[test]
+    fn rotates_turns_every_rotation_slots() {
+        assert_eq!(turn(0, 8192, 3), 0);
+        assert_eq!(turn(8191, 8192, 3), 0);
+        assert_eq!(turn(8192, 8192, 3), 1);
+        assert_eq!(turn(2 * 8192 + 5, 8192, 3), 2);
+        assert_eq!(turn(3 * 8192, 8192, 3), 0);
+        assert_eq!(turn(100, 8192, 1), 0);
+    }
+}
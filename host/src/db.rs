@@ -0,0 +1,584 @@
+use crate::fees::Fees;
+use crate::store::{load_store, save_store, write_atomic};
+use alloy_primitives::B256;
+use anyhow::{Context, Result};
+use helios_consensus_core::{consensus_spec::MainnetConsensusSpec, types::LightClientStore};
+use risc0_zkvm::Receipt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The stage an update job has reached in the operator.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    /// Proof inputs have been fetched and persisted.
+    Fetched,
+    /// The proof is being generated.
+    Proving,
+    /// The receipt has been generated and persisted, and is being relayed to the destinations.
+    /// See [`RelayRecord`] for the progress on each destination.
+    Proved,
+    /// The update was confirmed on at least one destination, and every destination is done.
+    Confirmed,
+    /// The job was abandoned. See [`UpdateJob::error`].
+    Failed,
+}
+
+impl JobStage {
+    /// Whether the job still has work left to do.
+    pub fn is_unfinished(&self) -> bool {
+        !matches!(self, JobStage::Confirmed | JobStage::Failed)
+    }
+}
+
+/// A single update from `from_head` to `target_head`, proven once and relayed to every
+/// destination whose contract is at `from_head`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateJob {
+    pub id: String,
+    pub from_head: u64,
+    pub target_head: u64,
+    pub destinations: Vec<String>,
+    pub stage: JobStage,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// The stage the relay of a job has reached on a single destination.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayState {
+    /// The update hasn't been sent to the destination yet.
+    #[default]
+    Pending,
+    /// The update transaction has been sent to the destination chain.
+    Submitted,
+    /// The update transaction has been confirmed on the destination chain.
+    Confirmed,
+    /// The update won't be relayed to the destination. See [`RelayRecord::error`].
+    Failed,
+}
+
+impl RelayState {
+    /// Whether the relay still has work left to do.
+    pub fn is_unfinished(&self) -> bool {
+        matches!(self, RelayState::Pending | RelayState::Submitted)
+    }
+}
+
+/// Relay progress of a job on a single destination.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RelayRecord {
+    pub state: RelayState,
+    /// The confirmed transaction once the relay is confirmed, the last one sent until then.
+    pub tx_hash: Option<B256>,
+    /// Every transaction sent for the update, all with the same nonce. Any of them may be the one
+    /// that gets mined.
+    #[serde(default)]
+    pub tx_hashes: Vec<B256>,
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    /// Fees of the last transaction sent.
+    #[serde(default)]
+    pub fees: Option<Fees>,
+    pub error: Option<String>,
+    pub updated_at: u64,
+}
+
+/// The last update confirmed on a destination.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfirmedRelay {
+    pub head: u64,
+    pub tx_hash: Option<B256>,
+    pub confirmed_at: u64,
+}
+
+/// Operator-wide state that outlives individual jobs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OperatorMeta {
+    /// The last confirmed update on each destination.
+    #[serde(default)]
+    pub last_confirmed: BTreeMap<String, ConfirmedRelay>,
+}
+
+/// Embedded, file-backed state store for the operator.
+///
+/// Everything lives under a single directory:
+///
+/// ```text
+/// <root>/
+///   light_client_store.cbor
+///   meta.json
+///   jobs/<from_head>-<target_head>/
+///     job.json
+///     inputs.cbor
+///     next_store.cbor
+///     receipt.cbor
+///     relays/<destination>.json
+/// ```
+///
+/// Every write goes through a temporary file and a rename, so a crash never leaves a partially
+/// written record behind.
+pub struct StateDb {
+    root: PathBuf,
+    /// Serializes updates of `meta.json` by relays running concurrently.
+    meta_lock: Mutex<()>,
+}
+
+impl StateDb {
+    /// Open the state store at `root`, creating it if it doesn't exist.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join("jobs"))
+            .with_context(|| format!("failed to create state dir {}", root.display()))?;
+        Ok(Self {
+            root,
+            meta_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Load the persisted light client store, if any.
+    pub fn load_light_client_store(
+        &self,
+    ) -> Result<Option<LightClientStore<MainnetConsensusSpec>>> {
+        load_store(&self.root.join("light_client_store.cbor"))
+    }
+
+    /// Persist the light client store.
+    pub fn save_light_client_store(
+        &self,
+        store: &LightClientStore<MainnetConsensusSpec>,
+    ) -> Result<()> {
+        save_store(&self.root.join("light_client_store.cbor"), store)
+    }
+
+    pub fn meta(&self) -> Result<OperatorMeta> {
+        let path = self.root.join("meta.json");
+        if !path.exists() {
+            return Ok(OperatorMeta::default());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    fn save_meta(&self, meta: &OperatorMeta) -> Result<()> {
+        write_atomic(
+            &self.root.join("meta.json"),
+            &serde_json::to_vec_pretty(meta)?,
+        )
+    }
+
+    /// Record a new job in the [`JobStage::Fetched`] stage for `destinations`, along with its
+    /// encoded `ProofInputs` and the light client store the contracts will hold once the job is
+    /// confirmed.
+    ///
+    /// A finished job for the same `from_head` and `target_head` is replaced, but an unfinished
+    /// one is refused, as replacing it would lose the transactions already sent for it.
+    pub fn create_job(
+        &self,
+        from_head: u64,
+        target_head: u64,
+        destinations: Vec<String>,
+        encoded_inputs: &[u8],
+        next_store: &LightClientStore<MainnetConsensusSpec>,
+    ) -> Result<UpdateJob> {
+        let id = format!("{}-{}", from_head, target_head);
+        if let Some(existing) = self.load_job(&id)? {
+            if existing.stage.is_unfinished() {
+                anyhow::bail!("Job {} is still unfinished and can't be replaced", id);
+            }
+        }
+        for destination in &destinations {
+            check_destination_name(destination)?;
+        }
+
+        let now = unix_now();
+        let job = UpdateJob {
+            id,
+            from_head,
+            target_head,
+            destinations,
+            stage: JobStage::Fetched,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let dir = self.job_dir(&job.id);
+        fs::create_dir_all(&dir)?;
+        let _ = fs::remove_file(dir.join("receipt.cbor"));
+        let _ = fs::remove_dir_all(dir.join("relays"));
+        write_atomic(&dir.join("inputs.cbor"), encoded_inputs)?;
+        save_store(&dir.join("next_store.cbor"), next_store)?;
+        self.save_job(&job)?;
+
+        Ok(job)
+    }
+
+    /// Move `job` to `stage` and persist it.
+    pub fn set_stage(&self, job: &mut UpdateJob, stage: JobStage) -> Result<()> {
+        job.stage = stage;
+        job.updated_at = unix_now();
+        self.save_job(job)
+    }
+
+    /// Persist the receipt for `job` and move it to [`JobStage::Proved`].
+    pub fn save_receipt(&self, job: &mut UpdateJob, receipt: &Receipt) -> Result<()> {
+        write_atomic(
+            &self.job_dir(&job.id).join("receipt.cbor"),
+            &serde_cbor::to_vec(receipt)?,
+        )?;
+        self.set_stage(job, JobStage::Proved)
+    }
+
+    /// The relay progress of `job` on `destination`.
+    pub fn relay(&self, job: &UpdateJob, destination: &str) -> Result<RelayRecord> {
+        let path = self.relay_path(job, destination)?;
+        if !path.exists() {
+            return Ok(RelayRecord::default());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// The destinations of `job` whose relay hasn't finished yet.
+    pub fn unfinished_destinations(&self, job: &UpdateJob) -> Result<Vec<String>> {
+        let mut destinations = Vec::new();
+        for destination in &job.destinations {
+            if self.relay(job, destination)?.state.is_unfinished() {
+                destinations.push(destination.clone());
+            }
+        }
+        Ok(destinations)
+    }
+
+    /// Record an update transaction sent to `destination` with `nonce` and `fees`, replacing any
+    /// sent before with the same nonce.
+    pub fn mark_submitted(
+        &self,
+        job: &UpdateJob,
+        destination: &str,
+        tx_hash: B256,
+        nonce: u64,
+        fees: Fees,
+    ) -> Result<RelayRecord> {
+        let mut relay = self.relay(job, destination)?;
+        relay.state = RelayState::Submitted;
+        relay.tx_hash = Some(tx_hash);
+        relay.tx_hashes.push(tx_hash);
+        relay.nonce = Some(nonce);
+        relay.fees = Some(fees);
+        relay.error = None;
+        relay.updated_at = unix_now();
+        self.save_relay(job, destination, relay.clone())?;
+        Ok(relay)
+    }
+
+    /// Record that `destination` confirmed `job` in `tx_hash`, and its target as the
+    /// destination's last confirmed head.
+    pub fn mark_confirmed(
+        &self,
+        job: &UpdateJob,
+        destination: &str,
+        tx_hash: B256,
+    ) -> Result<ConfirmedRelay> {
+        let mut relay = self.relay(job, destination)?;
+        relay.state = RelayState::Confirmed;
+        relay.tx_hash = Some(tx_hash);
+        relay.updated_at = unix_now();
+        self.save_relay(job, destination, relay.clone())?;
+
+        let confirmed = ConfirmedRelay {
+            head: job.target_head,
+            tx_hash: relay.tx_hash,
+            confirmed_at: relay.updated_at,
+        };
+        let _guard = self.meta_lock.lock().unwrap();
+        let mut meta = self.meta()?;
+        meta.last_confirmed
+            .insert(destination.to_string(), confirmed.clone());
+        self.save_meta(&meta)?;
+        Ok(confirmed)
+    }
+
+    /// Give up relaying `job` to `destination`, recording why.
+    pub fn mark_relay_failed(
+        &self,
+        job: &UpdateJob,
+        destination: &str,
+        error: impl ToString,
+    ) -> Result<()> {
+        let mut relay = self.relay(job, destination)?;
+        relay.state = RelayState::Failed;
+        relay.error = Some(error.to_string());
+        relay.updated_at = unix_now();
+        self.save_relay(job, destination, relay)
+    }
+
+    /// Move `job` to [`JobStage::Confirmed`] or [`JobStage::Failed`] once the relay to every
+    /// destination has finished. Returns whether the job is finished.
+    pub fn finish_job(&self, job: &mut UpdateJob) -> Result<bool> {
+        let mut confirmed = false;
+        for destination in &job.destinations {
+            match self.relay(job, destination)?.state {
+                RelayState::Confirmed => confirmed = true,
+                RelayState::Failed => {}
+                RelayState::Pending | RelayState::Submitted => return Ok(false),
+            }
+        }
+
+        if confirmed {
+            self.set_stage(job, JobStage::Confirmed)?;
+        } else {
+            self.mark_failed(job, "No destination confirmed the update")?;
+        }
+        Ok(true)
+    }
+
+    /// Abandon `job`, recording why.
+    pub fn mark_failed(&self, job: &mut UpdateJob, error: impl ToString) -> Result<()> {
+        job.error = Some(error.to_string());
+        self.set_stage(job, JobStage::Failed)
+    }
+
+    pub fn load_inputs(&self, job: &UpdateJob) -> Result<Vec<u8>> {
+        Ok(fs::read(self.job_dir(&job.id).join("inputs.cbor"))?)
+    }
+
+    pub fn load_next_store(
+        &self,
+        job: &UpdateJob,
+    ) -> Result<LightClientStore<MainnetConsensusSpec>> {
+        load_store(&self.job_dir(&job.id).join("next_store.cbor"))?
+            .with_context(|| format!("job {} has no next store", job.id))
+    }
+
+    pub fn load_receipt(&self, job: &UpdateJob) -> Result<Option<Receipt>> {
+        let path = self.job_dir(&job.id).join("receipt.cbor");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_cbor::from_slice(&fs::read(path)?)?))
+    }
+
+    /// All jobs that are neither confirmed nor failed, oldest `from_head` first.
+    pub fn unfinished_jobs(&self) -> Result<Vec<UpdateJob>> {
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(self.root.join("jobs"))? {
+            let Some(job) = self.load_job(&entry?.file_name().to_string_lossy())? else {
+                continue;
+            };
+            if job.stage.is_unfinished() {
+                jobs.push(job);
+            }
+        }
+        jobs.sort_by_key(|job| (job.from_head, job.target_head));
+        Ok(jobs)
+    }
+
+    fn load_job(&self, id: &str) -> Result<Option<UpdateJob>> {
+        let path = self.job_dir(id).join("job.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    fn save_job(&self, job: &UpdateJob) -> Result<()> {
+        write_atomic(
+            &self.job_dir(&job.id).join("job.json"),
+            &serde_json::to_vec_pretty(job)?,
+        )
+    }
+
+    fn save_relay(&self, job: &UpdateJob, destination: &str, relay: RelayRecord) -> Result<()> {
+        write_atomic(
+            &self.relay_path(job, destination)?,
+            &serde_json::to_vec_pretty(&relay)?,
+        )
+    }
+
+    fn relay_path(&self, job: &UpdateJob, destination: &str) -> Result<PathBuf> {
+        check_destination_name(destination)?;
+        Ok(self
+            .job_dir(&job.id)
+            .join("relays")
+            .join(format!("{}.json", destination)))
+    }
+
+    fn job_dir(&self, id: &str) -> PathBuf {
+        self.root.join("jobs").join(id)
+    }
+}
+
+/// Destination names are used as file names, so they can't leave the relays directory.
+fn check_destination_name(destination: &str) -> Result<()> {
+    if destination.is_empty() || destination.contains(['/', '\\']) || destination.contains("..") {
+        anyhow::bail!("Invalid destination name {:?}", destination);
+    }
+    Ok(())
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(max_fee_per_gas: u128) -> Fees {
+        Fees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: 1,
+        }
+    }
+
+    fn create_job(db: &StateDb, destinations: &[&str]) -> Result<UpdateJob> {
+        db.create_job(
+            100,
+            200,
+            destinations.iter().map(|name| name.to_string()).collect(),
+            b"inputs",
+            &LightClientStore::default(),
+        )
+    }
+
+    #[test]
+    fn creates_and_advances_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+
+        let mut job = create_job(&db, &["a", "b"]).unwrap();
+        assert_eq!(job.id, "100-200");
+        assert_eq!(job.stage, JobStage::Fetched);
+        assert_eq!(db.load_inputs(&job).unwrap(), b"inputs");
+        db.load_next_store(&job).unwrap();
+        assert!(db.load_receipt(&job).unwrap().is_none());
+
+        db.set_stage(&mut job, JobStage::Proving).unwrap();
+        assert_eq!(db.unfinished_jobs().unwrap()[0].stage, JobStage::Proving);
+
+        db.set_stage(&mut job, JobStage::Proved).unwrap();
+        db.mark_confirmed(&job, "a", B256::repeat_byte(1)).unwrap();
+        assert!(!db.finish_job(&mut job).unwrap());
+
+        db.mark_relay_failed(&job, "b", "reverted").unwrap();
+        assert!(db.finish_job(&mut job).unwrap());
+        assert_eq!(job.stage, JobStage::Confirmed);
+        assert!(db.unfinished_jobs().unwrap().is_empty());
+        assert_eq!(db.meta().unwrap().last_confirmed["a"].head, 200);
+    }
+
+    #[test]
+    fn fails_jobs_no_destination_confirmed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+
+        let mut job = create_job(&db, &["a"]).unwrap();
+        db.mark_relay_failed(&job, "a", "reverted").unwrap();
+        assert!(db.finish_job(&mut job).unwrap());
+        assert_eq!(job.stage, JobStage::Failed);
+        assert!(job.error.is_some());
+    }
+
+    #[test]
+    fn replaces_transactions_with_the_same_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+        let job = create_job(&db, &["a"]).unwrap();
+
+        db.mark_submitted(&job, "a", B256::repeat_byte(1), 7, fees(100))
+            .unwrap();
+        let relay = db
+            .mark_submitted(&job, "a", B256::repeat_byte(2), 7, fees(120))
+            .unwrap();
+        assert_eq!(relay.state, RelayState::Submitted);
+        assert_eq!(relay.tx_hash, Some(B256::repeat_byte(2)));
+        assert_eq!(
+            relay.tx_hashes,
+            vec![B256::repeat_byte(1), B256::repeat_byte(2)]
+        );
+        assert_eq!(relay.nonce, Some(7));
+        assert_eq!(relay.fees, Some(fees(120)));
+    }
+
+    #[test]
+    fn lists_unfinished_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+        let job = create_job(&db, &["a", "b", "c", "d"]).unwrap();
+
+        db.mark_submitted(&job, "b", B256::repeat_byte(1), 0, fees(100))
+            .unwrap();
+        db.mark_confirmed(&job, "c", B256::repeat_byte(2)).unwrap();
+        db.mark_relay_failed(&job, "d", "reverted").unwrap();
+        assert_eq!(db.unfinished_destinations(&job).unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn resumes_jobs_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db = StateDb::open(dir.path()).unwrap();
+            let mut job = create_job(&db, &["a", "b"]).unwrap();
+            db.set_stage(&mut job, JobStage::Proved).unwrap();
+            db.mark_submitted(&job, "a", B256::repeat_byte(1), 3, fees(100))
+                .unwrap();
+        }
+
+        let db = StateDb::open(dir.path()).unwrap();
+        let jobs = db.unfinished_jobs().unwrap();
+        assert_eq!(jobs.len(), 1);
+        let job = &jobs[0];
+        assert_eq!(job.stage, JobStage::Proved);
+        assert_eq!(db.unfinished_destinations(job).unwrap(), vec!["a", "b"]);
+        let relay = db.relay(job, "a").unwrap();
+        assert_eq!(relay.state, RelayState::Submitted);
+        assert_eq!(relay.tx_hashes, vec![B256::repeat_byte(1)]);
+        assert_eq!(relay.nonce, Some(3));
+    }
+
+    #[test]
+    fn refuses_to_replace_unfinished_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+
+        let mut job = create_job(&db, &["a"]).unwrap();
+        db.mark_submitted(&job, "a", B256::repeat_byte(1), 0, fees(100))
+            .unwrap();
+        assert!(create_job(&db, &["a"]).is_err());
+        assert_eq!(db.relay(&job, "a").unwrap().state, RelayState::Submitted);
+
+        db.mark_confirmed(&job, "a", B256::repeat_byte(1)).unwrap();
+        db.finish_job(&mut job).unwrap();
+        let job = create_job(&db, &["a"]).unwrap();
+        assert_eq!(job.stage, JobStage::Fetched);
+        assert_eq!(db.unfinished_destinations(&job).unwrap(), vec!["a"]);
+    }
+
+    #[test]
+    fn rejects_destination_names_outside_the_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = StateDb::open(dir.path()).unwrap();
+
+        for name in ["../a", "a/b", "a\\b", "..", ""] {
+            assert!(create_job(&db, &[name]).is_err(), "{:?}", name);
+        }
+        let job = create_job(&db, &["a"]).unwrap();
+        assert!(db.relay(&job, "../../meta").is_err());
+        assert!(db.mark_relay_failed(&job, "../a", "reverted").is_err());
+    }
+}
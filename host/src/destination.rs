@@ -0,0 +1,112 @@
+use crate::fees::FeePolicy;
+use crate::secrets::{read_secret, require_secret, signer_from_env};
+use alloy::{network::EthereumWallet, primitives::Address};
+use anyhow::{Context, Result};
+use reqwest::Url;
+use std::env;
+
+/// A destination chain and `R0VMHelios` contract the operator relays proofs to.
+///
+/// Every destination must verify proofs of the same guest image and source chain, so a single
+/// proof can be relayed to all destinations whose contract is at the same head.
+#[derive(Clone)]
+pub struct Destination {
+    /// Name identifying the destination in logs, metrics and the operator state.
+    pub name: String,
+    pub rpc_url: Url,
+    pub contract_address: Address,
+    pub wallet: EthereumWallet,
+    pub relayer_address: Address,
+    /// Gas limit of update transactions. Estimated if unset.
+    pub gas_limit: Option<u64>,
+    /// Fees of update transactions, and how they are bumped when a transaction stalls.
+    pub fees: FeePolicy,
+}
+
+impl Destination {
+    /// Read the destinations from the environment.
+    ///
+    /// Destinations are numbered from 1: `DEST_1_RPC_URL`, `DEST_1_CONTRACT_ADDRESS`,
+    /// `DEST_1_PRIVATE_KEY`, then `DEST_2_*` and so on, until `DEST_<N>_RPC_URL` isn't set. If no
+    /// numbered destination is set, the single destination given by `DEST_RPC_URL`,
+    /// `CONTRACT_ADDRESS` and `PRIVATE_KEY` is used. The relayer key of a destination can also be
+    /// loaded from a keystore, see [`signer_from_env`].
+    pub fn from_env() -> Result<Vec<Self>> {
+        let mut destinations = Vec::new();
+        for index in 1.. {
+            let prefix = format!("DEST_{}_", index);
+            if read_secret(&format!("{}RPC_URL", prefix))?.is_none() {
+                break;
+            }
+
+            let name = env::var(format!("{}NAME", prefix)).unwrap_or(format!("dest-{}", index));
+            destinations.push(Self::from_vars(
+                name,
+                &format!("{}RPC_URL", prefix),
+                &format!("{}CONTRACT_ADDRESS", prefix),
+                &prefix,
+                &prefix,
+            )?);
+        }
+
+        if destinations.is_empty() {
+            destinations.push(Self::from_vars(
+                "default".to_string(),
+                "DEST_RPC_URL",
+                "CONTRACT_ADDRESS",
+                "",
+                "DEST_",
+            )?);
+        }
+
+        for (index, destination) in destinations.iter().enumerate() {
+            if destinations[..index]
+                .iter()
+                .any(|other| other.name == destination.name)
+            {
+                anyhow::bail!("Duplicate destination name {}", destination.name);
+            }
+        }
+
+        Ok(destinations)
+    }
+
+    fn from_vars(
+        name: String,
+        rpc_url_var: &str,
+        contract_address_var: &str,
+        signer_prefix: &str,
+        gas_prefix: &str,
+    ) -> Result<Self> {
+        let rpc_url = require_secret(rpc_url_var)?
+            .parse()
+            .with_context(|| format!("Failed to parse {}", rpc_url_var))?;
+        let contract_address = env::var(contract_address_var)
+            .with_context(|| format!("{} not set", contract_address_var))?
+            .parse()
+            .with_context(|| format!("Failed to parse {}", contract_address_var))?;
+        let signer = signer_from_env(signer_prefix)?;
+        let relayer_address = signer.address();
+
+        Ok(Self {
+            name,
+            rpc_url,
+            contract_address,
+            wallet: EthereumWallet::from(signer),
+            relayer_address,
+            gas_limit: parse_optional(&format!("{}GAS_LIMIT", gas_prefix))?,
+            fees: FeePolicy::from_env(gas_prefix)?,
+        })
+    }
+}
+
+pub(crate) fn parse_optional<T: std::str::FromStr>(var: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    env::var(var)
+        .ok()
+        .map(|value| value.parse())
+        .transpose()
+        .with_context(|| format!("Failed to parse {}", var))
+}
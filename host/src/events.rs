@@ -0,0 +1,333 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::Url;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+const SLOTS_PER_EPOCH: u64 = 32;
+
+/// Beacon API event topics that signal a new finalized checkpoint.
+pub const FINALITY_TOPICS: &str = "finalized_checkpoint,light_client_finality_update";
+
+/// A message of a `text/event-stream` body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseMessage {
+    pub event: String,
+    pub data: String,
+}
+
+/// Incremental parser of a `text/event-stream` body, fed chunks as they arrive.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Parse `chunk`, returning the messages it completes.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseMessage> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            messages.extend(self.line(&String::from_utf8_lossy(&line)));
+        }
+        messages
+    }
+
+    /// Parse the rest of a body that ended, returning the message it leaves undispatched, if any.
+    pub fn finish(&mut self) -> Option<SseMessage> {
+        let line: Vec<u8> = std::mem::take(&mut self.buffer);
+        let message = self.line(&String::from_utf8_lossy(&line));
+        message.or_else(|| self.dispatch())
+    }
+
+    /// Parse one line, returning the message it dispatches, if any.
+    fn line(&mut self, line: &str) -> Option<SseMessage> {
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        // A blank line dispatches the message.
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseMessage> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseMessage {
+            event: event.unwrap_or("message".to_string()),
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
+
+/// The finalized slot a beacon API event announces, if it is a finality event.
+pub fn finalized_slot(message: &SseMessage) -> Result<Option<u64>> {
+    if !FINALITY_TOPICS
+        .split(',')
+        .any(|topic| topic == message.event)
+    {
+        return Ok(None);
+    }
+
+    let data: Value = serde_json::from_str(&message.data)
+        .with_context(|| format!("Invalid {} event", message.event))?;
+    let number = |value: &Value| -> Result<u64> {
+        value
+            .as_str()
+            .context("Missing field")?
+            .parse()
+            .context("Invalid number")
+    };
+
+    match message.event.as_str() {
+        "finalized_checkpoint" => Ok(Some(number(&data["epoch"])? * SLOTS_PER_EPOCH)),
+        "light_client_finality_update" => {
+            let header = &data["data"]["finalized_header"]["beacon"];
+            Ok(Some(number(&header["slot"])?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Finality events from a beacon node's SSE event stream.
+///
+/// A background task subscribes to `/eth/v1/events` and reconnects whenever the stream drops or
+/// goes quiet. Callers keep polling on their own schedule and use [`FinalityEvents::next`] to wake
+/// up early, so a dropped stream only costs latency.
+pub struct FinalityEvents {
+    receiver: watch::Receiver<u64>,
+}
+
+impl FinalityEvents {
+    /// Subscribe to the finality events of the beacon node at `consensus_rpc`, until `shutdown`.
+    pub fn subscribe(consensus_rpc: &str, shutdown: CancellationToken) -> Result<Self> {
+        let mut url: Url = consensus_rpc
+            .parse()
+            .with_context(|| format!("Invalid consensus RPC URL {}", consensus_rpc))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid consensus RPC URL {}", consensus_rpc))?
+            .pop_if_empty()
+            .extend(["eth", "v1", "events"]);
+        url.query_pairs_mut().append_pair("topics", FINALITY_TOPICS);
+
+        let (sender, receiver) = watch::channel(0);
+        tokio::spawn(stream_events(url, sender, shutdown));
+        Ok(Self { receiver })
+    }
+
+    /// Wait for a finalized slot newer than any seen before, and return it.
+    ///
+    /// Never returns once the event source has stopped.
+    pub async fn next(&mut self) -> u64 {
+        if self.receiver.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        *self.receiver.borrow_and_update()
+    }
+}
+
+/// Stream events from `url` into `sender`, reconnecting until `shutdown`.
+async fn stream_events(url: Url, sender: watch::Sender<u64>, shutdown: CancellationToken) {
+    const RECONNECT_DELAY_SECS: u64 = 12;
+    // A new checkpoint is finalized every epoch, and light client updates arrive more often.
+    const IDLE_TIMEOUT_SECS: u64 = 20 * 60;
+
+    let client = reqwest::Client::new();
+    loop {
+        let result = tokio::select! {
+            result = read_stream(&client, &url, &sender, Duration::from_secs(IDLE_TIMEOUT_SECS)) => result,
+            _ = shutdown.cancelled() => return,
+        };
+        match result {
+            Ok(()) => warn!("Beacon event stream closed, falling back to polling"),
+            Err(e) => warn!(
+                "Beacon event stream failed, falling back to polling: {:#}",
+                e
+            ),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY_SECS)) => {}
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+/// Read the event stream at `url` until it ends, publishing every new finalized slot.
+async fn read_stream(
+    client: &reqwest::Client,
+    url: &Url,
+    sender: &watch::Sender<u64>,
+    idle_timeout: Duration,
+) -> Result<()> {
+    let mut response = client
+        .get(url.clone())
+        .header("Accept", "text/event-stream")
+        .send()
+        .await?
+        .error_for_status()?;
+    info!("Subscribed to beacon finality events");
+
+    let mut parser = SseParser::default();
+    loop {
+        let chunk = tokio::time::timeout(idle_timeout, response.chunk())
+            .await
+            .map_err(|_| anyhow::anyhow!("No events for {:?}", idle_timeout))??;
+        let Some(chunk) = chunk else {
+            if let Some(message) = parser.finish() {
+                publish(&message, sender);
+            }
+            return Ok(());
+        };
+
+        for message in parser.push(&chunk) {
+            publish(&message, sender);
+        }
+    }
+}
+
+/// Publish the finalized slot `message` announces, if it is newer than any published before.
+fn publish(message: &SseMessage, sender: &watch::Sender<u64>) {
+    match finalized_slot(message) {
+        Ok(Some(slot)) => {
+            sender.send_if_modified(|latest| {
+                let newer = slot > *latest;
+                if newer {
+                    *latest = slot;
+                }
+                newer
+            });
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Ignoring beacon event: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(event: &str, data: &str) -> SseMessage {
+        SseMessage {
+            event: event.to_string(),
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_events() {
+        let mut parser = SseParser::default();
+        let messages = parser.push(b"event: finalized_checkpoint\ndata: {}\n\ndata:plain\n\n");
+        assert_eq!(
+            messages,
+            vec![
+                message("finalized_checkpoint", "{}"),
+                message("message", "plain")
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let mut parser = SseParser::default();
+        let messages = parser.push(b"event: update\ndata: {\ndata: \"a\": 1\ndata: }\n\n");
+        assert_eq!(messages, vec![message("update", "{\n\"a\": 1\n}")]);
+    }
+
+    #[test]
+    fn skips_comments_and_unknown_fields() {
+        let mut parser = SseParser::default();
+        let messages = parser.push(b": keep-alive\n\n:\nid: 1\nretry: 10\ndata: x\n: between\n\n");
+        assert_eq!(messages, vec![message("message", "x")]);
+    }
+
+    #[test]
+    fn handles_crlf_line_endings() {
+        let mut parser = SseParser::default();
+        let messages = parser.push(b"event: update\r\ndata: x\r\n\r\n");
+        assert_eq!(messages, vec![message("update", "x")]);
+    }
+
+    #[test]
+    fn parses_events_split_across_chunks() {
+        let body = b"event: update\r\ndata: first\r\n\r\nevent: update\ndata: second\n\n";
+        for split in 0..=body.len() {
+            let mut parser = SseParser::default();
+            let mut messages = parser.push(&body[..split]);
+            messages.extend(parser.push(&body[split..]));
+            assert_eq!(
+                messages,
+                vec![message("update", "first"), message("update", "second")],
+                "split at {}",
+                split
+            );
+        }
+
+        // One byte at a time.
+        let mut parser = SseParser::default();
+        let messages: Vec<_> = body.chunks(1).flat_map(|byte| parser.push(byte)).collect();
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[test]
+    fn finishes_trailing_event() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: update\ndata: x\n").is_empty());
+        assert_eq!(parser.finish(), Some(message("update", "x")));
+        assert_eq!(parser.finish(), None);
+
+        // Without the final line break either.
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: update\ndata: x").is_empty());
+        assert_eq!(parser.finish(), Some(message("update", "x")));
+
+        // An event without data is not dispatched.
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: update\n").is_empty());
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn reads_finalized_slots() {
+        assert_eq!(
+            finalized_slot(&message(
+                "finalized_checkpoint",
+                r#"{"block": "0x01", "state": "0x02", "epoch": "10"}"#
+            ))
+            .unwrap(),
+            Some(320)
+        );
+        assert_eq!(
+            finalized_slot(&message(
+                "light_client_finality_update",
+                r#"{"version": "deneb", "data": {"finalized_header": {"beacon": {"slot": "321"}}}}"#
+            ))
+            .unwrap(),
+            Some(321)
+        );
+        assert_eq!(finalized_slot(&message("head", "{}")).unwrap(), None);
+        assert!(finalized_slot(&message("finalized_checkpoint", "{}")).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::destination::parse_optional;
+use alloy::providers::utils::Eip1559Estimation;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Minimum fee increase, in percent, nodes require to accept a replacement transaction with the
+/// same nonce.
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u128 = 10;
+
+/// EIP-1559 fees of a transaction, in wei.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl From<Eip1559Estimation> for Fees {
+    fn from(estimate: Eip1559Estimation) -> Self {
+        Self {
+            max_fee_per_gas: estimate.max_fee_per_gas,
+            max_priority_fee_per_gas: estimate.max_priority_fee_per_gas,
+        }
+    }
+}
+
+/// How the fees of update transactions are chosen, and bumped when a transaction stalls.
+#[derive(Debug, Clone)]
+pub struct FeePolicy {
+    /// Max fee per gas of the first transaction. Estimated if unset.
+    pub max_fee_per_gas: Option<u128>,
+    /// Max priority fee per gas of the first transaction. Estimated if unset.
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// Upper bound on the max fee per gas, including after bumps.
+    pub max_fee_per_gas_cap: Option<u128>,
+    /// Upper bound on the max priority fee per gas, including after bumps.
+    pub max_priority_fee_per_gas_cap: Option<u128>,
+    /// Percentage both fees are raised by when replacing a stalled transaction.
+    pub bump_percent: u128,
+    /// Time to wait for a transaction to be mined before replacing it.
+    pub stall_timeout: Duration,
+    /// Time to wait for any transaction of an update to be mined before giving up.
+    pub max_wait: Duration,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas_cap: None,
+            max_priority_fee_per_gas_cap: None,
+            bump_percent: 20,
+            stall_timeout: Duration::from_secs(120),
+            max_wait: Duration::from_secs(1800),
+        }
+    }
+}
+
+impl FeePolicy {
+    /// Read the fee policy from the environment variables starting with `prefix`, such as
+    /// `<prefix>MAX_FEE_PER_GAS_CAP`.
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        let default = Self::default();
+        let var = |name: &str| format!("{}{}", prefix, name);
+
+        let policy = Self {
+            max_fee_per_gas: parse_optional(&var("MAX_FEE_PER_GAS"))?,
+            max_priority_fee_per_gas: parse_optional(&var("MAX_PRIORITY_FEE_PER_GAS"))?,
+            max_fee_per_gas_cap: parse_optional(&var("MAX_FEE_PER_GAS_CAP"))?,
+            max_priority_fee_per_gas_cap: parse_optional(&var("MAX_PRIORITY_FEE_PER_GAS_CAP"))?,
+            bump_percent: parse_optional(&var("FEE_BUMP_PERCENT"))?.unwrap_or(default.bump_percent),
+            stall_timeout: parse_optional(&var("STALL_TIMEOUT_SECS"))?
+                .map(Duration::from_secs)
+                .unwrap_or(default.stall_timeout),
+            max_wait: parse_optional(&var("RELAY_TIMEOUT_SECS"))?
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_wait),
+        };
+
+        if policy.bump_percent < MIN_REPLACEMENT_BUMP_PERCENT {
+            anyhow::bail!(
+                "{} must be at least {}",
+                var("FEE_BUMP_PERCENT"),
+                MIN_REPLACEMENT_BUMP_PERCENT
+            );
+        }
+        Ok(policy)
+    }
+
+    /// Fees of the first transaction of an update, given the node's current `estimate`.
+    pub fn initial(&self, estimate: Fees) -> Fees {
+        self.capped(Fees {
+            max_fee_per_gas: self.max_fee_per_gas.unwrap_or(estimate.max_fee_per_gas),
+            max_priority_fee_per_gas: self
+                .max_priority_fee_per_gas
+                .unwrap_or(estimate.max_priority_fee_per_gas),
+        })
+    }
+
+    /// Fees of a transaction replacing a stalled one sent with `fees`, given the node's current
+    /// `estimate`.
+    ///
+    /// Both fees are raised by `bump_percent`, or to the current estimate if it is higher. Returns
+    /// `None` if the caps don't leave room for a bump nodes would accept as a replacement.
+    pub fn bump(&self, fees: Fees, estimate: Fees) -> Option<Fees> {
+        let bumped = self.capped(Fees {
+            max_fee_per_gas: bump(fees.max_fee_per_gas, self.bump_percent)
+                .max(estimate.max_fee_per_gas),
+            max_priority_fee_per_gas: bump(fees.max_priority_fee_per_gas, self.bump_percent)
+                .max(estimate.max_priority_fee_per_gas),
+        });
+
+        let min_max_fee = bump(fees.max_fee_per_gas, MIN_REPLACEMENT_BUMP_PERCENT);
+        let min_priority_fee = bump(fees.max_priority_fee_per_gas, MIN_REPLACEMENT_BUMP_PERCENT);
+        if bumped.max_fee_per_gas < min_max_fee
+            || bumped.max_priority_fee_per_gas < min_priority_fee
+        {
+            return None;
+        }
+        Some(bumped)
+    }
+
+    /// Clamp `fees` to the caps. The priority fee never exceeds the max fee.
+    fn capped(&self, fees: Fees) -> Fees {
+        let max_fee_per_gas = self
+            .max_fee_per_gas_cap
+            .map_or(fees.max_fee_per_gas, |cap| fees.max_fee_per_gas.min(cap));
+        let max_priority_fee_per_gas = self
+            .max_priority_fee_per_gas_cap
+            .map_or(fees.max_priority_fee_per_gas, |cap| {
+                fees.max_priority_fee_per_gas.min(cap)
+            })
+            .min(max_fee_per_gas);
+
+        Fees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+}
+
+/// Raise `fee` by `percent`, rounding up so that small fees still increase.
+fn bump(fee: u128, percent: u128) -> u128 {
+    fee.saturating_mul(100 + percent).div_ceil(100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> Fees {
+        Fees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+
+    #[test]
+    fn bumps_by_percentage_rounding_up() {
+        assert_eq!(bump(100, 20), 120);
+        assert_eq!(bump(1, 10), 2);
+        assert_eq!(bump(0, 10), 0);
+        assert_eq!(bump(u128::MAX, 10), u128::MAX.div_ceil(100));
+    }
+
+    #[test]
+    fn bumps_stalled_fees() {
+        let policy = FeePolicy::default();
+        assert_eq!(policy.bump(fees(100, 10), fees(50, 5)), Some(fees(120, 12)));
+        // A higher estimate wins over the bump.
+        assert_eq!(
+            policy.bump(fees(100, 10), fees(200, 11)),
+            Some(fees(200, 12))
+        );
+    }
+
+    #[test]
+    fn clamps_fees_to_caps() {
+        let policy = FeePolicy {
+            max_fee_per_gas_cap: Some(150),
+            max_priority_fee_per_gas_cap: Some(20),
+            ..FeePolicy::default()
+        };
+        assert_eq!(policy.initial(fees(200, 30)), fees(150, 20));
+        assert_eq!(policy.bump(fees(130, 15), fees(0, 0)), Some(fees(150, 18)));
+
+        // The priority fee never exceeds the max fee.
+        let policy = FeePolicy {
+            max_fee_per_gas_cap: Some(10),
+            ..FeePolicy::default()
+        };
+        assert_eq!(policy.initial(fees(100, 50)), fees(10, 10));
+    }
+
+    #[test]
+    fn refuses_bumps_the_caps_leave_no_room_for() {
+        let policy = FeePolicy {
+            max_fee_per_gas_cap: Some(105),
+            ..FeePolicy::default()
+        };
+        // The cap only leaves room for a 5% bump, less than nodes accept as a replacement.
+        assert_eq!(policy.bump(fees(100, 10), fees(0, 0)), None);
+    }
+
+    #[test]
+    fn uses_configured_initial_fees() {
+        let policy = FeePolicy {
+            max_fee_per_gas: Some(70),
+            ..FeePolicy::default()
+        };
+        assert_eq!(policy.initial(fees(100, 10)), fees(70, 10));
+    }
+}
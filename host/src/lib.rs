@@ -1,8 +1,9 @@
 use alloy_primitives::B256;
 use helios_consensus_core::{
-    calc_sync_period,
+    apply_finality_update, apply_update, calc_sync_period,
     consensus_spec::MainnetConsensusSpec,
-    types::{BeaconBlock, Update},
+    types::{BeaconBlock, Forks, LightClientStore, Update},
+    verify_finality_update, verify_update,
 };
 use helios_ethereum::rpc::ConsensusRpc;
 use helios_ethereum::{
@@ -11,16 +12,38 @@ use helios_ethereum::{
     rpc::http_rpc::HttpRpc,
 };
 
+use anyhow::Context;
+use r0vm_helios_primitives::types::ProofInputs;
 use std::sync::Arc;
 use tokio::sync::{mpsc::channel, watch};
 use tree_hash::TreeHash;
 
+pub mod archive;
+pub mod catchup;
+pub mod config;
+pub mod consistency;
+pub mod contract;
+pub mod coordination;
+pub mod db;
+pub mod destination;
+pub mod events;
+pub mod fees;
+pub mod metrics;
+pub mod pipeline;
+pub mod policy;
+pub mod prover;
+pub mod revert;
+pub mod secrets;
+pub mod status;
+pub mod storage;
+pub mod store;
+
 pub const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u8 = 128;
 
 /// Fetch updates for client
 pub async fn get_updates(
     client: &Inner<MainnetConsensusSpec, HttpRpc>,
-) -> Vec<Update<MainnetConsensusSpec>> {
+) -> anyhow::Result<Vec<Update<MainnetConsensusSpec>>> {
     let period =
         calc_sync_period::<MainnetConsensusSpec>(client.store.finalized_header.beacon().slot);
 
@@ -28,61 +51,121 @@ pub async fn get_updates(
         .rpc
         .get_updates(period, MAX_REQUEST_LIGHT_CLIENT_UPDATES)
         .await
-        .unwrap();
+        .map_err(|e| anyhow::anyhow!("Failed to fetch updates for period {}: {}", period, e))?;
+
+    Ok(updates)
+}
+
+/// Fetch the latest finalized slot of the source chain.
+pub async fn get_latest_finalized_slot() -> anyhow::Result<u64> {
+    let client = new_client()?;
+    let finality_update = client
+        .rpc
+        .get_finality_update()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch finality update: {}", e))?;
 
-    updates.clone()
+    Ok(finality_update.finalized_header().beacon().slot)
 }
 
 /// Fetch latest checkpoint from chain to bootstrap client to the latest state.
-pub async fn get_latest_checkpoint() -> B256 {
+pub async fn get_latest_checkpoint() -> anyhow::Result<B256> {
     let cf = checkpoints::CheckpointFallback::new()
         .build()
         .await
-        .unwrap();
+        .map_err(|e| anyhow::anyhow!("Failed to build checkpoint fallback: {}", e))?;
 
-    let chain_id = std::env::var("SOURCE_CHAIN_ID").expect("SOURCE_CHAIN_ID not set");
-    let network = Network::from_chain_id(chain_id.parse().unwrap()).unwrap();
+    let network = source_network()?;
 
-    cf.fetch_latest_checkpoint(&network).await.unwrap()
+    cf.fetch_latest_checkpoint(&network)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch latest checkpoint: {}", e))
 }
 
 /// Fetch checkpoint from a slot number.
-pub async fn get_checkpoint(slot: u64) -> B256 {
-    let consensus_rpc = std::env::var("SOURCE_CONSENSUS_RPC_URL").unwrap();
-    let chain_id = std::env::var("SOURCE_CHAIN_ID").unwrap();
-    let network = Network::from_chain_id(chain_id.parse().unwrap()).unwrap();
-    let base_config = network.to_base_config();
+pub async fn get_checkpoint(slot: u64) -> anyhow::Result<B256> {
+    let client = new_client()?;
 
-    let config = Config {
-        consensus_rpc: consensus_rpc.to_string(),
-        execution_rpc: String::new(),
-        chain: base_config.chain,
-        forks: base_config.forks,
-        strict_checkpoint_age: false,
-        ..Default::default()
-    };
+    let block: BeaconBlock<MainnetConsensusSpec> = client
+        .rpc
+        .get_block(slot)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch block at slot {}: {}", slot, e))?;
 
-    let (block_send, _) = channel(256);
-    let (finalized_block_send, _) = watch::channel(None);
-    let (channel_send, _) = watch::channel(None);
-    let client = Inner::<MainnetConsensusSpec, HttpRpc>::new(
-        &consensus_rpc,
-        block_send,
-        finalized_block_send,
-        channel_send,
-        Arc::new(config),
-    );
+    Ok(B256::from_slice(block.tree_hash_root().as_ref()))
+}
+
+/// Setup a client from a checkpoint.
+pub async fn get_client(checkpoint: B256) -> anyhow::Result<Inner<MainnetConsensusSpec, HttpRpc>> {
+    let mut client = new_client()?;
+
+    client.bootstrap(checkpoint).await.map_err(|e| {
+        anyhow::anyhow!("Failed to bootstrap from checkpoint {}: {}", checkpoint, e)
+    })?;
+    Ok(client)
+}
 
-    let block: BeaconBlock<MainnetConsensusSpec> = client.rpc.get_block(slot).await.unwrap();
+/// Setup a client from a previously persisted light client store, without bootstrapping.
+pub fn get_client_from_store(
+    store: LightClientStore<MainnetConsensusSpec>,
+) -> anyhow::Result<Inner<MainnetConsensusSpec, HttpRpc>> {
+    let mut client = new_client()?;
 
-    B256::from_slice(block.tree_hash_root().as_ref())
+    client.store = store;
+    Ok(client)
 }
 
-/// Setup a client from a checkpoint.
-pub async fn get_client(checkpoint: B256) -> Inner<MainnetConsensusSpec, HttpRpc> {
-    let consensus_rpc = std::env::var("SOURCE_CONSENSUS_RPC_URL").unwrap();
-    let chain_id = std::env::var("SOURCE_CHAIN_ID").unwrap();
-    let network = Network::from_chain_id(chain_id.parse().unwrap()).unwrap();
+/// Apply the sync committee updates and finality update, if any, in `inputs` to `store`.
+///
+/// This mirrors the state transition proven by the guest program, so the resulting store matches
+/// the state the contract holds once the proof has been relayed.
+pub fn advance_store(
+    store: &mut LightClientStore<MainnetConsensusSpec>,
+    inputs: &ProofInputs,
+) -> anyhow::Result<()> {
+    apply_updates(
+        store,
+        &inputs.sync_committee_updates,
+        inputs.expected_current_slot,
+        inputs.genesis_root,
+        &inputs.forks,
+    )?;
+
+    if let Some(finality_update) = &inputs.finality_update {
+        verify_finality_update(
+            finality_update,
+            inputs.expected_current_slot,
+            store,
+            inputs.genesis_root,
+            &inputs.forks,
+        )
+        .map_err(|e| anyhow::anyhow!("Finality update is invalid: {}", e))?;
+        apply_finality_update(store, finality_update);
+    }
+
+    Ok(())
+}
+
+/// Verify and apply the sync committee `updates` to `store`, as the guest program does.
+pub fn apply_updates(
+    store: &mut LightClientStore<MainnetConsensusSpec>,
+    updates: &[Update<MainnetConsensusSpec>],
+    expected_current_slot: u64,
+    genesis_root: B256,
+    forks: &Forks,
+) -> anyhow::Result<()> {
+    for update in updates {
+        verify_update(update, expected_current_slot, store, genesis_root, forks)
+            .map_err(|e| anyhow::anyhow!("Update is invalid: {}", e))?;
+        apply_update(store, update);
+    }
+    Ok(())
+}
+
+/// Create a client for the source chain without bootstrapping it.
+fn new_client() -> anyhow::Result<Inner<MainnetConsensusSpec, HttpRpc>> {
+    let consensus_rpc = secrets::require_secret("SOURCE_CONSENSUS_RPC_URL")?;
+    let network = source_network()?;
     let base_config = network.to_base_config();
 
     let config = Config {
@@ -98,14 +181,21 @@ pub async fn get_client(checkpoint: B256) -> Inner<MainnetConsensusSpec, HttpRpc
     let (finalized_block_send, _) = watch::channel(None);
     let (channel_send, _) = watch::channel(None);
 
-    let mut client = Inner::new(
+    Ok(Inner::new(
         &consensus_rpc,
         block_send,
         finalized_block_send,
         channel_send,
         Arc::new(config),
-    );
+    ))
+}
 
-    client.bootstrap(checkpoint).await.unwrap();
-    client
+/// The source chain's network, from `SOURCE_CHAIN_ID`.
+fn source_network() -> anyhow::Result<Network> {
+    let chain_id: u64 = std::env::var("SOURCE_CHAIN_ID")
+        .context("SOURCE_CHAIN_ID not set")?
+        .parse()
+        .context("Invalid SOURCE_CHAIN_ID")?;
+    Network::from_chain_id(chain_id)
+        .map_err(|e| anyhow::anyhow!("Unsupported source chain {}: {}", chain_id, e))
 }
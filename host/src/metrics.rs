@@ -0,0 +1,193 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+/// Prometheus metrics exported by the operator.
+///
+/// Metrics of the destination contracts and their relays are labelled by `destination`.
+pub struct Metrics {
+    registry: Registry,
+    /// Latest head slot stored in the contract.
+    pub contract_head_slot: IntGaugeVec,
+    /// Age of the contract head against wall-clock time, in seconds.
+    pub contract_head_age_seconds: IntGaugeVec,
+    /// Seconds left until the contract head is older than `MAX_SLOT_AGE` and can no longer be
+    /// proven against. Negative once the deadline has passed.
+    pub contract_head_deadline_seconds: IntGaugeVec,
+    /// Latest finalized slot reported by the source chain.
+    pub source_finalized_slot: IntGauge,
+    /// Wall-clock time spent generating a proof.
+    pub proving_duration_seconds: Histogram,
+    /// Total cycles of the last proven session.
+    pub proof_total_cycles: IntGauge,
+    /// User cycles of the last proven session.
+    pub proof_user_cycles: IntGauge,
+    /// Number of segments of the last proven session.
+    pub proof_segments: IntGauge,
+    /// Number of sync committee updates included in each proof.
+    pub sync_committee_updates_per_proof: Histogram,
+    /// Gas used by the last relayed update transaction.
+    pub relay_gas_used: IntGaugeVec,
+    /// Effective gas price of the last relayed update transaction, in wei.
+    pub relay_gas_price_wei: IntGaugeVec,
+    /// Time between sending an update transaction and its confirmation.
+    pub tx_confirmation_seconds: HistogramVec,
+    /// Errors encountered by the operator, labelled by stage.
+    pub errors_total: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("r0vm_helios".to_string()), None)
+            .expect("valid registry prefix");
+
+        let contract_head_slot = IntGaugeVec::new(
+            Opts::new(
+                "contract_head_slot",
+                "Latest head slot stored in the contract",
+            ),
+            &["destination"],
+        )
+        .unwrap();
+        let contract_head_age_seconds = IntGaugeVec::new(
+            Opts::new(
+                "contract_head_age_seconds",
+                "Age of the contract head against wall-clock time",
+            ),
+            &["destination"],
+        )
+        .unwrap();
+        let contract_head_deadline_seconds = IntGaugeVec::new(
+            Opts::new(
+                "contract_head_deadline_seconds",
+                "Seconds until the contract head exceeds MAX_SLOT_AGE",
+            ),
+            &["destination"],
+        )
+        .unwrap();
+        let source_finalized_slot = IntGauge::new(
+            "source_finalized_slot",
+            "Latest finalized slot of the source chain",
+        )
+        .unwrap();
+        let proving_duration_seconds = Histogram::with_opts(
+            HistogramOpts::new("proving_duration_seconds", "Time spent generating a proof")
+                .buckets(vec![
+                    30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0, 7200.0,
+                ]),
+        )
+        .unwrap();
+        let proof_total_cycles =
+            IntGauge::new("proof_total_cycles", "Total cycles of the last proof").unwrap();
+        let proof_user_cycles =
+            IntGauge::new("proof_user_cycles", "User cycles of the last proof").unwrap();
+        let proof_segments = IntGauge::new("proof_segments", "Segments of the last proof").unwrap();
+        let sync_committee_updates_per_proof = Histogram::with_opts(
+            HistogramOpts::new(
+                "sync_committee_updates_per_proof",
+                "Number of sync committee updates included in each proof",
+            )
+            .buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]),
+        )
+        .unwrap();
+        let relay_gas_used = IntGaugeVec::new(
+            Opts::new("relay_gas_used", "Gas used by the last update transaction"),
+            &["destination"],
+        )
+        .unwrap();
+        let relay_gas_price_wei = IntGaugeVec::new(
+            Opts::new(
+                "relay_gas_price_wei",
+                "Effective gas price of the last update transaction",
+            ),
+            &["destination"],
+        )
+        .unwrap();
+        let tx_confirmation_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "tx_confirmation_seconds",
+                "Time between sending an update transaction and its confirmation",
+            )
+            .buckets(vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]),
+            &["destination"],
+        )
+        .unwrap();
+        let errors_total = IntCounterVec::new(
+            Opts::new("errors_total", "Errors encountered by the operator"),
+            &["stage"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(contract_head_slot.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(contract_head_age_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(contract_head_deadline_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(source_finalized_slot.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(proving_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(proof_total_cycles.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(proof_user_cycles.clone()))
+            .unwrap();
+        registry.register(Box::new(proof_segments.clone())).unwrap();
+        registry
+            .register(Box::new(sync_committee_updates_per_proof.clone()))
+            .unwrap();
+        registry.register(Box::new(relay_gas_used.clone())).unwrap();
+        registry
+            .register(Box::new(relay_gas_price_wei.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(tx_confirmation_seconds.clone()))
+            .unwrap();
+        registry.register(Box::new(errors_total.clone())).unwrap();
+
+        Self {
+            registry,
+            contract_head_slot,
+            contract_head_age_seconds,
+            contract_head_deadline_seconds,
+            source_finalized_slot,
+            proving_duration_seconds,
+            proof_total_cycles,
+            proof_user_cycles,
+            proof_segments,
+            sync_committee_updates_per_proof,
+            relay_gas_used,
+            relay_gas_price_wei,
+            tx_confirmation_seconds,
+            errors_total,
+        }
+    }
+
+    /// Count an error in `stage`.
+    pub fn record_error(&self, stage: &str) {
+        self.errors_total.with_label_values(&[stage]).inc();
+    }
+
+    /// Encode all metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics are valid");
+        String::from_utf8(buffer).expect("metrics are valid utf-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
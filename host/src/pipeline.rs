@@ -0,0 +1,1935 @@
+//! The operator's pipeline: a fetch stage that requests updates from the source chain, a prove
+//! stage and a relay stage that submits the proofs to every destination contract.
+
+use crate::archive::ProofArchive;
+use crate::catchup::{plan_update, UpdatePlan, DEFAULT_MAX_PERIODS_PER_PROOF};
+use crate::contract::R0VMHelios;
+use crate::coordination::{Coordinator, Role, Superseded};
+use crate::db::{ConfirmedRelay, JobStage, RelayState, StateDb, UpdateJob};
+use crate::destination::Destination;
+use crate::events::FinalityEvents;
+use crate::metrics::Metrics;
+use crate::policy::{Decision, PolicyInputs, UpdatePolicy};
+use crate::prover::{prove_async, prover_from_env, HeliosProver, ReceiptKind};
+use crate::revert::{revert_data, RecoveryAction, UpdateRevert};
+use crate::status::{DestinationStatus, FetchStatus, OperatorStatus, RelayStatus};
+use crate::storage::StorageProofs;
+use crate::{
+    advance_store, apply_updates, get_client, get_client_from_store, get_latest_finalized_slot,
+    get_updates,
+};
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::rpc::types::TransactionReceipt;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use anyhow::{Context, Result};
+use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
+use helios_consensus_core::types::{LightClientHeader, LightClientStore, Update};
+use helios_ethereum::consensus::Inner;
+use helios_ethereum::rpc::http_rpc::HttpRpc;
+use helios_ethereum::rpc::ConsensusRpc;
+use log::{error, info, warn};
+use r0vm_helios_methods::R0VM_HELIOS_GUEST_ID;
+use r0vm_helios_primitives::types::ProofInputs;
+use risc0_zkvm::{Digest, Receipt};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tree_hash::TreeHash;
+
+/// Configuration and handles shared by the fetch, prove and relay stages.
+pub struct OperatorContext {
+    pub destinations: Vec<Destination>,
+    /// The source chain storage proven with each update, if any.
+    storage: Option<StorageProofs>,
+    pub db: StateDb,
+    archive: ProofArchive,
+    pub metrics: Arc<Metrics>,
+    pub status: Arc<RwLock<OperatorStatus>>,
+    pub shutdown: CancellationToken,
+    shutdown_timeout: Duration,
+    proof_max_lag_slots: Option<u64>,
+    max_periods_per_proof: usize,
+    loop_delay: Duration,
+    prover: Arc<dyn HeliosProver>,
+    receipt_kind: ReceiptKind,
+    policy: UpdatePolicy,
+    coordinator: Coordinator,
+}
+
+/// Progress of a job through the pipeline, broadcast from the prove and relay stages.
+#[derive(Debug, Clone)]
+enum JobEvent {
+    /// The job targeting `target_head` has been proven.
+    Proved { target_head: u64 },
+    /// `destination` confirmed the job targeting `target_head`.
+    Confirmed {
+        target_head: u64,
+        destination: String,
+    },
+    /// The job targeting `target_head` won't land on `destination`.
+    Failed {
+        target_head: u64,
+        destination: String,
+    },
+    /// The job targeting `target_head` was skipped on `destination`, which another operator
+    /// updated in the meantime.
+    Skipped {
+        target_head: u64,
+        destination: String,
+    },
+    /// The light client store at `head` diverges from a destination contract, and must be
+    /// bootstrapped again.
+    StoreDiverged { head: u64 },
+}
+
+/// Broadcasts [`JobEvent`]s to the stages, and counts the failures among them, which a lagging
+/// receiver could miss.
+#[derive(Clone)]
+struct JobEvents {
+    sender: broadcast::Sender<JobEvent>,
+    failures: Arc<AtomicUsize>,
+}
+
+impl JobEvents {
+    fn new() -> Self {
+        Self {
+            sender: broadcast::channel(64).0,
+            failures: Arc::default(),
+        }
+    }
+
+    fn send(&self, event: JobEvent) {
+        if matches!(event, JobEvent::Failed { .. }) {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        // No stage may be listening anymore.
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.sender.subscribe()
+    }
+
+    /// How many [`JobEvent::Failed`] have been sent.
+    fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
+
+/// The inputs of an update, and the store the contract will hold once it is relayed.
+pub struct FetchedUpdate {
+    pub from_head: u64,
+    pub target_head: u64,
+    pub encoded_proof_inputs: Vec<u8>,
+    pub next_store: LightClientStore<MainnetConsensusSpec>,
+}
+
+impl OperatorContext {
+    pub fn new(shutdown: CancellationToken, loop_delay_mins: u64) -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        let destinations = Destination::from_env().context("Failed to read destinations")?;
+        let storage = StorageProofs::from_env()?;
+
+        let db = StateDb::open(state_dir()).context("Failed to open operator state")?;
+        let archive = ProofArchive::open(archive_dir()).context("Failed to open proof archive")?;
+
+        let shutdown_timeout = Duration::from_secs(
+            env::var("SHUTDOWN_TIMEOUT_SECS")
+                .unwrap_or("120".to_string())
+                .parse()
+                .context("Failed to parse SHUTDOWN_TIMEOUT_SECS")?,
+        );
+        let proof_max_lag_slots = env::var("PROOF_MAX_LAG_SLOTS")
+            .ok()
+            .map(|slots| slots.parse())
+            .transpose()
+            .context("Failed to parse PROOF_MAX_LAG_SLOTS")?;
+        let max_periods_per_proof = env::var("MAX_PERIODS_PER_PROOF")
+            .ok()
+            .map(|periods| periods.parse())
+            .transpose()
+            .context("Failed to parse MAX_PERIODS_PER_PROOF")?
+            .unwrap_or(DEFAULT_MAX_PERIODS_PER_PROOF);
+
+        let prover = prover_from_env().context("Failed to set up prover")?;
+        let receipt_kind = receipt_kind_from_env().context("Failed to parse RECEIPT_KIND")?;
+        let policy = UpdatePolicy::from_env().context("Failed to read update policy")?;
+        if policy.watches_storage() && storage.is_none() {
+            anyhow::bail!("The storage update trigger requires STORAGE_CONTRACT_ADDRESS");
+        }
+        let coordinator =
+            Coordinator::from_env().context("Failed to read coordination settings")?;
+
+        let mut meta = db.meta().context("Failed to read operator state")?;
+        let status = OperatorStatus {
+            fetch_stage: FetchStatus::Starting,
+            proving_job: None,
+            source_finalized_head: None,
+            image_id: B256::from_slice(Digest::from(R0VM_HELIOS_GUEST_ID).as_bytes()),
+            destinations: destinations
+                .iter()
+                .map(|destination| {
+                    let status = DestinationStatus {
+                        contract_address: destination.contract_address,
+                        contract_head: None,
+                        contract_image_id: None,
+                        relayer_address: destination.relayer_address,
+                        relayer_balance: None,
+                        relaying_job: None,
+                        last_successful_relay: meta
+                            .last_confirmed
+                            .remove(&destination.name)
+                            .map(RelayStatus::from),
+                    };
+                    (destination.name.clone(), status)
+                })
+                .collect(),
+        };
+
+        Ok(Self {
+            destinations,
+            storage,
+            db,
+            archive,
+            metrics: Arc::new(Metrics::new()),
+            status: Arc::new(RwLock::new(status)),
+            shutdown,
+            shutdown_timeout,
+            proof_max_lag_slots,
+            max_periods_per_proof,
+            loop_delay: Duration::from_secs(60 * loop_delay_mins),
+            prover,
+            receipt_kind,
+            policy,
+            coordinator,
+        })
+    }
+
+    /// Look up a configured destination by name.
+    pub fn destination(&self, name: &str) -> Option<&Destination> {
+        self.destinations
+            .iter()
+            .find(|destination| destination.name == name)
+    }
+
+    /// Age of the `destination` contract's `head` and the contract's `MAX_SLOT_AGE`, in seconds.
+    async fn contract_head_age(&self, destination: &Destination, head: u64) -> Result<(i64, u64)> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, provider);
+
+        let head_timestamp: u64 = contract
+            .slotTimestamp(U256::from(head))
+            .call()
+            .await?
+            ._0
+            .try_into()?;
+        let max_slot_age: u64 = contract.MAX_SLOT_AGE().call().await?._0.try_into()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Ok((now as i64 - head_timestamp as i64, max_slot_age))
+    }
+
+    /// Read the current head of the `destination` contract.
+    pub async fn contract_head(&self, destination: &Destination) -> Result<u64> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, provider);
+
+        Ok(contract.head().call().await?._0.try_into()?)
+    }
+
+    /// Read the header root and sync committee hash the `destination` contract stores for `head`,
+    /// along with the sync committee period of `head`.
+    async fn contract_checkpoint(
+        &self,
+        destination: &Destination,
+        head: u64,
+    ) -> Result<(B256, B256, U256)> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, &provider);
+
+        let header = contract.headers(U256::from(head)).call().await?._0;
+        let period = contract
+            .getSyncCommitteePeriod(U256::from(head))
+            .call()
+            .await?
+            ._0;
+        let sync_committee = contract.syncCommittees(period).call().await?._0;
+
+        Ok((header, sync_committee, period))
+    }
+
+    /// Whether the header and sync committee the `destination` contract stores for `head` match
+    /// the store `inputs` start from.
+    async fn matches_contract(
+        &self,
+        destination: &Destination,
+        inputs: &ProofInputs,
+        head: u64,
+    ) -> Result<bool> {
+        let (header, sync_committee, _) = self.contract_checkpoint(destination, head).await?;
+
+        let store_header = B256::from_slice(
+            inputs
+                .store
+                .finalized_header
+                .beacon()
+                .tree_hash_root()
+                .as_ref(),
+        );
+        let store_sync_committee = B256::from_slice(
+            inputs
+                .store
+                .current_sync_committee
+                .tree_hash_root()
+                .as_ref(),
+        );
+
+        Ok(store_header == header && store_sync_committee == sync_committee)
+    }
+
+    /// Record the contract head, its age and its distance to `MAX_SLOT_AGE` in the metrics.
+    async fn observe_contract_head(&self, destination: &Destination, head: u64) -> Result<()> {
+        let (age, max_slot_age) = self.contract_head_age(destination, head).await?;
+
+        let labels = [destination.name.as_str()];
+        self.metrics
+            .contract_head_slot
+            .with_label_values(&labels)
+            .set(head as i64);
+        self.metrics
+            .contract_head_age_seconds
+            .with_label_values(&labels)
+            .set(age);
+        self.metrics
+            .contract_head_deadline_seconds
+            .with_label_values(&labels)
+            .set(max_slot_age as i64 - age);
+        Ok(())
+    }
+
+    /// Refresh the contract head, image ID and relayer balance of `destination` reported by
+    /// `/status`.
+    pub async fn refresh_status(&self, destination: &Destination, head: u64) -> Result<()> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, &provider);
+
+        let contract_image_id = contract.heliosImageID().call().await?._0;
+        let relayer_balance = provider.get_balance(destination.relayer_address).await?;
+
+        self.update_destination_status(&destination.name, |status| {
+            status.contract_head = Some(head);
+            status.contract_image_id = Some(contract_image_id);
+            status.relayer_balance = Some(relayer_balance);
+        });
+        Ok(())
+    }
+
+    fn set_fetch_status(&self, stage: FetchStatus) {
+        self.status.write().unwrap().fetch_stage = stage;
+    }
+
+    fn update_destination_status(&self, name: &str, update: impl FnOnce(&mut DestinationStatus)) {
+        if let Some(status) = self.status.write().unwrap().destinations.get_mut(name) {
+            update(status);
+        }
+    }
+
+    /// Persist the light client store the contracts hold once `job` is confirmed.
+    fn persist_store(&self, job: &UpdateJob) -> Result<()> {
+        let next_store = self.db.load_next_store(job)?;
+        self.db.save_light_client_store(&next_store)
+    }
+
+    /// Archive the proof of confirmed `job`, with the transactions that relayed it.
+    ///
+    /// Archiving is best effort: a failure is logged and doesn't affect the job.
+    fn archive_job(&self, job: &UpdateJob) {
+        let result = (|| {
+            let Some(receipt) = self.db.load_receipt(job)? else {
+                return Ok(None);
+            };
+            let mut relays = BTreeMap::new();
+            for destination in &job.destinations {
+                let relay = self.db.relay(job, destination)?;
+                if let (RelayState::Confirmed, Some(tx_hash)) = (relay.state, relay.tx_hash) {
+                    relays.insert(destination.clone(), tx_hash);
+                }
+            }
+            let id = self.archive.archive(
+                job,
+                &self.db.load_inputs(job)?,
+                &receipt,
+                Digest::from(R0VM_HELIOS_GUEST_ID),
+                relays,
+            )?;
+            Ok::<_, anyhow::Error>(Some(id))
+        })();
+
+        match result {
+            Ok(Some(id)) => info!("Archived proof of job {} as {}", job.id, id),
+            Ok(None) => warn!("Job {} has no receipt to archive", job.id),
+            Err(e) => error!("Failed to archive proof of job {}: {:#}", job.id, e),
+        }
+    }
+
+    /// Prove the encoded `ProofInputs` with the configured prover backend.
+    ///
+    /// Cancelling `cancel` stops the prover. The returned future still resolves once it has, so
+    /// callers should await it rather than drop it.
+    async fn prove(
+        &self,
+        encoded_proof_inputs: Vec<u8>,
+        cancel: CancellationToken,
+    ) -> Result<Receipt> {
+        let start = Instant::now();
+        let proof = prove_async(
+            self.prover.clone(),
+            encoded_proof_inputs,
+            self.receipt_kind,
+            cancel,
+        )
+        .await
+        .context("proving failed")?;
+
+        self.metrics
+            .proving_duration_seconds
+            .observe(start.elapsed().as_secs_f64());
+        if let Some(stats) = proof.stats {
+            self.metrics
+                .proof_total_cycles
+                .set(stats.total_cycles as i64);
+            self.metrics.proof_user_cycles.set(stats.user_cycles as i64);
+            self.metrics.proof_segments.set(stats.segments as i64);
+        }
+
+        Ok(proof.receipt)
+    }
+
+    /// Resolve once `job` is no longer worth proving for `destinations`, returning the reason.
+    ///
+    /// A job is obsolete once every destination's contract head has reached its target, or, if
+    /// `PROOF_MAX_LAG_SLOTS` is set and `check_lag` is true, once the source chain has finalized
+    /// more than that many slots past its target, so a fresh job would advance the contracts
+    /// further. Intermediate catch-up proofs lag the finalized head by design and skip the latter
+    /// check.
+    async fn wait_until_obsolete(
+        &self,
+        job: &UpdateJob,
+        destinations: &[String],
+        check_lag: bool,
+    ) -> String {
+        const CHECK_INTERVAL_SECS: u64 = 60;
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+            let mut reached = true;
+            for destination in destinations
+                .iter()
+                .filter_map(|name| self.destination(name))
+            {
+                match self.contract_head(destination).await {
+                    Ok(head) if head >= job.target_head => {}
+                    Ok(_) => reached = false,
+                    Err(e) => {
+                        warn!(
+                            "Failed to read contract head of {}: {}",
+                            destination.name, e
+                        );
+                        reached = false;
+                    }
+                }
+            }
+            if reached {
+                return format!("contract heads are already at {}", job.target_head);
+            }
+
+            if let Some(max_lag) = self.proof_max_lag_slots.filter(|_| check_lag) {
+                match get_latest_finalized_slot().await {
+                    Ok(finalized) if finalized > job.target_head + max_lag => {
+                        return format!(
+                            "source chain finalized slot {} is more than {} slots past the target",
+                            finalized, max_lag
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to read finalized slot: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Relay an update proof to the `destination` contract, recording every transaction sent in
+    /// the relay record of `job`.
+    ///
+    /// Fees follow the destination's `FeePolicy`. If no transaction has been mined after the
+    /// stall timeout, it is replaced by one with the same nonce and bumped fees, up to the caps.
+    /// Whichever of the transactions gets mined confirms the relay, so a late confirmation of the
+    /// original transaction isn't mistaken for a failure. A transaction sent by a previous run is
+    /// picked up where it was left rather than sent again.
+    async fn relay_update(
+        &self,
+        destination: &Destination,
+        job: &UpdateJob,
+        proof: Receipt,
+    ) -> Result<ConfirmedRelay> {
+        const POLL_INTERVAL_SECS: u64 = 5;
+
+        let policy = &destination.fees;
+        let seal: Bytes = risc0_ethereum_contracts::encode_seal(&proof)?.into();
+        let journal: Bytes = proof.journal.bytes.into();
+
+        let wallet_filler = ProviderBuilder::new()
+            .with_recommended_fillers()
+            .wallet(destination.wallet.clone())
+            .on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, wallet_filler.clone());
+
+        let mut relay = self.db.relay(job, &destination.name)?;
+        let (nonce, mut next_fees) = match (relay.state, relay.nonce) {
+            (RelayState::Submitted, Some(nonce)) => {
+                info!(
+                    "Waiting for the update transactions of job {} already sent to {}",
+                    job.id, destination.name
+                );
+                (nonce, None)
+            }
+            _ => {
+                // Another updater may have advanced the contract while the proof was generated.
+                let contract_head = self.contract_head(destination).await?;
+                if contract_head >= job.target_head {
+                    return Err(Superseded {
+                        contract_head,
+                        target_head: job.target_head,
+                    }
+                    .into());
+                }
+                self.simulate_update(destination, &seal, &journal, job.from_head)
+                    .await?;
+                let nonce = wallet_filler
+                    .get_transaction_count(destination.relayer_address)
+                    .await?;
+                let estimate = wallet_filler.estimate_eip1559_fees(None).await?;
+                (nonce, Some(policy.initial(estimate.into())))
+            }
+        };
+
+        let mut gas_limit = destination.gas_limit;
+        let started = Instant::now();
+        let mut sent_at = Instant::now();
+        loop {
+            if let Some(fees) = next_fees.take() {
+                let sent = async {
+                    let call =
+                        contract.update(seal.clone(), journal.clone(), U256::from(job.from_head));
+                    let gas = match gas_limit {
+                        Some(gas) => gas,
+                        None => *gas_limit.insert(call.estimate_gas().await?),
+                    };
+                    let pending_tx = call
+                        .nonce(nonce)
+                        .gas(gas)
+                        .max_fee_per_gas(fees.max_fee_per_gas)
+                        .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
+                        .send()
+                        .await?;
+                    Ok::<_, anyhow::Error>(*pending_tx.tx_hash())
+                }
+                .await;
+
+                match sent {
+                    Ok(tx_hash) => {
+                        info!(
+                            "Sent update transaction {:?} to {} with nonce {}, max fee {} and priority fee {}",
+                            tx_hash,
+                            destination.name,
+                            nonce,
+                            fees.max_fee_per_gas,
+                            fees.max_priority_fee_per_gas
+                        );
+                        relay =
+                            self.db
+                                .mark_submitted(job, &destination.name, tx_hash, nonce, fees)?;
+                        sent_at = Instant::now();
+                    }
+                    // A transaction sent earlier may have been mined in the meantime.
+                    Err(e) if !relay.tx_hashes.is_empty() => {
+                        warn!(
+                            "Failed to replace update transaction to {}: {}",
+                            destination.name, e
+                        );
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // Wait for any of the transactions sent so far to be mined and confirmed.
+            let mut stalled_at = Instant::now() + policy.stall_timeout;
+            while Instant::now() < stalled_at {
+                if let Some(receipt) = self.find_receipt(destination, &relay.tx_hashes).await? {
+                    if self.is_confirmed(destination, &receipt).await? {
+                        return self.confirm_relay(destination, job, receipt, sent_at);
+                    }
+                    // Mined, only waiting for confirmations.
+                    stalled_at = Instant::now() + policy.stall_timeout;
+                }
+                tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+            }
+
+            if started.elapsed() >= policy.max_wait {
+                return Err(anyhow::anyhow!(
+                    "No update transaction was mined within {:?}",
+                    policy.max_wait
+                ));
+            }
+
+            let mined_nonce = wallet_filler
+                .get_transaction_count(destination.relayer_address)
+                .await?;
+            if mined_nonce > nonce {
+                if self
+                    .find_receipt(destination, &relay.tx_hashes)
+                    .await?
+                    .is_some()
+                {
+                    continue;
+                }
+                return Err(anyhow::anyhow!(
+                    "Nonce {} was used by another transaction",
+                    nonce
+                ));
+            }
+
+            let fees = relay.fees.context("Submitted relay has no fees")?;
+            let estimate = wallet_filler.estimate_eip1559_fees(None).await?;
+            match policy.bump(fees, estimate.into()) {
+                Some(bumped) => {
+                    info!(
+                        "Update transaction to {} stalled for {:?}, replacing it",
+                        destination.name, policy.stall_timeout
+                    );
+                    next_fees = Some(bumped);
+                }
+                None => warn!(
+                    "Update transaction to {} stalled, but the fee caps leave no room for a replacement",
+                    destination.name
+                ),
+            }
+        }
+    }
+
+    /// Simulate the update with `eth_call` from the relayer, so a revert is caught before paying
+    /// for a transaction. Returns the decoded [`UpdateRevert`] as the error if it would revert.
+    ///
+    /// While the contract may not have caught up with the head the update builds on yet, the
+    /// simulation is retried a few times.
+    async fn simulate_update(
+        &self,
+        destination: &Destination,
+        seal: &Bytes,
+        journal: &Bytes,
+        from_head: u64,
+    ) -> Result<()> {
+        const MAX_ATTEMPTS: u32 = 5;
+        const RETRY_DELAY_SECS: u64 = 12;
+
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, &provider);
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let result = contract
+                .update(seal.clone(), journal.clone(), U256::from(from_head))
+                .from(destination.relayer_address)
+                .call()
+                .await;
+            let revert = match result {
+                Ok(_) => return Ok(()),
+                Err(e) => match revert_data(&e) {
+                    Some(data) => UpdateRevert::decode(&data),
+                    None => return Err(e.into()),
+                },
+            };
+
+            if revert.recovery() != RecoveryAction::Wait || attempt >= MAX_ATTEMPTS {
+                return Err(revert.into());
+            }
+            info!(
+                "Simulated update of {} reverted with {}, retrying in {}s",
+                destination.name, revert, RETRY_DELAY_SECS
+            );
+            tokio::time::sleep(Duration::from_secs(RETRY_DELAY_SECS)).await;
+        }
+    }
+
+    /// The receipt of whichever of `tx_hashes` has been mined on `destination`, if any.
+    async fn find_receipt(
+        &self,
+        destination: &Destination,
+        tx_hashes: &[B256],
+    ) -> Result<Option<TransactionReceipt>> {
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        for tx_hash in tx_hashes {
+            if let Some(receipt) = provider.get_transaction_receipt(*tx_hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether `receipt` has the required number of confirmations on `destination`.
+    async fn is_confirmed(
+        &self,
+        destination: &Destination,
+        receipt: &TransactionReceipt,
+    ) -> Result<bool> {
+        const NUM_CONFIRMATIONS: u64 = 3;
+
+        let Some(block_number) = receipt.block_number else {
+            return Ok(false);
+        };
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let latest = provider.get_block_number().await?;
+        Ok(latest + 1 >= block_number + NUM_CONFIRMATIONS)
+    }
+
+    /// Record the outcome of the mined update transaction of `job` on `destination`.
+    fn confirm_relay(
+        &self,
+        destination: &Destination,
+        job: &UpdateJob,
+        receipt: TransactionReceipt,
+        sent_at: Instant,
+    ) -> Result<ConfirmedRelay> {
+        // If status is false, it reverted.
+        if !receipt.status() {
+            error!("Transaction reverted!");
+            self.db
+                .mark_relay_failed(job, &destination.name, "Transaction reverted!")?;
+            return Err(anyhow::anyhow!("Transaction reverted!"));
+        }
+
+        let labels = [destination.name.as_str()];
+        self.metrics
+            .tx_confirmation_seconds
+            .with_label_values(&labels)
+            .observe(sent_at.elapsed().as_secs_f64());
+        self.metrics
+            .relay_gas_used
+            .with_label_values(&labels)
+            .set(receipt.gas_used as i64);
+        self.metrics
+            .relay_gas_price_wei
+            .with_label_values(&labels)
+            .set(receipt.effective_gas_price as i64);
+
+        let confirmed = self
+            .db
+            .mark_confirmed(job, &destination.name, receipt.transaction_hash)?;
+        info!(
+            "Successfully updated {} to new head block! Tx hash: {:?}",
+            destination.name, receipt.transaction_hash
+        );
+
+        Ok(confirmed)
+    }
+
+    /// Fetch values for an 'update' proof for the R0VM Helios contracts of `destinations`,
+    /// starting from the head `client` is synced to, and record them as a new job.
+    ///
+    /// `destination` is the first of `destinations`, and is read on behalf of the group.
+    pub async fn request_update(
+        &self,
+        client: &mut Inner<MainnetConsensusSpec, HttpRpc>,
+        destination: &Destination,
+        destinations: Vec<String>,
+    ) -> Result<Option<UpdateJob>> {
+        let Some(update) = self.fetch_update(client, destination).await? else {
+            return Ok(None);
+        };
+
+        let job = self.db.create_job(
+            update.from_head,
+            update.target_head,
+            destinations,
+            &update.encoded_proof_inputs,
+            &update.next_store,
+        )?;
+        info!(
+            "Fetched inputs for update from head {} to {} for {}",
+            job.from_head,
+            job.target_head,
+            job.destinations.join(", ")
+        );
+        Ok(Some(job))
+    }
+
+    /// Fetch values for an 'update' proof for the `destination` contract, starting from the head
+    /// `client` is synced to. Returns `None` if the contract is up to date.
+    pub async fn fetch_update(
+        &self,
+        client: &mut Inner<MainnetConsensusSpec, HttpRpc>,
+        destination: &Destination,
+    ) -> Result<Option<FetchedUpdate>> {
+        // Fetch required values.
+        let head = client.store.finalized_header.beacon().slot;
+        let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+        let contract = R0VMHelios::new(destination.contract_address, &provider);
+        let period: u64 = contract
+            .getSyncCommitteePeriod(U256::from(head))
+            .call()
+            .await?
+            ._0
+            .try_into()?;
+        let contract_next_sync_committee = contract
+            .syncCommittees(U256::from(period + 1))
+            .call()
+            .await?
+            ._0;
+
+        // Setup client.
+        let mut sync_committee_updates = get_updates(client).await?;
+        let finality_update = client
+            .rpc
+            .get_finality_update()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch finality update: {}", e))?;
+
+        // Check if contract is up to date
+        let latest_block = finality_update.finalized_header().beacon().slot;
+        self.metrics.source_finalized_slot.set(latest_block as i64);
+        self.status.write().unwrap().source_finalized_head = Some(latest_block);
+        if latest_block <= head {
+            info!("Contract is up to date. Nothing to update.");
+            return Ok(None);
+        }
+
+        // Optimization:
+        // Skip processing update inside program if next_sync_committee is already known.
+        // A long-lived store already holds it once an update for the period has been relayed.
+        // A freshly bootstrapped store doesn't, so if the contract already stores it, we apply the
+        // update locally to "sync" the helios client instead of proving it again.
+        if let Some(first_update) = sync_committee_updates.first() {
+            let next_sync_committee =
+                B256::from_slice(first_update.next_sync_committee().tree_hash_root().as_ref());
+            let store_next_sync_committee = client
+                .store
+                .next_sync_committee
+                .as_ref()
+                .map(|committee| B256::from_slice(committee.tree_hash_root().as_ref()));
+
+            if store_next_sync_committee == Some(next_sync_committee) {
+                info!("Next sync committee already in store, skipping update");
+                sync_committee_updates.remove(0);
+            } else if contract_next_sync_committee == next_sync_committee {
+                info!("Applying optimization, skipping update");
+                let temp_update = sync_committee_updates.remove(0);
+
+                client
+                    .verify_update(&temp_update)
+                    .map_err(|e| anyhow::anyhow!("Update is invalid: {}", e))?;
+                client.apply_update(&temp_update);
+            }
+        }
+
+        // Catch up over a sequence of proofs if the contract is too many periods behind to reach
+        // the finalized head in one. Intermediate proofs skip the finality update and the storage
+        // proofs, which the execution RPC may no longer serve for old blocks. Each of them must
+        // advance the finalized head, or the contract rejects it.
+        let from_slot = client.store.finalized_header.beacon().slot;
+        let advances = |updates: &[Update<MainnetConsensusSpec>]| {
+            let mut store = client.store.clone();
+            apply_updates(
+                &mut store,
+                updates,
+                client.expected_current_slot(),
+                client.config.chain.genesis_root,
+                &client.config.forks,
+            )
+            .is_ok()
+                && store.finalized_header.beacon().slot > from_slot
+        };
+        let (sync_committee_updates, finality_update, contract_storage_slots) =
+            match plan_update(sync_committee_updates, self.max_periods_per_proof, advances) {
+                UpdatePlan::Finalize(sync_committee_updates) => {
+                    // Fetch the storage proofs at the block the new finalized header commits to.
+                    let contract_storage_slots = match &self.storage {
+                        Some(storage) => {
+                            let block_number = *finality_update
+                                .finalized_header()
+                                .execution()
+                                .map_err(|_| {
+                                    anyhow::anyhow!("Finalized header has no execution payload")
+                                })?
+                                .block_number();
+                            Some(storage.fetch(block_number).await?)
+                        }
+                        None => None,
+                    };
+                    (
+                        sync_committee_updates,
+                        Some(finality_update),
+                        contract_storage_slots,
+                    )
+                }
+                UpdatePlan::CatchUp(sync_committee_updates) => {
+                    info!(
+                        "Contract is more than {} periods behind, catching up over several proofs",
+                        self.max_periods_per_proof
+                    );
+                    (sync_committee_updates, None, None)
+                }
+            };
+
+        // Create program inputs
+        let expected_current_slot = client.expected_current_slot();
+        let inputs = ProofInputs {
+            sync_committee_updates,
+            finality_update,
+            expected_current_slot,
+            store: client.store.clone(),
+            genesis_root: client.config.chain.genesis_root,
+            forks: client.config.forks.clone(),
+            contract_storage_slots,
+        };
+        let encoded_proof_inputs = serde_cbor::to_vec(&inputs)?;
+        self.metrics
+            .sync_committee_updates_per_proof
+            .observe(inputs.sync_committee_updates.len() as f64);
+
+        // Compute the store the contract will hold once this update is relayed.
+        let mut next_store = client.store.clone();
+        advance_store(&mut next_store, &inputs)?;
+        let target_head = next_store.finalized_header.beacon().slot;
+        if target_head <= head {
+            info!(
+                "Update from head {} doesn't advance the finalized head, waiting for a newer one",
+                head
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(FetchedUpdate {
+            from_head: head,
+            target_head,
+            encoded_proof_inputs,
+            next_store,
+        }))
+    }
+
+    /// Bootstrap a helios client from the header root the `destination` contract stores for
+    /// `head`.
+    ///
+    /// Rather than trusting the consensus RPC to return the block matching the contract's state,
+    /// the checkpoint is read from `headers[head]`, and the bootstrapped `current_sync_committee`
+    /// must hash to `syncCommittees[period]`. Any mismatch aborts the update.
+    pub async fn bootstrap_from_contract(
+        &self,
+        destination: &Destination,
+        head: u64,
+    ) -> Result<Inner<MainnetConsensusSpec, HttpRpc>> {
+        let (checkpoint, contract_sync_committee, period) =
+            self.contract_checkpoint(destination, head).await?;
+        if checkpoint == B256::ZERO {
+            return Err(anyhow::anyhow!(
+                "Contract has no header stored for head {}",
+                head
+            ));
+        }
+
+        let client = get_client(checkpoint).await?;
+
+        let sync_committee = B256::from_slice(
+            client
+                .store
+                .current_sync_committee
+                .tree_hash_root()
+                .as_ref(),
+        );
+        if sync_committee != contract_sync_committee {
+            return Err(anyhow::anyhow!(
+                "Bootstrap sync committee {:?} does not match contract sync committee {:?} for period {}",
+                sync_committee,
+                contract_sync_committee,
+                period
+            ));
+        }
+
+        Ok(client)
+    }
+}
+
+/// Fetches proof inputs and hands them to the prove stage as new jobs.
+///
+/// Destinations are grouped by the head the next proof for them starts from, and one job is
+/// fetched per group. Once a job has been fetched, its light client store is advanced to the job's
+/// target optimistically, so the next job for the group can be fetched while the previous one is
+/// still being relayed.
+struct FetchStage {
+    ctx: Arc<OperatorContext>,
+    /// Long-lived light clients, by the slot of their store's finalized header.
+    clients: HashMap<u64, Inner<MainnetConsensusSpec, HttpRpc>>,
+    /// Destinations each job in flight will advance, by the job's target.
+    pending: BTreeMap<u64, BTreeSet<String>>,
+    /// Heads whose light client store diverged from a destination contract.
+    diverged: BTreeSet<u64>,
+    jobs: mpsc::Sender<UpdateJob>,
+    events: broadcast::Receiver<JobEvent>,
+    /// Fetch a single round of jobs and stop, rather than running until shutdown.
+    once: bool,
+    /// Wakes the stage as soon as the source chain finalizes a new checkpoint.
+    finality: Option<FinalityEvents>,
+}
+
+impl FetchStage {
+    async fn run(mut self) -> Result<()> {
+        info!("Starting R0VM Helios operator");
+
+        // Finish any job a previous run left behind before starting a new one.
+        if let Err(e) = self.resume_jobs().await {
+            self.ctx.metrics.record_error("resume");
+            error!("Failed to resume unfinished jobs: {}", e);
+        }
+
+        // Dropping the job sender lets the other stages finish the jobs handed over and stop.
+        if self.once {
+            if self.fetch_jobs().await?.is_empty() {
+                info!("Contracts are up to date. Nothing to update.");
+            }
+            return Ok(());
+        }
+
+        // Stop starting new jobs once shutdown has been requested.
+        while !self.ctx.shutdown.is_cancelled() {
+            match self.fetch_jobs().await {
+                Ok(targets) if !targets.is_empty() => {
+                    // Fetch the next jobs as soon as these have been proven.
+                    self.wait_for_proofs(targets).await;
+                    continue;
+                }
+                Ok(_) => {
+                    // Contracts are up to date. Nothing to update.
+                }
+                Err(e) => {
+                    self.ctx.metrics.record_error("fetch");
+                    error!("Header range request failed: {}", e);
+                }
+            }
+
+            self.ctx.set_fetch_status(FetchStatus::Sleeping);
+            info!("Sleeping for {:?}", self.ctx.loop_delay);
+            tokio::select! {
+                _ = tokio::time::sleep(self.ctx.loop_delay) => {}
+                slot = next_finalized_slot(&mut self.finality) => {
+                    info!("Source chain finalized slot {}, waking up", slot);
+                }
+                _ = self.ctx.shutdown.cancelled() => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch the inputs for the next job of every group of destinations, and hand them to the
+    /// prove stage. Returns the targets of the jobs handed over.
+    async fn fetch_jobs(&mut self) -> Result<Vec<u64>> {
+        let ctx = self.ctx.clone();
+        self.drain_events();
+
+        // Group the destinations by the head the next proof for them starts from.
+        let mut groups: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+        let mut contract_heads = HashMap::new();
+        for destination in &ctx.destinations {
+            // Get the current slot from the contract
+            let head = ctx.contract_head(destination).await.with_context(|| {
+                format!(
+                    "Failed to get head. Are you sure the R0VMHelios is deployed to address: {:?}?",
+                    destination.contract_address
+                )
+            })?;
+            if let Err(e) = ctx.observe_contract_head(destination, head).await {
+                warn!("Failed to read contract head timestamp: {}", e);
+            }
+            if let Err(e) = ctx.refresh_status(destination, head).await {
+                warn!("Failed to refresh operator status: {}", e);
+            }
+
+            contract_heads.insert(destination.name.clone(), head);
+            let from_head = self.next_head(&destination.name, head);
+            groups
+                .entry(from_head)
+                .or_default()
+                .push(destination.name.clone());
+        }
+
+        // Drop the light clients no group builds on anymore.
+        self.clients.retain(|head, _| groups.contains_key(head));
+
+        let mut targets = Vec::new();
+        for (head, names) in groups {
+            let destination = ctx.destination(&names[0]).unwrap();
+
+            // Build on the optimistically advanced store while the job in flight hasn't landed
+            // yet. Otherwise reuse the long-lived client, or bootstrap it from the header the
+            // contract has already verified at its head.
+            let mut client = if self.pending.contains_key(&head) {
+                self.clients
+                    .remove(&head)
+                    .context("Missing light client store of the job in flight")?
+            } else {
+                ctx.set_fetch_status(FetchStatus::Syncing);
+                self.sync_client(destination, head)
+                    .await
+                    .context("Failed to bootstrap from contract state")?
+            };
+
+            // Only update the destinations the update policy says are due.
+            let names = match self
+                .due_destinations(&client, head, names, &contract_heads)
+                .await
+            {
+                Ok(names) if !names.is_empty() => names,
+                Ok(_) => {
+                    self.clients.insert(head, client);
+                    continue;
+                }
+                Err(e) => {
+                    self.clients.insert(head, client);
+                    ctx.metrics.record_error("fetch");
+                    error!("Failed to apply the update policy: {}", e);
+                    continue;
+                }
+            };
+            let destination = ctx.destination(&names[0]).unwrap();
+
+            ctx.set_fetch_status(FetchStatus::Fetching);
+            match ctx
+                .request_update(&mut client, destination, names.clone())
+                .await
+            {
+                Ok(Some(job)) => {
+                    let target_head = job.target_head;
+                    client.store = ctx.db.load_next_store(&job)?;
+                    self.clients.insert(target_head, client);
+                    self.pending.entry(target_head).or_default().extend(names);
+
+                    self.jobs
+                        .send(job)
+                        .await
+                        .map_err(|_| anyhow::anyhow!("Prove stage stopped"))?;
+                    targets.push(target_head);
+                }
+                Ok(None) => {
+                    self.clients.insert(head, client);
+                }
+                Err(e) => {
+                    self.clients.insert(head, client);
+                    ctx.metrics.record_error("fetch");
+                    error!(
+                        "Header range request for {} failed: {}",
+                        names.join(", "),
+                        e
+                    );
+                }
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// The destinations of `names` the update policy says are due for an update from `head`, the
+    /// head `client` is synced to.
+    async fn due_destinations(
+        &self,
+        client: &Inner<MainnetConsensusSpec, HttpRpc>,
+        head: u64,
+        names: Vec<String>,
+        contract_heads: &HashMap<String, u64>,
+    ) -> Result<Vec<String>> {
+        let ctx = &self.ctx;
+        let finality_update = client
+            .rpc
+            .get_finality_update()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get finality update: {}", e))?;
+        let source_head = finality_update.finalized_header().beacon().slot;
+
+        let storage_changed = match &ctx.storage {
+            Some(storage) if ctx.policy.watches_storage() && source_head > head => {
+                let block_number = |header: &LightClientHeader<MainnetConsensusSpec>| {
+                    header
+                        .execution()
+                        .map(|execution| *execution.block_number())
+                        .map_err(|_| anyhow::anyhow!("Header has no execution payload"))
+                };
+                storage
+                    .changed(
+                        block_number(&client.store.finalized_header)?,
+                        block_number(finality_update.finalized_header())?,
+                    )
+                    .await?
+            }
+            _ => false,
+        };
+
+        let mut due = Vec::new();
+        for name in names {
+            let destination = ctx.destination(&name).unwrap();
+            match ctx.coordinator.role(destination, head).await? {
+                Role::Leader => {}
+                Role::Takeover { stalled_for } => warn!(
+                    "{} wasn't updated for {:?}, taking over from the leader",
+                    name, stalled_for
+                ),
+                Role::Standby => {
+                    info!("Not updating {} from head {}: standing by", name, head);
+                    continue;
+                }
+            }
+
+            let (head_age, max_slot_age) = ctx
+                .contract_head_age(destination, contract_heads[&name])
+                .await?;
+            let provider = ProviderBuilder::new().on_http(destination.rpc_url.clone());
+            let inputs = PolicyInputs {
+                from_head: head,
+                source_head,
+                head_age: Duration::from_secs(head_age.max(0) as u64),
+                max_slot_age: Duration::from_secs(max_slot_age),
+                gas_price: provider.get_gas_price().await?,
+                storage_changed,
+            };
+
+            match ctx.policy.decide(&inputs) {
+                Decision::Update(reason) => {
+                    info!("Updating {} from head {}: {}", name, head, reason);
+                    due.push(name);
+                }
+                Decision::Defer(reason) => {
+                    info!("Not updating {} from head {}: {}", name, head, reason)
+                }
+            }
+        }
+        Ok(due)
+    }
+
+    /// The head the next proof for `destination` starts from: the target of the latest job in
+    /// flight for it, or its contract head.
+    fn next_head(&mut self, destination: &str, contract_head: u64) -> u64 {
+        // Jobs the contract has already reached are no longer in flight.
+        for (target_head, destinations) in self.pending.iter_mut() {
+            if *target_head <= contract_head {
+                destinations.remove(destination);
+            }
+        }
+        self.pending
+            .retain(|_, destinations| !destinations.is_empty());
+
+        self.pending
+            .iter()
+            .filter(|(_, destinations)| destinations.contains(destination))
+            .map(|(target_head, _)| *target_head)
+            .max()
+            .unwrap_or(contract_head)
+    }
+
+    /// Return a helios client whose store matches the `destination` contract's state at `head`.
+    ///
+    /// The long-lived client (or the store persisted from a previous run) is reused as long as its
+    /// finalized header and sync committee match the contract's `headers` and `syncCommittees`.
+    /// Otherwise the client is re-bootstrapped from the contract.
+    async fn sync_client(
+        &mut self,
+        destination: &Destination,
+        head: u64,
+    ) -> Result<Inner<MainnetConsensusSpec, HttpRpc>> {
+        let client = match self.clients.remove(&head) {
+            // A store that diverged from a contract is never reused.
+            _ if self.diverged.remove(&head) => None,
+            Some(client) => Some(client),
+            None => self
+                .ctx
+                .db
+                .load_light_client_store()?
+                .map(get_client_from_store)
+                .transpose()?,
+        };
+
+        if let Some(client) = client {
+            let (header, sync_committee, _) =
+                self.ctx.contract_checkpoint(destination, head).await?;
+            let store_header = B256::from_slice(
+                client
+                    .store
+                    .finalized_header
+                    .beacon()
+                    .tree_hash_root()
+                    .as_ref(),
+            );
+            let store_sync_committee = B256::from_slice(
+                client
+                    .store
+                    .current_sync_committee
+                    .tree_hash_root()
+                    .as_ref(),
+            );
+
+            if store_header == header && store_sync_committee == sync_committee {
+                return Ok(client);
+            }
+            info!("Light client store diverges from contract, re-bootstrapping");
+        }
+
+        let client = self.ctx.bootstrap_from_contract(destination, head).await?;
+        self.ctx.db.save_light_client_store(&client.store)?;
+        Ok(client)
+    }
+
+    /// Hand jobs left unfinished by a previous run of the operator back to the pipeline.
+    async fn resume_jobs(&mut self) -> Result<()> {
+        for mut job in self.ctx.db.unfinished_jobs()? {
+            if self.ctx.shutdown.is_cancelled() {
+                break;
+            }
+
+            let mut destinations = BTreeSet::new();
+            for name in self.ctx.db.unfinished_destinations(&job)? {
+                let Some(destination) = self.ctx.destination(&name) else {
+                    self.ctx.db.mark_relay_failed(
+                        &job,
+                        &name,
+                        "Destination is no longer configured",
+                    )?;
+                    continue;
+                };
+
+                // Any of the transactions sent for the update may have been mined after we stopped
+                // waiting for it.
+                let relay = self.ctx.db.relay(&job, &name)?;
+                if relay.state == RelayState::Submitted {
+                    if let Some(receipt) =
+                        self.ctx.find_receipt(destination, &relay.tx_hashes).await?
+                    {
+                        let tx_hash = receipt.transaction_hash;
+                        if receipt.status() {
+                            info!(
+                                "Job {} was confirmed on {} in tx {:?}",
+                                job.id, name, tx_hash
+                            );
+                            let confirmed = self.ctx.db.mark_confirmed(&job, &name, tx_hash)?;
+                            self.ctx.update_destination_status(&name, |status| {
+                                status.last_successful_relay = Some(confirmed.into());
+                            });
+                        } else {
+                            self.ctx
+                                .db
+                                .mark_relay_failed(&job, &name, "Transaction reverted!")?;
+                        }
+                        continue;
+                    }
+                }
+
+                // The proof is only useful if the contract is still at the head it was built
+                // from, or will be once the job resumed before it lands.
+                let head = self.ctx.contract_head(destination).await?;
+                if head != job.from_head && self.next_head(&name, head) != job.from_head {
+                    info!(
+                        "Dropping job {} on {}, contract head is now {}",
+                        job.id, name, head
+                    );
+                    self.ctx.db.mark_relay_failed(
+                        &job,
+                        &name,
+                        format!("Contract head moved to {}", head),
+                    )?;
+                    continue;
+                }
+                destinations.insert(name);
+            }
+
+            if self.ctx.db.finish_job(&mut job)? {
+                if job.stage == JobStage::Confirmed {
+                    self.ctx.persist_store(&job)?;
+                    self.ctx.archive_job(&job);
+                }
+                continue;
+            }
+
+            info!("Resuming job {} from stage {:?}", job.id, job.stage);
+            let target_head = job.target_head;
+            let next_store = self.ctx.db.load_next_store(&job)?;
+            if self.jobs.send(job).await.is_err() {
+                break;
+            }
+            self.clients
+                .insert(target_head, get_client_from_store(next_store)?);
+            self.pending
+                .entry(target_head)
+                .or_default()
+                .extend(destinations);
+            self.wait_for_proofs(vec![target_head]).await;
+        }
+
+        Ok(())
+    }
+
+    /// Wait until the prove stage is done with the jobs targeting `targets`.
+    async fn wait_for_proofs(&mut self, targets: Vec<u64>) {
+        self.ctx.set_fetch_status(FetchStatus::WaitingForProof);
+
+        let mut waiting: BTreeSet<u64> = targets.into_iter().collect();
+        while !waiting.is_empty() {
+            tokio::select! {
+                event = self.events.recv() => match event {
+                    Ok(event) => {
+                        if let JobEvent::Proved { target_head } = &event {
+                            waiting.remove(target_head);
+                        }
+                        self.handle_event(event);
+                        // Stop waiting for jobs that won't land on any destination.
+                        waiting.retain(|target_head| self.pending.contains_key(target_head));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = self.ctx.shutdown.cancelled() => return,
+            }
+        }
+    }
+
+    /// Handle the events broadcast while the fetch stage was busy.
+    fn drain_events(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.handle_event(event),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Stop building on a job for a destination it won't land on.
+    fn handle_event(&mut self, event: JobEvent) {
+        match event {
+            JobEvent::Failed {
+                target_head,
+                destination,
+            }
+            | JobEvent::Skipped {
+                target_head,
+                destination,
+            } => {
+                if let Some(destinations) = self.pending.get_mut(&target_head) {
+                    destinations.remove(&destination);
+                    if destinations.is_empty() {
+                        self.pending.remove(&target_head);
+                    }
+                }
+            }
+            JobEvent::StoreDiverged { head } => {
+                self.clients.remove(&head);
+                self.diverged.insert(head);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Proves jobs once the destination contracts have reached the head they were built from.
+struct ProveStage {
+    ctx: Arc<OperatorContext>,
+    jobs: mpsc::Receiver<UpdateJob>,
+    proved: mpsc::Sender<(UpdateJob, Receipt)>,
+    events: JobEvents,
+    event_rx: broadcast::Receiver<JobEvent>,
+}
+
+impl ProveStage {
+    async fn run(mut self) {
+        loop {
+            let job = tokio::select! {
+                job = self.jobs.recv() => match job {
+                    Some(job) => job,
+                    None => break,
+                },
+                _ = self.ctx.shutdown.cancelled() => break,
+            };
+
+            let target_head = job.target_head;
+            let destinations = job.destinations.clone();
+            self.ctx.status.write().unwrap().proving_job = Some(job.id.clone());
+            let result = self.prove_job(job).await;
+            self.ctx.status.write().unwrap().proving_job = None;
+
+            match result {
+                Ok(Some(proved)) => {
+                    self.events.send(JobEvent::Proved { target_head });
+                    if self.proved.send(proved).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Proving failed: {}", e);
+                    self.emit_failed(target_head, &destinations);
+                }
+            }
+        }
+    }
+
+    /// Prove `job` and persist its receipt. Returns `None` if the job was dropped instead.
+    async fn prove_job(&mut self, mut job: UpdateJob) -> Result<Option<(UpdateJob, Receipt)>> {
+        if !matches!(job.stage, JobStage::Fetched | JobStage::Proving) {
+            let receipt = self
+                .ctx
+                .db
+                .load_receipt(&job)?
+                .with_context(|| format!("job {} has no receipt", job.id))?;
+            return Ok(Some((job, receipt)));
+        }
+
+        // The proof commits to the contracts' head, so only start proving once the job this one
+        // builds on has been confirmed.
+        let Some(ready) = self.wait_for_base(&job).await? else {
+            return Ok(None);
+        };
+
+        // Re-check the contracts' header and sync committee against the store the job starts
+        // from, since it was built optimistically while the previous job was in flight.
+        let inputs = self.ctx.db.load_inputs(&job)?;
+        let proof_inputs: ProofInputs = serde_cbor::from_slice(&inputs)?;
+        let mut destinations = Vec::new();
+        for name in ready {
+            let destination = self.ctx.destination(&name).unwrap();
+            if self
+                .ctx
+                .matches_contract(destination, &proof_inputs, job.from_head)
+                .await?
+            {
+                destinations.push(name);
+            } else {
+                self.drop_destination(
+                    &job,
+                    &name,
+                    "Contract state diverges from the job's starting store",
+                )?;
+            }
+        }
+        if destinations.is_empty() {
+            self.ctx.db.finish_job(&mut job)?;
+            return Ok(None);
+        }
+
+        self.ctx.db.set_stage(&mut job, JobStage::Proving)?;
+        let check_lag = proof_inputs.finality_update.is_some();
+        let cancel = CancellationToken::new();
+        let proving = self.ctx.prove(inputs, cancel.clone());
+        tokio::pin!(proving);
+        let result = tokio::select! {
+            result = &mut proving => result,
+            _ = self.ctx.shutdown.cancelled() => {
+                // The job stays in the proving stage with its inputs persisted, so it is proven
+                // again on restart.
+                info!("Shutdown requested, cancelling proof for job {}", job.id);
+                cancel.cancel();
+                let _ = proving.await;
+                return Ok(None);
+            }
+            reason = self.ctx.wait_until_obsolete(&job, &destinations, check_lag) => {
+                info!("Cancelling proof for job {}: {}", job.id, reason);
+                cancel.cancel();
+                let _ = proving.await;
+                self.ctx.db.mark_failed(&mut job, format!("Obsolete: {}", reason))?;
+                self.emit_failed(job.target_head, &destinations);
+                return Ok(None);
+            }
+        };
+        let receipt = match result {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                self.ctx.metrics.record_error("prove");
+                self.ctx.db.mark_failed(&mut job, &e)?;
+                return Err(e);
+            }
+        };
+        self.ctx.db.save_receipt(&mut job, &receipt)?;
+
+        Ok(Some((job, receipt)))
+    }
+
+    /// Wait until every destination of `job` is at the head the job was built from, dropping the
+    /// destinations it can no longer land on. Returns the destinations that are ready, or `None`
+    /// if shutdown was requested.
+    async fn wait_for_base(&mut self, job: &UpdateJob) -> Result<Option<Vec<String>>> {
+        const POLL_INTERVAL_SECS: u64 = 15;
+
+        let mut waiting = self.ctx.db.unfinished_destinations(job)?;
+        let mut ready = Vec::new();
+        loop {
+            let mut still_waiting = Vec::new();
+            for name in waiting {
+                let Some(destination) = self.ctx.destination(&name) else {
+                    self.drop_destination(job, &name, "Destination is no longer configured")?;
+                    continue;
+                };
+
+                let head = self.ctx.contract_head(destination).await?;
+                if head == job.from_head {
+                    ready.push(name);
+                } else if head > job.from_head {
+                    self.drop_destination(job, &name, format!("Contract head moved to {}", head))?;
+                } else {
+                    still_waiting.push(name);
+                }
+            }
+            waiting = still_waiting;
+            if waiting.is_empty() {
+                return Ok(Some(ready));
+            }
+
+            tokio::select! {
+                event = self.event_rx.recv() => match event {
+                    Ok(JobEvent::Confirmed { target_head, destination })
+                        if target_head == job.from_head =>
+                    {
+                        if let Some(index) = waiting.iter().position(|name| *name == destination) {
+                            ready.push(waiting.remove(index));
+                        }
+                    }
+                    Ok(
+                        JobEvent::Failed { target_head, destination }
+                        | JobEvent::Skipped { target_head, destination },
+                    ) if target_head == job.from_head =>
+                    {
+                        if let Some(index) = waiting.iter().position(|name| *name == destination) {
+                            waiting.remove(index);
+                            self.drop_destination(job, &destination, "The job it builds on failed")?;
+                        }
+                    }
+                    _ => {}
+                },
+                _ = tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)) => {}
+                _ = self.ctx.shutdown.cancelled() => return Ok(None),
+            }
+        }
+    }
+
+    /// Give up relaying `job` to `destination`.
+    fn drop_destination(
+        &self,
+        job: &UpdateJob,
+        destination: &str,
+        reason: impl ToString,
+    ) -> Result<()> {
+        let reason = reason.to_string();
+        info!("Dropping job {} on {}: {}", job.id, destination, reason);
+        self.ctx.db.mark_relay_failed(job, destination, reason)?;
+        self.emit_failed(job.target_head, &[destination.to_string()]);
+        Ok(())
+    }
+
+    fn emit_failed(&self, target_head: u64, destinations: &[String]) {
+        for destination in destinations {
+            self.events.send(JobEvent::Failed {
+                target_head,
+                destination: destination.clone(),
+            });
+        }
+    }
+}
+
+/// The next finalized slot announced by `finality`. Never returns without an event source.
+async fn next_finalized_slot(finality: &mut Option<FinalityEvents>) -> u64 {
+    match finality {
+        Some(finality) => finality.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Relays proven jobs to their destination contracts.
+struct RelayStage {
+    ctx: Arc<OperatorContext>,
+    proved: mpsc::Receiver<(UpdateJob, Receipt)>,
+    events: JobEvents,
+}
+
+impl RelayStage {
+    async fn run(mut self) {
+        while let Some((mut job, receipt)) = self.proved.recv().await {
+            // Don't send new transactions once shutdown has been requested. The job stays proven
+            // and is relayed on restart.
+            if self.ctx.shutdown.is_cancelled() {
+                break;
+            }
+
+            // Relay to the destinations concurrently, except for destinations sharing a relayer
+            // account on the same chain: their transactions draw from the same nonces, so they
+            // are relayed one after the other.
+            let mut accounts: BTreeMap<(String, Address), Vec<Destination>> = BTreeMap::new();
+            for name in self
+                .ctx
+                .db
+                .unfinished_destinations(&job)
+                .unwrap_or_default()
+            {
+                if let Some(destination) = self.ctx.destination(&name) {
+                    accounts
+                        .entry((destination.rpc_url.to_string(), destination.relayer_address))
+                        .or_default()
+                        .push(destination.clone());
+                }
+            }
+
+            let mut relays = JoinSet::new();
+            for destinations in accounts.into_values() {
+                let ctx = self.ctx.clone();
+                let job = job.clone();
+                let receipt = receipt.clone();
+                let events = self.events.clone();
+                relays.spawn(async move {
+                    for destination in destinations {
+                        relay_to(
+                            ctx.clone(),
+                            destination,
+                            job.clone(),
+                            receipt.clone(),
+                            events.clone(),
+                        )
+                        .await;
+                    }
+                });
+            }
+            while relays.join_next().await.is_some() {}
+
+            let result = self.ctx.db.finish_job(&mut job).and_then(|finished| {
+                if finished && job.stage == JobStage::Confirmed {
+                    self.ctx.persist_store(&job)?;
+                    self.ctx.archive_job(&job);
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                error!("Failed to update job {}: {}", job.id, e);
+            }
+        }
+    }
+}
+
+/// Relay `job` to `destination`, broadcasting the outcome.
+///
+/// Once the transaction may have been sent, it is given a bounded amount of time to confirm on
+/// shutdown rather than being abandoned. If it doesn't confirm in time, it stays submitted and is
+/// checked again on restart.
+async fn relay_to(
+    ctx: Arc<OperatorContext>,
+    destination: Destination,
+    job: UpdateJob,
+    receipt: Receipt,
+    events: JobEvents,
+) {
+    let name = destination.name.clone();
+    ctx.update_destination_status(&name, |status| {
+        status.relaying_job = Some(job.id.clone());
+    });
+    info!(
+        "Attempting to update {} to new head block: {:?}",
+        name, job.target_head
+    );
+
+    let shutdown_timeout = ctx.shutdown_timeout;
+    let relay = ctx.relay_update(&destination, &job, receipt);
+    tokio::pin!(relay);
+    let result = tokio::select! {
+        result = &mut relay => result,
+        _ = ctx.shutdown.cancelled() => {
+            info!(
+                "Shutdown requested, waiting up to {:?} for the update transaction to {} to confirm",
+                shutdown_timeout, name
+            );
+            tokio::time::timeout(shutdown_timeout, &mut relay)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow::anyhow!(
+                        "Timed out waiting for the update transaction during shutdown"
+                    ))
+                })
+        }
+    };
+    ctx.update_destination_status(&name, |status| status.relaying_job = None);
+
+    match result {
+        Ok(confirmed) => {
+            ctx.update_destination_status(&name, |status| {
+                status.last_successful_relay = Some(confirmed.into());
+            });
+            events.send(JobEvent::Confirmed {
+                target_head: job.target_head,
+                destination: name,
+            });
+        }
+        Err(e) if ctx.shutdown.is_cancelled() => {
+            // The relay record is left as is, and picked up again on restart.
+            error!("Update of {} interrupted by shutdown: {}", name, e);
+        }
+        Err(e) => {
+            let recovery = match e.downcast_ref::<UpdateRevert>() {
+                Some(revert) => Some(revert.recovery()),
+                None => e.downcast_ref::<Superseded>().map(|_| RecoveryAction::Skip),
+            };
+            if recovery == Some(RecoveryAction::Skip) {
+                info!("Skipping update of {}: {}", name, e);
+            } else {
+                ctx.metrics.record_error("relay");
+                error!("Update of {} failed: {}", name, e);
+            }
+            if let Err(e) = ctx.db.mark_relay_failed(&job, &name, &e) {
+                error!("Failed to update job {}: {}", job.id, e);
+            }
+            if recovery == Some(RecoveryAction::Rebootstrap) {
+                events.send(JobEvent::StoreDiverged {
+                    head: job.from_head,
+                });
+            }
+            if recovery == Some(RecoveryAction::Skip) {
+                events.send(JobEvent::Skipped {
+                    target_head: job.target_head,
+                    destination: name,
+                });
+            } else {
+                events.send(JobEvent::Failed {
+                    target_head: job.target_head,
+                    destination: name,
+                });
+            }
+        }
+    }
+}
+
+/// Run the fetch, prove and relay stages until shutdown or, if `once` is set, until a single round
+/// of updates has been relayed. `finality` wakes the fetch stage up as soon as the source chain
+/// finalizes a new checkpoint.
+///
+/// The stages are connected by channels: the next job is fetched while the previous one is
+/// relayed, and proven as soon as it has been confirmed. Cancels `ctx.shutdown` once they have
+/// stopped.
+pub async fn run(
+    ctx: Arc<OperatorContext>,
+    once: bool,
+    finality: Option<FinalityEvents>,
+) -> Result<()> {
+    let (job_tx, job_rx) = mpsc::channel(1);
+    let (proved_tx, proved_rx) = mpsc::channel(1);
+    let events = JobEvents::new();
+
+    // Hold the coordination lock, if configured, for as long as the operator runs.
+    let lock = {
+        let ctx = ctx.clone();
+        let shutdown = ctx.shutdown.clone();
+        tokio::spawn(async move { ctx.coordinator.hold_lock(shutdown).await })
+    };
+
+    let fetch = FetchStage {
+        ctx: ctx.clone(),
+        clients: HashMap::new(),
+        pending: BTreeMap::new(),
+        diverged: BTreeSet::new(),
+        jobs: job_tx,
+        events: events.subscribe(),
+        once,
+        finality,
+    };
+    let prove = ProveStage {
+        ctx: ctx.clone(),
+        jobs: job_rx,
+        proved: proved_tx,
+        events: events.clone(),
+        event_rx: events.subscribe(),
+    };
+    let relay = RelayStage {
+        ctx: ctx.clone(),
+        proved: proved_rx,
+        events: events.clone(),
+    };
+
+    let (fetch, prove, relay) = tokio::join!(
+        tokio::spawn(fetch.run()),
+        tokio::spawn(prove.run()),
+        tokio::spawn(relay.run()),
+    );
+    let fetched = fetch?;
+    prove?;
+    relay?;
+
+    // Make sure everything waiting on shutdown stops even if the stages exited on their own.
+    ctx.shutdown.cancel();
+    lock.await?;
+
+    // A single run fails if any update did, so that schedulers can retry it.
+    if once {
+        fetched?;
+        let failures = events.failures();
+        if failures > 0 {
+            anyhow::bail!("{} updates failed", failures);
+        }
+    }
+    Ok(())
+}
+
+/// Relay the proven `job` to its unfinished destinations, like the relay stage does. Fails unless
+/// every destination confirmed the update.
+pub async fn relay(ctx: Arc<OperatorContext>, job: UpdateJob, receipt: Receipt) -> Result<()> {
+    let (proved_tx, proved_rx) = mpsc::channel(1);
+    proved_tx.send((job.clone(), receipt)).await?;
+    drop(proved_tx);
+    RelayStage {
+        ctx: ctx.clone(),
+        proved: proved_rx,
+        events: JobEvents::new(),
+    }
+    .run()
+    .await;
+
+    let mut unconfirmed = Vec::new();
+    for name in &job.destinations {
+        let state = ctx.db.relay(&job, name)?.state;
+        if state != RelayState::Confirmed {
+            unconfirmed.push(format!("{} ({:?})", name, state));
+        }
+    }
+    if !unconfirmed.is_empty() {
+        anyhow::bail!(
+            "Job {} wasn't confirmed on {}",
+            job.id,
+            unconfirmed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Directory the operator keeps its state in.
+pub fn state_dir() -> PathBuf {
+    env::var("STATE_DIR")
+        .unwrap_or("operator-state".to_string())
+        .into()
+}
+
+/// Kind of receipt to prove, from `RECEIPT_KIND`.
+pub fn receipt_kind_from_env() -> Result<ReceiptKind> {
+    match env::var("RECEIPT_KIND") {
+        Ok(kind) => kind.parse(),
+        Err(_) => Ok(ReceiptKind::default()),
+    }
+}
+
+/// Directory proofs are archived in, `<STATE_DIR>/archive` by default.
+pub fn archive_dir() -> PathBuf {
+    env::var("ARCHIVE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| state_dir().join("archive"))
+}
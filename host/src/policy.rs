@@ -0,0 +1,351 @@
+use crate::destination::parse_optional;
+use anyhow::Result;
+use helios_consensus_core::{calc_sync_period, consensus_spec::MainnetConsensusSpec};
+use std::{env, fmt, str::FromStr, time::Duration};
+
+/// A condition under which the operator relays an update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateTrigger {
+    /// Whenever the source chain has finalized a new head.
+    Always,
+    /// Once the source chain has finalized `UPDATE_INTERVAL_SLOTS` slots past the head.
+    Interval,
+    /// Once the source chain has entered a new sync committee period.
+    Rotation,
+    /// Once one of the proven `STORAGE_SLOTS` has changed.
+    Storage,
+}
+
+impl FromStr for UpdateTrigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "always" => Ok(Self::Always),
+            "interval" => Ok(Self::Interval),
+            "rotation" => Ok(Self::Rotation),
+            "storage" => Ok(Self::Storage),
+            _ => Err(anyhow::anyhow!(
+                "Unknown update trigger {}, expected always, interval, rotation or storage",
+                s
+            )),
+        }
+    }
+}
+
+/// When the operator relays updates.
+///
+/// An update is relayed once any of the triggers fires, unless the destination's gas price is
+/// above `max_gas_price`. Once the contract head gets within `deadline_margin` of `MAX_SLOT_AGE`,
+/// after which the contract can no longer be updated from it, an update is relayed regardless.
+#[derive(Debug, Clone)]
+pub struct UpdatePolicy {
+    pub triggers: Vec<UpdateTrigger>,
+    pub interval_slots: u64,
+    /// Defer updates while the destination's gas price, in wei, is above this.
+    pub max_gas_price: Option<u128>,
+    pub deadline_margin: Duration,
+}
+
+impl Default for UpdatePolicy {
+    fn default() -> Self {
+        Self {
+            triggers: vec![UpdateTrigger::Always],
+            interval_slots: 0,
+            max_gas_price: None,
+            deadline_margin: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+}
+
+/// What the policy decides on, for one destination.
+#[derive(Debug, Clone)]
+pub struct PolicyInputs {
+    /// Head the next update starts from.
+    pub from_head: u64,
+    /// Finalized head of the source chain.
+    pub source_head: u64,
+    /// Time since the contract head, and the contract's `MAX_SLOT_AGE`.
+    pub head_age: Duration,
+    pub max_slot_age: Duration,
+    /// Current gas price of the destination chain, in wei.
+    pub gas_price: u128,
+    /// Whether a proven storage slot changed since `from_head`. Only read with the storage
+    /// trigger.
+    pub storage_changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Update(UpdateReason),
+    Defer(DeferReason),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateReason {
+    NewHead,
+    Interval {
+        slots: u64,
+    },
+    Rotation {
+        period: u64,
+    },
+    StorageChanged,
+    /// The contract head expires within `remaining`.
+    Deadline {
+        remaining: Duration,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeferReason {
+    UpToDate,
+    NotDue,
+    GasPrice { gas_price: u128, cap: u128 },
+}
+
+impl UpdatePolicy {
+    /// Read the update policy from `UPDATE_TRIGGERS`, `UPDATE_INTERVAL_SLOTS`,
+    /// `UPDATE_MAX_GAS_PRICE` and `UPDATE_DEADLINE_MARGIN_SECS`.
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        let triggers = match env::var("UPDATE_TRIGGERS") {
+            Ok(triggers) => triggers
+                .split(',')
+                .map(str::trim)
+                .filter(|trigger| !trigger.is_empty())
+                .map(str::parse)
+                .collect::<Result<_>>()?,
+            Err(_) => default.triggers,
+        };
+
+        let policy = Self {
+            triggers,
+            interval_slots: parse_optional("UPDATE_INTERVAL_SLOTS")?.unwrap_or_default(),
+            max_gas_price: parse_optional("UPDATE_MAX_GAS_PRICE")?,
+            deadline_margin: parse_optional("UPDATE_DEADLINE_MARGIN_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(default.deadline_margin),
+        };
+
+        if policy.triggers.is_empty() {
+            anyhow::bail!("UPDATE_TRIGGERS must name at least one trigger");
+        }
+        if policy.triggers.contains(&UpdateTrigger::Interval) && policy.interval_slots == 0 {
+            anyhow::bail!("The interval trigger requires UPDATE_INTERVAL_SLOTS");
+        }
+        Ok(policy)
+    }
+
+    /// Whether the policy needs to know if the proven storage slots changed.
+    pub fn watches_storage(&self) -> bool {
+        self.triggers.contains(&UpdateTrigger::Storage)
+    }
+
+    pub fn decide(&self, inputs: &PolicyInputs) -> Decision {
+        if inputs.source_head <= inputs.from_head {
+            return Decision::Defer(DeferReason::UpToDate);
+        }
+
+        let remaining = inputs.max_slot_age.saturating_sub(inputs.head_age);
+        if remaining <= self.deadline_margin {
+            return Decision::Update(UpdateReason::Deadline { remaining });
+        }
+
+        let Some(reason) = self.triggered(inputs) else {
+            return Decision::Defer(DeferReason::NotDue);
+        };
+        match self.max_gas_price {
+            Some(cap) if inputs.gas_price > cap => Decision::Defer(DeferReason::GasPrice {
+                gas_price: inputs.gas_price,
+                cap,
+            }),
+            _ => Decision::Update(reason),
+        }
+    }
+
+    /// The first trigger that fires, if any.
+    fn triggered(&self, inputs: &PolicyInputs) -> Option<UpdateReason> {
+        let slots = inputs.source_head - inputs.from_head;
+        let period = calc_sync_period::<MainnetConsensusSpec>(inputs.source_head);
+
+        self.triggers.iter().find_map(|trigger| match trigger {
+            UpdateTrigger::Always => Some(UpdateReason::NewHead),
+            UpdateTrigger::Interval if slots >= self.interval_slots => {
+                Some(UpdateReason::Interval { slots })
+            }
+            UpdateTrigger::Rotation
+                if period > calc_sync_period::<MainnetConsensusSpec>(inputs.from_head) =>
+            {
+                Some(UpdateReason::Rotation { period })
+            }
+            UpdateTrigger::Storage if inputs.storage_changed => Some(UpdateReason::StorageChanged),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for UpdateReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateReason::NewHead => write!(f, "the source chain finalized a new head"),
+            UpdateReason::Interval { slots } => {
+                write!(
+                    f,
+                    "the source chain finalized {} slots past the head",
+                    slots
+                )
+            }
+            UpdateReason::Rotation { period } => {
+                write!(
+                    f,
+                    "the source chain entered sync committee period {}",
+                    period
+                )
+            }
+            UpdateReason::StorageChanged => write!(f, "a watched storage slot changed"),
+            UpdateReason::Deadline { remaining } => write!(
+                f,
+                "the contract head reaches MAX_SLOT_AGE in {:?}",
+                remaining
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DeferReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeferReason::UpToDate => write!(f, "the contract is up to date"),
+            DeferReason::NotDue => write!(f, "no update trigger fired"),
+            DeferReason::GasPrice { gas_price, cap } => write!(
+                f,
+                "gas price {} is above UPDATE_MAX_GAS_PRICE {}",
+                gas_price, cap
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+    const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+    /// Slots per sync committee period.
+    const PERIOD: u64 = 8192;
+
+    fn inputs(from_head: u64, source_head: u64) -> PolicyInputs {
+        PolicyInputs {
+            from_head,
+            source_head,
+            head_age: HOUR,
+            max_slot_age: WEEK,
+            gas_price: 10,
+            storage_changed: false,
+        }
+    }
+
+    fn policy(triggers: &[UpdateTrigger]) -> UpdatePolicy {
+        UpdatePolicy {
+            triggers: triggers.to_vec(),
+            interval_slots: 100,
+            ..UpdatePolicy::default()
+        }
+    }
+
+    #[test]
+    fn defers_when_up_to_date() {
+        let policy = policy(&[UpdateTrigger::Always]);
+        let mut inputs = inputs(PERIOD, PERIOD);
+        assert_eq!(
+            policy.decide(&inputs),
+            Decision::Defer(DeferReason::UpToDate)
+        );
+
+        // Even past the deadline, as there is nothing to update to.
+        inputs.head_age = WEEK;
+        assert_eq!(
+            policy.decide(&inputs),
+            Decision::Defer(DeferReason::UpToDate)
+        );
+    }
+
+    #[test]
+    fn updates_once_a_trigger_fires() {
+        let policy = policy(&[
+            UpdateTrigger::Interval,
+            UpdateTrigger::Rotation,
+            UpdateTrigger::Storage,
+        ]);
+        assert_eq!(
+            policy.decide(&inputs(PERIOD, PERIOD + 99)),
+            Decision::Defer(DeferReason::NotDue)
+        );
+        assert_eq!(
+            policy.decide(&inputs(PERIOD, PERIOD + 100)),
+            Decision::Update(UpdateReason::Interval { slots: 100 })
+        );
+        assert_eq!(
+            policy.decide(&inputs(2 * PERIOD - 1, 2 * PERIOD)),
+            Decision::Update(UpdateReason::Rotation { period: 2 })
+        );
+
+        let mut changed = inputs(PERIOD, PERIOD + 1);
+        changed.storage_changed = true;
+        assert_eq!(
+            policy.decide(&changed),
+            Decision::Update(UpdateReason::StorageChanged)
+        );
+    }
+
+    #[test]
+    fn defers_above_the_gas_cap() {
+        let policy = UpdatePolicy {
+            max_gas_price: Some(10),
+            ..policy(&[UpdateTrigger::Always])
+        };
+        assert_eq!(
+            policy.decide(&inputs(PERIOD, PERIOD + 1)),
+            Decision::Update(UpdateReason::NewHead)
+        );
+
+        let mut expensive = inputs(PERIOD, PERIOD + 1);
+        expensive.gas_price = 11;
+        assert_eq!(
+            policy.decide(&expensive),
+            Decision::Defer(DeferReason::GasPrice {
+                gas_price: 11,
+                cap: 10
+            })
+        );
+    }
+
+    #[test]
+    fn deadline_overrides_gas_cap_and_triggers() {
+        let policy = UpdatePolicy {
+            max_gas_price: Some(10),
+            ..policy(&[UpdateTrigger::Interval])
+        };
+        let mut inputs = inputs(PERIOD, PERIOD + 1);
+        inputs.gas_price = 1_000;
+        assert_eq!(policy.decide(&inputs), Decision::Defer(DeferReason::NotDue));
+
+        inputs.head_age = WEEK - policy.deadline_margin;
+        assert_eq!(
+            policy.decide(&inputs),
+            Decision::Update(UpdateReason::Deadline {
+                remaining: policy.deadline_margin
+            })
+        );
+
+        inputs.head_age = 2 * WEEK;
+        assert_eq!(
+            policy.decide(&inputs),
+            Decision::Update(UpdateReason::Deadline {
+                remaining: Duration::ZERO
+            })
+        );
+    }
+}
@@ -0,0 +1,949 @@
+//! Proving backends for the R0VM Helios guest.
+//!
+//! Besides proving on this machine, the operator can hand proofs to a separate proving process,
+//! over a Unix socket or through a shared directory. [`serve_socket`] and [`serve_directory`]
+//! implement the other end of both protocols.
+//!
+//! Socket protocol, one proof per connection:
+//!
+//! - request: `u32` LE length and JSON [`ProveRequest`], then `u64` LE length and the encoded
+//!   `ProofInputs`.
+//! - response: `u32` LE length and JSON [`ProveResponse`], then `u64` LE length and the
+//!   CBOR-encoded `Receipt`, empty on error.
+//!
+//! Directory protocol, one subdirectory per proof, named after the hash of its inputs:
+//!
+//! ```text
+//! <dir>/<id>/
+//!   inputs.cbor     encoded ProofInputs, written by the operator
+//!   request.json    ProveRequest, written last by the operator
+//!   receipt.cbor    CBOR-encoded Receipt, written by the prover
+//!   response.json   ProveResponse, written last by the prover
+//!   cancel          written by the operator to cancel the request
+//! ```
+//!
+//! Proofs are cancelled by closing the connection, or by writing the `cancel` file. The prover
+//! then stops proving and, in the directory protocol, removes the request's subdirectory.
+
+use crate::store::write_atomic;
+use anyhow::{Context, Result};
+use log::{error, info};
+use r0vm_helios_methods::{R0VM_HELIOS_GUEST_ELF, R0VM_HELIOS_GUEST_ID};
+use risc0_zkvm::{
+    default_executor, default_prover, sha::Impl, sha::Sha256, ExecutorEnv, FakeReceipt,
+    InnerReceipt, ProverOpts, Receipt, ReceiptClaim,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt, fs,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+/// How often blocking provers check whether their proof has been cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Set in the environment of the child process a [`LocalProver`] proves in, to the kind of
+/// receipt to prove.
+const PROVER_CHILD_VAR: &str = "R0VM_HELIOS_PROVER_CHILD";
+
+/// Set in the environment of a [`LocalProver`]'s child process, to the file it writes the proof
+/// to.
+const PROVER_CHILD_OUT_VAR: &str = "R0VM_HELIOS_PROVER_CHILD_OUT";
+
+/// Kind of receipt to generate.
+///
+/// Only Groth16 receipts can be verified by the `R0VMHelios` contract. The other kinds are
+/// cheaper to generate, and useful to test the guest or to archive proofs off-chain.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    Composite,
+    Succinct,
+    #[default]
+    Groth16,
+}
+
+impl ReceiptKind {
+    pub fn prover_opts(&self) -> ProverOpts {
+        match self {
+            ReceiptKind::Composite => ProverOpts::composite(),
+            ReceiptKind::Succinct => ProverOpts::succinct(),
+            ReceiptKind::Groth16 => ProverOpts::groth16(),
+        }
+    }
+}
+
+impl FromStr for ReceiptKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "composite" => Ok(ReceiptKind::Composite),
+            "succinct" => Ok(ReceiptKind::Succinct),
+            "groth16" => Ok(ReceiptKind::Groth16),
+            _ => Err(anyhow::anyhow!("Unknown receipt kind {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ReceiptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptKind::Composite => write!(f, "composite"),
+            ReceiptKind::Succinct => write!(f, "succinct"),
+            ReceiptKind::Groth16 => write!(f, "groth16"),
+        }
+    }
+}
+
+/// Statistics of a proven session.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ProofStats {
+    pub total_cycles: u64,
+    pub user_cycles: u64,
+    pub segments: usize,
+}
+
+/// A receipt for the guest, along with statistics if the backend reports them.
+pub struct Proof {
+    pub receipt: Receipt,
+    pub stats: Option<ProofStats>,
+}
+
+/// A proof was cancelled before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "proof was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A backend that proves the R0VM Helios guest.
+///
+/// Proving is blocking, so async callers should run it on a blocking thread, see
+/// [`prove_async`].
+pub trait HeliosProver: Send + Sync {
+    /// Prove the guest for the encoded `ProofInputs`, producing a receipt of `kind`.
+    ///
+    /// Once `cancel` is cancelled, the prover stops as soon as it can and returns [`Cancelled`].
+    fn prove(
+        &self,
+        encoded_inputs: &[u8],
+        kind: ReceiptKind,
+        cancel: &CancellationToken,
+    ) -> Result<Proof>;
+}
+
+/// Prove on a blocking thread.
+///
+/// The returned future only resolves once the prover has returned, so after cancelling `cancel`,
+/// awaiting it waits for the prover to stop.
+pub async fn prove_async(
+    prover: Arc<dyn HeliosProver>,
+    encoded_inputs: Vec<u8>,
+    kind: ReceiptKind,
+    cancel: CancellationToken,
+) -> Result<Proof> {
+    tokio::task::spawn_blocking(move || prover.prove(&encoded_inputs, kind, &cancel))
+        .await
+        .context("Prover panicked")?
+}
+
+/// Proves on this machine with the prover `risc0-zkvm` selects from the environment, which is
+/// the local `r0vm` unless `RISC0_PROVER` or `BONSAI_API_KEY` say otherwise.
+///
+/// Each proof runs in a child process, a copy of the current executable, which is killed if the
+/// proof is cancelled. Binaries proving with it must call [`run_if_prover_child`] first thing in
+/// `main`.
+pub struct LocalProver;
+
+impl HeliosProver for LocalProver {
+    fn prove(
+        &self,
+        encoded_inputs: &[u8],
+        kind: ReceiptKind,
+        cancel: &CancellationToken,
+    ) -> Result<Proof> {
+        static NEXT_CHILD: AtomicU64 = AtomicU64::new(0);
+
+        let out = env::temp_dir().join(format!(
+            "r0vm-helios-proof-{}-{}",
+            process::id(),
+            NEXT_CHILD.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut child = Command::new(env::current_exe()?)
+            .env(PROVER_CHILD_VAR, kind.to_string())
+            .env(PROVER_CHILD_OUT_VAR, &out)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Failed to start prover process")?;
+
+        let written = child
+            .stdin
+            .take()
+            .context("Prover process has no stdin")?
+            .write_all(encoded_inputs);
+        let status = loop {
+            if written.is_err() || cancel.is_cancelled() {
+                let _ = child.kill();
+                child.wait()?;
+                let _ = fs::remove_file(&out);
+                written.context("Failed to send inputs to prover process")?;
+                return Err(Cancelled.into());
+            }
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            thread::sleep(CANCEL_POLL_INTERVAL);
+        };
+        if !status.success() {
+            let _ = fs::remove_file(&out);
+            return Err(anyhow::anyhow!("Prover process exited with {}", status));
+        }
+
+        let result = read_child_proof(&out);
+        fs::remove_file(&out)?;
+        result
+    }
+}
+
+/// Prove in this process if it was started as the child process of a [`LocalProver`], then
+/// exit. Returns otherwise.
+///
+/// The child reads the encoded `ProofInputs` from stdin, and writes a [`ProveResponse`] and the
+/// CBOR-encoded `Receipt` to the file its parent named, like the socket protocol does.
+pub fn run_if_prover_child() {
+    let Ok(kind) = env::var(PROVER_CHILD_VAR) else {
+        return;
+    };
+
+    let result = (|| -> Result<()> {
+        let out = PathBuf::from(env::var(PROVER_CHILD_OUT_VAR)?);
+        let mut inputs = Vec::new();
+        io::stdin().read_to_end(&mut inputs)?;
+
+        let (response, receipt) = match prove_locally(&inputs, kind.parse()?) {
+            Ok(proof) => (
+                ProveResponse {
+                    error: None,
+                    stats: proof.stats,
+                },
+                serde_cbor::to_vec(&proof.receipt)?,
+            ),
+            Err(e) => (
+                ProveResponse {
+                    error: Some(format!("{:#}", e)),
+                    stats: None,
+                },
+                Vec::new(),
+            ),
+        };
+        let mut encoded = Vec::new();
+        write_header(&mut encoded, &serde_json::to_vec(&response)?)?;
+        write_payload(&mut encoded, &receipt)?;
+        write_atomic(&out, &encoded)
+    })();
+
+    match result {
+        Ok(()) => process::exit(0),
+        Err(e) => {
+            eprintln!("Prover process failed: {:#}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn prove_locally(encoded_inputs: &[u8], kind: ReceiptKind) -> Result<Proof> {
+    let env = ExecutorEnv::builder().write_frame(encoded_inputs).build()?;
+    let info = default_prover().prove_with_opts(env, R0VM_HELIOS_GUEST_ELF, &kind.prover_opts())?;
+
+    Ok(Proof {
+        receipt: info.receipt,
+        stats: Some(ProofStats {
+            total_cycles: info.stats.total_cycles,
+            user_cycles: info.stats.user_cycles,
+            segments: info.stats.segments,
+        }),
+    })
+}
+
+fn read_child_proof(path: &Path) -> Result<Proof> {
+    let mut file = fs::File::open(path)?;
+    let response: ProveResponse = serde_json::from_slice(&read_header(&mut file)?)?;
+    let receipt = read_payload(&mut file)?;
+    if let Some(error) = response.error {
+        return Err(anyhow::anyhow!("Prover process failed: {}", error));
+    }
+
+    Ok(Proof {
+        receipt: serde_cbor::from_slice(&receipt)?,
+        stats: response.stats,
+    })
+}
+
+/// Executes the guest without proving, and returns a fake receipt of its journal.
+///
+/// Fake receipts are only accepted by contracts deployed in
+/// [dev-mode](https://dev.risczero.com/api/generating-proofs/dev-mode), with a mock verifier.
+pub struct DevModeProver;
+
+impl HeliosProver for DevModeProver {
+    fn prove(
+        &self,
+        encoded_inputs: &[u8],
+        _kind: ReceiptKind,
+        cancel: &CancellationToken,
+    ) -> Result<Proof> {
+        // Executing is quick enough to only check for cancellation before starting.
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        let env = ExecutorEnv::builder().write_frame(encoded_inputs).build()?;
+        let session = default_executor().execute(env, R0VM_HELIOS_GUEST_ELF)?;
+
+        let journal = session.journal.bytes;
+        let claim = ReceiptClaim::ok(R0VM_HELIOS_GUEST_ID, journal.clone());
+        let receipt = Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal);
+
+        Ok(Proof {
+            receipt,
+            stats: Some(ProofStats {
+                total_cycles: session
+                    .segments
+                    .iter()
+                    .map(|segment| 1u64 << segment.po2)
+                    .sum(),
+                user_cycles: session
+                    .segments
+                    .iter()
+                    .map(|segment| segment.cycles as u64)
+                    .sum(),
+                segments: session.segments.len(),
+            }),
+        })
+    }
+}
+
+/// Header of a proof request sent to an external prover.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProveRequest {
+    pub receipt_kind: ReceiptKind,
+}
+
+/// Header of an external prover's response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProveResponse {
+    /// Why proving failed. No receipt is sent if set.
+    pub error: Option<String>,
+    pub stats: Option<ProofStats>,
+}
+
+/// Proves with an external prover listening on a Unix socket.
+pub struct SocketProver {
+    pub path: PathBuf,
+}
+
+impl HeliosProver for SocketProver {
+    fn prove(
+        &self,
+        encoded_inputs: &[u8],
+        kind: ReceiptKind,
+        cancel: &CancellationToken,
+    ) -> Result<Proof> {
+        let mut stream = UnixStream::connect(&self.path)
+            .with_context(|| format!("Failed to connect to prover at {}", self.path.display()))?;
+
+        let request = ProveRequest { receipt_kind: kind };
+        write_header(&mut stream, &serde_json::to_vec(&request)?)?;
+        write_payload(&mut stream, encoded_inputs)?;
+
+        // Returning closes the connection, which cancels the proof.
+        stream.set_read_timeout(Some(CANCEL_POLL_INTERVAL))?;
+        let mut reader = CancellableRead {
+            inner: &stream,
+            cancel,
+        };
+        let result =
+            read_header(&mut reader).and_then(|header| Ok((header, read_payload(&mut reader)?)));
+        let (header, receipt) = match result {
+            Err(_) if cancel.is_cancelled() => return Err(Cancelled.into()),
+            result => result?,
+        };
+
+        let response: ProveResponse = serde_json::from_slice(&header)?;
+        if let Some(error) = response.error {
+            return Err(anyhow::anyhow!("External prover failed: {}", error));
+        }
+
+        Ok(Proof {
+            receipt: serde_cbor::from_slice(&receipt)?,
+            stats: response.stats,
+        })
+    }
+}
+
+/// Proves with an external prover watching a shared directory.
+pub struct DirectoryProver {
+    pub dir: PathBuf,
+    /// Time to wait for the prover's response before giving up.
+    pub timeout: Duration,
+}
+
+impl HeliosProver for DirectoryProver {
+    fn prove(
+        &self,
+        encoded_inputs: &[u8],
+        kind: ReceiptKind,
+        cancel: &CancellationToken,
+    ) -> Result<Proof> {
+        const POLL_INTERVAL_SECS: u64 = 5;
+
+        let id = format!("{}-{}", Impl::hash_bytes(encoded_inputs), kind);
+        let job_dir = self.dir.join(&id);
+        // Submit the request again if a previous one for the same inputs was cancelled and the
+        // prover hasn't cleaned it up yet.
+        if job_dir.join("cancel").exists() {
+            fs::remove_dir_all(&job_dir)?;
+        }
+        // A previous request for the same inputs may already have been answered.
+        if !job_dir.join("response.json").exists() {
+            write_atomic(&job_dir.join("inputs.cbor"), encoded_inputs)?;
+            let request = ProveRequest { receipt_kind: kind };
+            write_atomic(
+                &job_dir.join("request.json"),
+                &serde_json::to_vec_pretty(&request)?,
+            )?;
+        }
+
+        let started = Instant::now();
+        let response_path = job_dir.join("response.json");
+        while !response_path.exists() {
+            if started.elapsed() >= self.timeout {
+                return Err(anyhow::anyhow!(
+                    "External prover didn't answer request {} within {:?}",
+                    id,
+                    self.timeout
+                ));
+            }
+            if sleep_unless_cancelled(Duration::from_secs(POLL_INTERVAL_SECS), cancel) {
+                write_atomic(&job_dir.join("cancel"), &[])?;
+                return Err(Cancelled.into());
+            }
+        }
+
+        let response: ProveResponse = serde_json::from_slice(&fs::read(&response_path)?)?;
+        let result = match response.error {
+            Some(error) => Err(anyhow::anyhow!("External prover failed: {}", error)),
+            None => Ok(Proof {
+                receipt: serde_cbor::from_slice(&fs::read(job_dir.join("receipt.cbor"))?)?,
+                stats: response.stats,
+            }),
+        };
+        fs::remove_dir_all(&job_dir)?;
+        result
+    }
+}
+
+/// Build the prover backend selected by the environment.
+///
+/// `PROVER` is one of `local` (the default), `dev-mode`, `socket` or `directory`. The external
+/// backends read `PROVER_SOCKET_PATH`, or `PROVER_DIR` and `PROVER_TIMEOUT_SECS`.
+pub fn prover_from_env() -> Result<Arc<dyn HeliosProver>> {
+    let backend = env::var("PROVER").unwrap_or("local".to_string());
+    let prover: Arc<dyn HeliosProver> = match backend.as_str() {
+        "local" => Arc::new(LocalProver),
+        "dev-mode" => Arc::new(DevModeProver),
+        "socket" => Arc::new(SocketProver {
+            path: env::var("PROVER_SOCKET_PATH")
+                .context("PROVER_SOCKET_PATH not set")?
+                .into(),
+        }),
+        "directory" => Arc::new(DirectoryProver {
+            dir: env::var("PROVER_DIR").context("PROVER_DIR not set")?.into(),
+            timeout: Duration::from_secs(
+                env::var("PROVER_TIMEOUT_SECS")
+                    .unwrap_or("7200".to_string())
+                    .parse()
+                    .context("Failed to parse PROVER_TIMEOUT_SECS")?,
+            ),
+        }),
+        _ => return Err(anyhow::anyhow!("Unknown prover backend {}", backend)),
+    };
+    Ok(prover)
+}
+
+/// Serve proof requests from [`SocketProver`]s on the Unix socket at `path`, one at a time.
+pub fn serve_socket(path: &Path, prover: &dyn HeliosProver) -> Result<()> {
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind prover socket {}", path.display()))?;
+    info!("Serving proof requests on {}", path.display());
+
+    for stream in listener.incoming() {
+        let result = stream
+            .map_err(anyhow::Error::from)
+            .and_then(|mut stream| handle_socket_request(&mut stream, prover));
+        if let Err(e) = result {
+            error!("Failed to handle proof request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_socket_request(stream: &mut UnixStream, prover: &dyn HeliosProver) -> Result<()> {
+    let request: ProveRequest = serde_json::from_slice(&read_header(stream)?)?;
+    let inputs = read_payload(stream)?;
+    info!("Proving {} receipt", request.receipt_kind);
+
+    // The operator sends nothing more after its request, and closes the connection to cancel it.
+    let cancel = CancellationToken::new();
+    let watcher = stream.try_clone()?;
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let _ = (&watcher).read(&mut [0u8; 1]);
+            cancel.cancel();
+        });
+        let proof = prover.prove(&inputs, request.receipt_kind, &cancel);
+        let result = if cancel.is_cancelled() {
+            info!("Proof request cancelled");
+            Ok(())
+        } else {
+            respond(stream, proof)
+        };
+        // Unblock the watcher.
+        let _ = watcher.shutdown(Shutdown::Both);
+        result
+    })
+}
+
+fn respond(stream: &mut UnixStream, proof: Result<Proof>) -> Result<()> {
+    let (response, receipt) = match proof {
+        Ok(proof) => (
+            ProveResponse {
+                error: None,
+                stats: proof.stats,
+            },
+            serde_cbor::to_vec(&proof.receipt)?,
+        ),
+        Err(e) => (
+            ProveResponse {
+                error: Some(format!("{:#}", e)),
+                stats: None,
+            },
+            Vec::new(),
+        ),
+    };
+    write_header(stream, &serde_json::to_vec(&response)?)?;
+    write_payload(stream, &receipt)
+}
+
+/// Serve proof requests from [`DirectoryProver`]s in `dir`, polling it every `poll_interval`.
+pub fn serve_directory(
+    dir: &Path,
+    prover: &dyn HeliosProver,
+    poll_interval: Duration,
+) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    info!("Serving proof requests in {}", dir.display());
+
+    loop {
+        for entry in fs::read_dir(dir)? {
+            let job_dir = entry?.path();
+            if job_dir.join("cancel").exists() {
+                let _ = fs::remove_dir_all(&job_dir);
+                continue;
+            }
+            if !job_dir.join("request.json").exists() || job_dir.join("response.json").exists() {
+                continue;
+            }
+            if let Err(e) = handle_directory_request(&job_dir, prover) {
+                error!(
+                    "Failed to handle proof request {}: {}",
+                    job_dir.display(),
+                    e
+                );
+            }
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+fn handle_directory_request(job_dir: &Path, prover: &dyn HeliosProver) -> Result<()> {
+    let request: ProveRequest = serde_json::from_slice(&fs::read(job_dir.join("request.json"))?)?;
+    let inputs = fs::read(job_dir.join("inputs.cbor"))?;
+    info!(
+        "Proving {} receipt for {}",
+        request.receipt_kind,
+        job_dir.display()
+    );
+
+    // The operator writes the `cancel` file to cancel the request.
+    let cancel = CancellationToken::new();
+    let done = AtomicBool::new(false);
+    let proof = thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(Ordering::Relaxed) && !cancel.is_cancelled() {
+                if job_dir.join("cancel").exists() {
+                    cancel.cancel();
+                }
+                thread::sleep(CANCEL_POLL_INTERVAL);
+            }
+        });
+        let proof = prover.prove(&inputs, request.receipt_kind, &cancel);
+        done.store(true, Ordering::Relaxed);
+        proof
+    });
+    if cancel.is_cancelled() {
+        info!("Proof request {} cancelled", job_dir.display());
+        return Ok(fs::remove_dir_all(job_dir)?);
+    }
+
+    let response = match proof {
+        Ok(proof) => {
+            write_atomic(
+                &job_dir.join("receipt.cbor"),
+                &serde_cbor::to_vec(&proof.receipt)?,
+            )?;
+            ProveResponse {
+                error: None,
+                stats: proof.stats,
+            }
+        }
+        Err(e) => ProveResponse {
+            error: Some(format!("{:#}", e)),
+            stats: None,
+        },
+    };
+    write_atomic(
+        &job_dir.join("response.json"),
+        &serde_json::to_vec_pretty(&response)?,
+    )
+}
+
+/// Sleep for `duration`, waking up early if `cancel` is cancelled. Returns whether it was.
+fn sleep_unless_cancelled(duration: Duration, cancel: &CancellationToken) -> bool {
+    let deadline = Instant::now() + duration;
+    while !cancel.is_cancelled() {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep(CANCEL_POLL_INTERVAL.min(deadline - now));
+    }
+    true
+}
+
+/// Reads from a stream with a read timeout, retrying on timeouts until `cancel` is cancelled.
+struct CancellableRead<'a, R> {
+    inner: R,
+    cancel: &'a CancellationToken,
+}
+
+impl<R: Read> Read for CancellableRead<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.cancel.is_cancelled() {
+                return Err(io::Error::new(io::ErrorKind::Other, Cancelled));
+            }
+            match self.inner.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+fn write_header(stream: &mut impl Write, header: &[u8]) -> Result<()> {
+    stream.write_all(&(header.len() as u32).to_le_bytes())?;
+    stream.write_all(header)?;
+    Ok(())
+}
+
+fn write_payload(stream: &mut impl Write, payload: &[u8]) -> Result<()> {
+    stream.write_all(&(payload.len() as u64).to_le_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn read_header(stream: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let mut header = vec![0u8; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut header)?;
+    Ok(header)
+}
+
+fn read_payload(stream: &mut impl Read) -> Result<Vec<u8>> {
+    let mut len = [0u8; 8];
+    stream.read_exact(&mut len)?;
+    let mut payload = vec![0u8; u64::from_le_bytes(len) as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::sol_types::SolValue;
+    use alloy_primitives::U256;
+    use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
+    use helios_consensus_core::types::{FinalityUpdate, LightClientHeader, LightClientStore};
+    use helios_ethereum::config::networks::Network;
+    use r0vm_helios_primitives::types::{ProofInputs, ProofOutputs};
+    use r0vm_helios_synthetic::{
+        finalized_slot, parse, ChainSpec, SyntheticChain, UpdateOptions, SLOTS_PER_EPOCH,
+        SLOTS_PER_PERIOD,
+    };
+    use std::sync::Mutex;
+
+    /// A Deneb period of mainnet.
+    const PERIOD: u64 = 1300;
+    const CHECKPOINT_SLOT: u64 = PERIOD * SLOTS_PER_PERIOD + 2 * SLOTS_PER_EPOCH;
+    const HEAD_SLOT: u64 = PERIOD * SLOTS_PER_PERIOD + 100 * SLOTS_PER_EPOCH;
+
+    /// Encoded inputs advancing a store bootstrapped from a synthetic checkpoint to a later head
+    /// of the same period.
+    fn synthetic_inputs() -> Vec<u8> {
+        let chain = SyntheticChain::new(ChainSpec::deneb(Network::Mainnet), b"prover");
+        let bootstrap = chain.bootstrap(CHECKPOINT_SLOT);
+        let header: LightClientHeader =
+            serde_json::from_value(bootstrap["data"]["header"].clone()).unwrap();
+        let store = LightClientStore {
+            finalized_header: header.clone(),
+            optimistic_header: header,
+            current_sync_committee: serde_json::from_value(
+                bootstrap["data"]["current_sync_committee"].clone(),
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let finality_update: FinalityUpdate<MainnetConsensusSpec> =
+            parse(&chain.finality_update(HEAD_SLOT, &UpdateOptions::default())).unwrap();
+
+        let inputs = ProofInputs {
+            sync_committee_updates: Vec::new(),
+            finality_update: Some(finality_update),
+            expected_current_slot: HEAD_SLOT,
+            store,
+            genesis_root: chain.spec().genesis_validators_root,
+            forks: Network::Mainnet.to_base_config().forks,
+            contract_storage_slots: None,
+        };
+        serde_cbor::to_vec(&inputs).unwrap()
+    }
+
+    /// Proves with a [`DevModeProver`], recording the kinds of receipt it is asked for.
+    #[derive(Default)]
+    struct RecordingProver {
+        kinds: Mutex<Vec<ReceiptKind>>,
+    }
+
+    impl HeliosProver for RecordingProver {
+        fn prove(
+            &self,
+            encoded_inputs: &[u8],
+            kind: ReceiptKind,
+            cancel: &CancellationToken,
+        ) -> Result<Proof> {
+            self.kinds.lock().unwrap().push(kind);
+            DevModeProver.prove(encoded_inputs, kind, cancel)
+        }
+    }
+
+    fn assert_proves_synthetic_inputs(proof: Proof) {
+        let outputs = ProofOutputs::abi_decode(&proof.receipt.journal.bytes, true).unwrap();
+        assert_eq!(outputs.prevHead, U256::from(CHECKPOINT_SLOT));
+        assert_eq!(outputs.newHead, U256::from(finalized_slot(HEAD_SLOT)));
+        assert!(outputs.slots.is_empty());
+        assert!(proof.stats.is_some());
+    }
+
+    /// Proves until cancelled, recording that it stopped.
+    struct SlowProver {
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl HeliosProver for SlowProver {
+        fn prove(
+            &self,
+            _encoded_inputs: &[u8],
+            _kind: ReceiptKind,
+            cancel: &CancellationToken,
+        ) -> Result<Proof> {
+            while !cancel.is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            self.stopped.store(true, Ordering::SeqCst);
+            Err(Cancelled.into())
+        }
+    }
+
+    fn slow_prover() -> (SlowProver, Arc<AtomicBool>) {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let prover = SlowProver {
+            stopped: stopped.clone(),
+        };
+        (prover, stopped)
+    }
+
+    fn wait_for(flag: &AtomicBool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !flag.load(Ordering::SeqCst) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    fn cancel_after(cancel: &CancellationToken, delay: Duration) {
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            cancel.cancel();
+        });
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_prover() {
+        let (prover, stopped) = slow_prover();
+        let cancel = CancellationToken::new();
+        let proving = prove_async(
+            Arc::new(prover),
+            Vec::new(),
+            ReceiptKind::default(),
+            cancel.clone(),
+        );
+
+        cancel_after(&cancel, Duration::from_millis(50));
+        let error = proving.await.err().unwrap();
+        assert!(error.downcast_ref::<Cancelled>().is_some());
+        // The future only resolves once the prover has returned.
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn cancels_socket_prover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prover.sock");
+        let (prover, stopped) = slow_prover();
+        {
+            let path = path.clone();
+            thread::spawn(move || serve_socket(&path, &prover));
+        }
+        while !path.exists() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let cancel = CancellationToken::new();
+        cancel_after(&cancel, Duration::from_millis(200));
+        let error = SocketProver { path }
+            .prove(b"inputs", ReceiptKind::default(), &cancel)
+            .err()
+            .unwrap();
+        assert!(error.downcast_ref::<Cancelled>().is_some());
+        assert!(wait_for(&stopped));
+    }
+
+    #[test]
+    fn cancels_directory_prover() {
+        let dir = tempfile::tempdir().unwrap();
+        let (prover, stopped) = slow_prover();
+        {
+            let dir = dir.path().to_path_buf();
+            thread::spawn(move || serve_directory(&dir, &prover, Duration::from_millis(10)));
+        }
+
+        let cancel = CancellationToken::new();
+        cancel_after(&cancel, Duration::from_millis(200));
+        let error = DirectoryProver {
+            dir: dir.path().to_path_buf(),
+            timeout: Duration::from_secs(60),
+        }
+        .prove(b"inputs", ReceiptKind::default(), &cancel)
+        .err()
+        .unwrap();
+        assert!(error.downcast_ref::<Cancelled>().is_some());
+        assert!(wait_for(&stopped));
+
+        // The prover cleans up cancelled requests.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while fs::read_dir(dir.path()).unwrap().next().is_some() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn proves_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prover.sock");
+        let prover = Arc::new(RecordingProver::default());
+        {
+            let (path, prover) = (path.clone(), prover.clone());
+            thread::spawn(move || serve_socket(&path, prover.as_ref()));
+        }
+        while !path.exists() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let proof = SocketProver { path }
+            .prove(
+                &synthetic_inputs(),
+                ReceiptKind::Succinct,
+                &CancellationToken::new(),
+            )
+            .unwrap();
+        assert_proves_synthetic_inputs(proof);
+        assert_eq!(*prover.kinds.lock().unwrap(), vec![ReceiptKind::Succinct]);
+    }
+
+    #[test]
+    fn proves_through_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let prover = Arc::new(RecordingProver::default());
+        {
+            let (dir, prover) = (dir.path().to_path_buf(), prover.clone());
+            thread::spawn(move || {
+                serve_directory(&dir, prover.as_ref(), Duration::from_millis(10))
+            });
+        }
+
+        let proof = DirectoryProver {
+            dir: dir.path().to_path_buf(),
+            timeout: Duration::from_secs(600),
+        }
+        .prove(
+            &synthetic_inputs(),
+            ReceiptKind::Composite,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_proves_synthetic_inputs(proof);
+        assert_eq!(*prover.kinds.lock().unwrap(), vec![ReceiptKind::Composite]);
+        // The operator removes the request once answered.
+        assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::contract::IRiscZeroVerifier::IRiscZeroVerifierErrors;
+use crate::contract::R0VMHelios::R0VMHeliosErrors;
+use alloy::sol_types::SolInterface;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use std::fmt;
+
+/// Why `R0VMHelios.update` reverts, decoded from the contract's custom errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateRevert {
+    /// The update doesn't advance past the head it is proven from.
+    SlotBehindHead {
+        slot: U256,
+    },
+    /// The contract already stores a different sync committee for `period`.
+    SyncCommitteeAlreadySet {
+        period: U256,
+    },
+    /// The contract already stores a different header for `slot`.
+    InvalidHeaderRoot {
+        slot: U256,
+    },
+    /// The contract already stores a different execution state root for `slot`.
+    InvalidStateRoot {
+        slot: U256,
+    },
+    /// The proof starts from a sync committee the contract doesn't store for its period.
+    SyncCommitteeStartMismatch {
+        given: B256,
+        expected: B256,
+    },
+    /// The contract stores no header for the head the update is proven from.
+    PreviousHeadNotSet {
+        slot: U256,
+    },
+    /// The head the update is proven from is older than `MAX_SLOT_AGE`.
+    PreviousHeadTooOld {
+        slot: U256,
+    },
+    NoUpdatersProvided,
+    /// The relayer lacks the updater role.
+    Unauthorized {
+        account: Address,
+    },
+    /// The verifier rejected the proof.
+    VerificationFailed,
+    /// A revert this enum doesn't know about, with its raw data.
+    Unknown(Bytes),
+}
+
+/// How the operator recovers from an update that would revert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// The update is no longer needed. Drop it and fetch the next one.
+    Skip,
+    /// The light client store diverges from the contract. Drop the update and bootstrap again
+    /// from the contract's state.
+    Rebootstrap,
+    /// The update it builds on may not have landed yet. Simulate it again later.
+    Wait,
+    /// The update can't succeed without changes to the contract or the operator's configuration.
+    Abort,
+}
+
+impl UpdateRevert {
+    /// Decode the revert data of a failed `update` call.
+    pub fn decode(data: &[u8]) -> Self {
+        if let Ok(error) = R0VMHeliosErrors::abi_decode(data, true) {
+            return error.into();
+        }
+        match IRiscZeroVerifierErrors::abi_decode(data, true) {
+            Ok(IRiscZeroVerifierErrors::VerificationFailed(_)) => Self::VerificationFailed,
+            Err(_) => Self::Unknown(Bytes::copy_from_slice(data)),
+        }
+    }
+
+    pub fn recovery(&self) -> RecoveryAction {
+        match self {
+            UpdateRevert::SlotBehindHead { .. } => RecoveryAction::Skip,
+            UpdateRevert::SyncCommitteeAlreadySet { .. }
+            | UpdateRevert::SyncCommitteeStartMismatch { .. } => RecoveryAction::Rebootstrap,
+            UpdateRevert::PreviousHeadNotSet { .. } => RecoveryAction::Wait,
+            UpdateRevert::InvalidHeaderRoot { .. }
+            | UpdateRevert::InvalidStateRoot { .. }
+            | UpdateRevert::PreviousHeadTooOld { .. }
+            | UpdateRevert::NoUpdatersProvided
+            | UpdateRevert::Unauthorized { .. }
+            | UpdateRevert::VerificationFailed
+            | UpdateRevert::Unknown(_) => RecoveryAction::Abort,
+        }
+    }
+}
+
+impl From<R0VMHeliosErrors> for UpdateRevert {
+    fn from(error: R0VMHeliosErrors) -> Self {
+        match error {
+            R0VMHeliosErrors::SlotBehindHead(e) => Self::SlotBehindHead { slot: e.slot },
+            R0VMHeliosErrors::SyncCommitteeAlreadySet(e) => {
+                Self::SyncCommitteeAlreadySet { period: e.period }
+            }
+            R0VMHeliosErrors::InvalidHeaderRoot(e) => Self::InvalidHeaderRoot { slot: e.slot },
+            R0VMHeliosErrors::InvalidStateRoot(e) => Self::InvalidStateRoot { slot: e.slot },
+            R0VMHeliosErrors::SyncCommitteeStartMismatch(e) => Self::SyncCommitteeStartMismatch {
+                given: e.given,
+                expected: e.expected,
+            },
+            R0VMHeliosErrors::PreviousHeadNotSet(e) => Self::PreviousHeadNotSet { slot: e.slot },
+            R0VMHeliosErrors::PreviousHeadTooOld(e) => Self::PreviousHeadTooOld { slot: e.slot },
+            R0VMHeliosErrors::NoUpdatersProvided(_) => Self::NoUpdatersProvided,
+            R0VMHeliosErrors::AccessControlUnauthorizedAccount(e) => {
+                Self::Unauthorized { account: e.account }
+            }
+            // Only raised by `renounceRole`.
+            error @ R0VMHeliosErrors::AccessControlBadConfirmation(_) => {
+                Self::Unknown(error.abi_encode().into())
+            }
+        }
+    }
+}
+
+impl fmt::Display for UpdateRevert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateRevert::SlotBehindHead { slot } => {
+                write!(f, "SlotBehindHead: new head {} is not past the contract head", slot)
+            }
+            UpdateRevert::SyncCommitteeAlreadySet { period } => write!(
+                f,
+                "SyncCommitteeAlreadySet: a different sync committee is stored for period {}",
+                period
+            ),
+            UpdateRevert::InvalidHeaderRoot { slot } => write!(
+                f,
+                "InvalidHeaderRoot: a different header is stored for slot {}",
+                slot
+            ),
+            UpdateRevert::InvalidStateRoot { slot } => write!(
+                f,
+                "InvalidStateRoot: a different execution state root is stored for slot {}",
+                slot
+            ),
+            UpdateRevert::SyncCommitteeStartMismatch { given, expected } => write!(
+                f,
+                "SyncCommitteeStartMismatch: proof starts from sync committee {:?}, contract stores {:?}",
+                given, expected
+            ),
+            UpdateRevert::PreviousHeadNotSet { slot } => write!(
+                f,
+                "PreviousHeadNotSet: no header is stored for slot {}",
+                slot
+            ),
+            UpdateRevert::PreviousHeadTooOld { slot } => write!(
+                f,
+                "PreviousHeadTooOld: head {} is older than MAX_SLOT_AGE",
+                slot
+            ),
+            UpdateRevert::NoUpdatersProvided => write!(f, "NoUpdatersProvided"),
+            UpdateRevert::Unauthorized { account } => write!(
+                f,
+                "AccessControlUnauthorizedAccount: {:?} is not an updater",
+                account
+            ),
+            UpdateRevert::VerificationFailed => {
+                write!(f, "VerificationFailed: the verifier rejected the proof")
+            }
+            UpdateRevert::Unknown(data) => write!(f, "unknown revert {}", data),
+        }
+    }
+}
+
+impl std::error::Error for UpdateRevert {}
+
+/// The revert data carried by a failed contract call, if the node returned any.
+pub fn revert_data(error: &alloy::contract::Error) -> Option<Bytes> {
+    match error {
+        alloy::contract::Error::TransportError(e) => e
+            .as_error_resp()
+            .and_then(|payload| payload.as_revert_data()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{IRiscZeroVerifier, R0VMHelios};
+    use alloy::sol_types::SolError;
+
+    fn decode(error: impl SolError) -> UpdateRevert {
+        UpdateRevert::decode(&error.abi_encode())
+    }
+
+    #[test]
+    fn decodes_contract_errors() {
+        let slot = U256::from(7_000_000);
+        let given = B256::repeat_byte(1);
+        let expected = B256::repeat_byte(2);
+        let account = Address::repeat_byte(3);
+
+        assert_eq!(
+            decode(R0VMHelios::SlotBehindHead { slot }),
+            UpdateRevert::SlotBehindHead { slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::SyncCommitteeAlreadySet { period: slot }),
+            UpdateRevert::SyncCommitteeAlreadySet { period: slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::InvalidHeaderRoot { slot }),
+            UpdateRevert::InvalidHeaderRoot { slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::InvalidStateRoot { slot }),
+            UpdateRevert::InvalidStateRoot { slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::SyncCommitteeStartMismatch { given, expected }),
+            UpdateRevert::SyncCommitteeStartMismatch { given, expected }
+        );
+        assert_eq!(
+            decode(R0VMHelios::PreviousHeadNotSet { slot }),
+            UpdateRevert::PreviousHeadNotSet { slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::PreviousHeadTooOld { slot }),
+            UpdateRevert::PreviousHeadTooOld { slot }
+        );
+        assert_eq!(
+            decode(R0VMHelios::NoUpdatersProvided {}),
+            UpdateRevert::NoUpdatersProvided
+        );
+        assert_eq!(
+            decode(R0VMHelios::AccessControlUnauthorizedAccount {
+                account,
+                neededRole: B256::repeat_byte(4),
+            }),
+            UpdateRevert::Unauthorized { account }
+        );
+    }
+
+    #[test]
+    fn decodes_verifier_errors() {
+        let revert = decode(IRiscZeroVerifier::VerificationFailed {});
+        assert_eq!(revert, UpdateRevert::VerificationFailed);
+        assert_eq!(revert.recovery(), RecoveryAction::Abort);
+    }
+
+    #[test]
+    fn keeps_unknown_reverts() {
+        let data = R0VMHelios::AccessControlBadConfirmation {}.abi_encode();
+        assert_eq!(
+            UpdateRevert::decode(&data),
+            UpdateRevert::Unknown(data.into())
+        );
+
+        let data = [0xde, 0xad, 0xbe, 0xef, 0x00];
+        assert_eq!(
+            UpdateRevert::decode(&data),
+            UpdateRevert::Unknown(Bytes::copy_from_slice(&data))
+        );
+        assert_eq!(
+            UpdateRevert::decode(&[]),
+            UpdateRevert::Unknown(Bytes::new())
+        );
+    }
+
+    #[test]
+    fn recovers_by_error() {
+        let slot = U256::from(1);
+        assert_eq!(
+            UpdateRevert::SlotBehindHead { slot }.recovery(),
+            RecoveryAction::Skip
+        );
+        assert_eq!(
+            UpdateRevert::SyncCommitteeAlreadySet { period: slot }.recovery(),
+            RecoveryAction::Rebootstrap
+        );
+        assert_eq!(
+            UpdateRevert::PreviousHeadNotSet { slot }.recovery(),
+            RecoveryAction::Wait
+        );
+        assert_eq!(
+            UpdateRevert::PreviousHeadTooOld { slot }.recovery(),
+            RecoveryAction::Abort
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result};
+use std::{env, fs};
+
+/// Read the secret `var` from the file named by `<var>_FILE` if it is set, or from `var` itself.
+///
+/// Surrounding whitespace, such as the trailing newline of a mounted secret, is trimmed from the
+/// file contents.
+pub fn read_secret(var: &str) -> Result<Option<String>> {
+    let file_var = format!("{}_FILE", var);
+    match env::var(&file_var) {
+        Ok(path) => {
+            let secret = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {} from {}", var, path))?;
+            Ok(Some(secret.trim().to_string()))
+        }
+        Err(_) => Ok(env::var(var).ok()),
+    }
+}
+
+/// Like [`read_secret`], but fails if the secret isn't set.
+pub fn require_secret(var: &str) -> Result<String> {
+    read_secret(var)?.with_context(|| format!("{} not set", var))
+}
+
+/// Load the signer configured by the variables starting with `prefix`.
+///
+/// If `<prefix>KEYSTORE_PATH` is set, the signer is decrypted from that JSON keystore (Web3 Secret
+/// Storage) with the passphrase in `<prefix>KEYSTORE_PASSWORD`. Otherwise it is parsed from the
+/// hex private key in `<prefix>PRIVATE_KEY`. Both secrets can be read from a file instead, see
+/// [`read_secret`].
+pub fn signer_from_env(prefix: &str) -> Result<PrivateKeySigner> {
+    let keystore_var = format!("{}KEYSTORE_PATH", prefix);
+    if let Ok(keystore) = env::var(&keystore_var) {
+        let password = require_secret(&format!("{}KEYSTORE_PASSWORD", prefix))?;
+        return PrivateKeySigner::decrypt_keystore(&keystore, password)
+            .with_context(|| format!("Failed to decrypt keystore {}", keystore));
+    }
+
+    let private_key_var = format!("{}PRIVATE_KEY", prefix);
+    require_secret(&private_key_var)
+        .with_context(|| format!("Neither {} nor {} set", keystore_var, private_key_var))?
+        .parse()
+        .with_context(|| format!("Failed to parse {}", private_key_var))
+}
@@ -0,0 +1,136 @@
+//! The operator's status, and the HTTP server exposing it alongside its metrics and health probes.
+
+use crate::db::ConfirmedRelay;
+use crate::metrics::Metrics;
+use alloy_primitives::{Address, B256, U256};
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use log::{error, info};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// What the fetch stage is currently doing, as reported by `/status`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchStatus {
+    Starting,
+    Syncing,
+    Fetching,
+    WaitingForProof,
+    Sleeping,
+}
+
+/// The last update relayed successfully to a destination.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayStatus {
+    pub head: u64,
+    pub tx_hash: Option<B256>,
+    pub timestamp: u64,
+}
+
+impl From<ConfirmedRelay> for RelayStatus {
+    fn from(relay: ConfirmedRelay) -> Self {
+        Self {
+            head: relay.head,
+            tx_hash: relay.tx_hash,
+            timestamp: relay.confirmed_at,
+        }
+    }
+}
+
+/// State of a single destination served by the `/status` endpoint.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DestinationStatus {
+    pub contract_address: Address,
+    pub contract_head: Option<u64>,
+    pub contract_image_id: Option<B256>,
+    pub relayer_address: Address,
+    pub relayer_balance: Option<U256>,
+    pub relaying_job: Option<String>,
+    pub last_successful_relay: Option<RelayStatus>,
+}
+
+/// Operator state served by the `/status` endpoint.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorStatus {
+    pub fetch_stage: FetchStatus,
+    pub proving_job: Option<String>,
+    pub source_finalized_head: Option<u64>,
+    pub image_id: B256,
+    pub destinations: BTreeMap<String, DestinationStatus>,
+}
+
+impl OperatorStatus {
+    /// The operator is ready once it has read every destination contract and proves with the same
+    /// image ID they verify against.
+    pub fn is_ready(&self) -> bool {
+        self.destinations.values().all(|destination| {
+            destination.contract_head.is_some()
+                && destination.contract_image_id == Some(self.image_id)
+        })
+    }
+}
+
+/// State shared with the HTTP server.
+#[derive(Clone)]
+pub struct AppState {
+    pub metrics: Arc<Metrics>,
+    pub status: Arc<RwLock<OperatorStatus>>,
+}
+
+/// Serve the operator's metrics, health probes and status on `addr`.
+///
+/// - `/metrics`: Prometheus metrics.
+/// - `/healthz`: liveness, always `200 OK` while the process is serving requests.
+/// - `/readyz`: readiness, `200 OK` once the operator has read every destination contract and its
+///   image ID matches their `heliosImageID`, `503 Service Unavailable` otherwise.
+/// - `/status`: the current [`OperatorStatus`] as JSON.
+pub async fn serve_http(addr: SocketAddr, state: AppState, shutdown: CancellationToken) {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/status", get(status_handler))
+        .with_state(state);
+
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind HTTP server to {}: {}", addr, e);
+            return;
+        }
+    };
+
+    info!("Serving operator HTTP API on http://{}", addr);
+    if let Err(e) = axum::serve(listener, app)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await
+    {
+        error!("HTTP server failed: {}", e);
+    }
+}
+
+async fn metrics_handler(State(state): State<AppState>) -> String {
+    state.metrics.encode()
+}
+
+async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+async fn readyz_handler(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.status.read().unwrap().is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+async fn status_handler(State(state): State<AppState>) -> Json<OperatorStatus> {
+    Json(state.status.read().unwrap().clone())
+}
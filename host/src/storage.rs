@@ -0,0 +1,114 @@
+use crate::secrets::require_secret;
+use alloy::{
+    eips::BlockId,
+    providers::{Provider, ProviderBuilder},
+};
+use alloy_primitives::{Address, B256, U256};
+use alloy_trie::TrieAccount;
+use anyhow::{Context, Result};
+use r0vm_helios_primitives::types::{ContractStorage, StorageSlot};
+use std::env;
+
+/// A source chain contract whose storage slots are proven with each update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageProofs {
+    pub contract: Address,
+    pub slots: Vec<B256>,
+}
+
+impl StorageProofs {
+    /// Read `STORAGE_CONTRACT_ADDRESS` and the comma-separated `STORAGE_SLOTS` from the
+    /// environment.
+    ///
+    /// Returns `None` if `STORAGE_CONTRACT_ADDRESS` isn't set, in which case updates prove no
+    /// storage.
+    pub fn from_env() -> Result<Option<Self>> {
+        let contract = match env::var("STORAGE_CONTRACT_ADDRESS") {
+            Ok(contract) if !contract.trim().is_empty() => contract
+                .trim()
+                .parse()
+                .context("Failed to parse STORAGE_CONTRACT_ADDRESS")?,
+            _ => return Ok(None),
+        };
+        let slots = env::var("STORAGE_SLOTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|slot| !slot.is_empty())
+            .map(|slot| {
+                slot.parse()
+                    .with_context(|| format!("Failed to parse storage slot {}", slot))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Self { contract, slots }))
+    }
+
+    /// Fetch the proofs of the storage slots at the source chain's execution block
+    /// `block_number`.
+    pub async fn fetch(&self, block_number: u64) -> Result<ContractStorage> {
+        get_contract_storage(self.contract, self.slots.clone(), block_number).await
+    }
+
+    /// Whether any of the storage slots differs between the source chain's execution blocks
+    /// `from_block` and `to_block`.
+    pub async fn changed(&self, from_block: u64, to_block: u64) -> Result<bool> {
+        let execution_rpc = require_secret("SOURCE_EXECUTION_RPC_URL")?;
+        let provider = ProviderBuilder::new().on_http(execution_rpc.parse()?);
+
+        for slot in &self.slots {
+            let key = U256::from_be_bytes(slot.0);
+            let before = provider
+                .get_storage_at(self.contract, key)
+                .block_id(BlockId::number(from_block))
+                .await?;
+            let after = provider
+                .get_storage_at(self.contract, key)
+                .block_id(BlockId::number(to_block))
+                .await?;
+            if before != after {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Fetch the account proof of `address` and the storage proofs of `slots` at `block_number` from
+/// the source chain's execution RPC.
+pub async fn get_contract_storage(
+    address: Address,
+    slots: Vec<B256>,
+    block_number: u64,
+) -> Result<ContractStorage> {
+    let execution_rpc = require_secret("SOURCE_EXECUTION_RPC_URL")?;
+    let provider = ProviderBuilder::new().on_http(execution_rpc.parse()?);
+
+    let proof = provider
+        .get_proof(address, slots.clone())
+        .block_id(BlockId::number(block_number))
+        .await?;
+
+    let storage_slots = proof
+        .storage_proof
+        .into_iter()
+        .zip(slots)
+        .map(|(storage_proof, key)| StorageSlot {
+            key,
+            expected_value: storage_proof.value,
+            mpt_proof: storage_proof.proof,
+        })
+        .collect();
+
+    Ok(ContractStorage {
+        address,
+        expected_value: TrieAccount {
+            nonce: proof.nonce,
+            balance: proof.balance,
+            storage_root: proof.storage_hash,
+            code_hash: proof.code_hash,
+        },
+        mpt_proof: proof.account_proof,
+        storage_slots,
+    })
+}
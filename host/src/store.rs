@@ -0,0 +1,33 @@
+use anyhow::Result;
+use helios_consensus_core::{consensus_spec::MainnetConsensusSpec, types::LightClientStore};
+use std::{fs, path::Path};
+
+/// Load a persisted light client store, if one exists at `path`.
+pub fn load_store(path: &Path) -> Result<Option<LightClientStore<MainnetConsensusSpec>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(path)?;
+    Ok(Some(serde_cbor::from_slice(&bytes)?))
+}
+
+/// Persist a light client store to `path`.
+///
+/// The store is written to a temporary file first and then renamed into place, so a crash
+/// mid-write never leaves a truncated store behind.
+pub fn save_store(path: &Path, store: &LightClientStore<MainnetConsensusSpec>) -> Result<()> {
+    write_atomic(path, &serde_cbor::to_vec(store)?)
+}
+
+/// Write `bytes` to a temporary file next to `path` and rename it into place.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
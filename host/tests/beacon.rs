@@ -0,0 +1,231 @@
+//! Tests of the host's beacon node client against a mock beacon node.
+//!
+//! The tests serve a synthetic mainnet chain, or the fixtures in `BEACON_FIXTURES` as recorded
+//! with `mock-beacon --upstream`, where `fixture.json` names the source chain and the slot of the
+//! checkpoint to bootstrap from.
+
+use anyhow::{Context, Result};
+use helios_consensus_core::calc_sync_period;
+use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
+use helios_ethereum::config::networks::Network;
+use helios_ethereum::rpc::ConsensusRpc;
+use r0vm_helios_mock_beacon::{Endpoint, FaultRule, MockBeacon};
+use r0vm_helios_primitives::types::ProofInputs;
+use r0vm_helios_script::events::FinalityEvents;
+use r0vm_helios_script::{
+    advance_store, get_checkpoint, get_client, get_latest_finalized_slot, get_updates,
+};
+use r0vm_helios_synthetic::{ChainSpec, SyntheticChain, SLOTS_PER_EPOCH, SLOTS_PER_PERIOD};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use std::{env, fs};
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// The host reads the beacon node URL from the environment, so tests take turns.
+static ENV: Mutex<()> = Mutex::const_new(());
+
+/// A sync committee period of the Deneb fork of mainnet, for the synthetic chain.
+const PERIOD: u64 = 1300;
+
+#[derive(Deserialize, Debug, Clone)]
+struct FixtureConfig {
+    source_chain_id: u64,
+    checkpoint_slot: u64,
+}
+
+struct Fixtures {
+    beacon: MockBeacon,
+    config: FixtureConfig,
+    _dir: Option<TempDir>,
+}
+
+/// Serve the fixtures with `faults` injected, and point the host at the server.
+async fn serve(faults: &[&str]) -> Result<Fixtures> {
+    let (beacon, config, dir) = match env::var("BEACON_FIXTURES") {
+        Ok(dir) => {
+            let config: FixtureConfig = serde_json::from_slice(
+                &fs::read(format!("{}/fixture.json", dir)).context("No fixture.json")?,
+            )?;
+            (MockBeacon::replay(dir), config, None)
+        }
+        Err(_) => {
+            let dir = tempfile::tempdir()?;
+            let config = FixtureConfig {
+                source_chain_id: 1,
+                checkpoint_slot: PERIOD * SLOTS_PER_PERIOD + 2 * SLOTS_PER_EPOCH,
+            };
+            let head_slot = (PERIOD + 2) * SLOTS_PER_PERIOD + 100 * SLOTS_PER_EPOCH;
+            SyntheticChain::new(ChainSpec::deneb(Network::Mainnet), b"host").write_fixtures(
+                dir.path(),
+                config.checkpoint_slot,
+                head_slot,
+            )?;
+            (MockBeacon::replay(dir.path()), config, Some(dir))
+        }
+    };
+
+    for fault in faults {
+        beacon.inject(fault.parse::<FaultRule>()?);
+    }
+    let url = beacon.spawn().await?;
+    env::set_var("SOURCE_CONSENSUS_RPC_URL", url.as_str());
+    env::set_var("SOURCE_CHAIN_ID", config.source_chain_id.to_string());
+    Ok(Fixtures {
+        beacon,
+        config,
+        _dir: dir,
+    })
+}
+
+/// The message of the error `result` fails with.
+fn error_message<T>(result: Result<T>) -> String {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => format!("{:#}", e),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bootstraps_and_fetches_updates() -> Result<()> {
+    let _env = ENV.lock().await;
+    let fixtures = serve(&[]).await?;
+
+    let client = get_client(get_checkpoint(fixtures.config.checkpoint_slot).await?).await?;
+    let period =
+        calc_sync_period::<MainnetConsensusSpec>(client.store.finalized_header.beacon().slot);
+
+    let updates = get_updates(&client).await?;
+    for (i, update) in updates.iter().enumerate() {
+        let slot = update.attested_header().beacon().slot;
+        assert_eq!(
+            calc_sync_period::<MainnetConsensusSpec>(slot),
+            period + i as u64
+        );
+    }
+
+    let finalized = get_latest_finalized_slot().await?;
+    assert!(finalized >= client.store.finalized_header.beacon().slot);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn advances_store_like_the_guest() -> Result<()> {
+    let _env = ENV.lock().await;
+    let fixtures = serve(&[]).await?;
+
+    let client = get_client(get_checkpoint(fixtures.config.checkpoint_slot).await?).await?;
+    let finality_update = client
+        .rpc
+        .get_finality_update()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch finality update: {}", e))?;
+    let finalized = finality_update.finalized_header().beacon().slot;
+
+    let inputs = ProofInputs {
+        sync_committee_updates: get_updates(&client).await?,
+        finality_update: Some(finality_update),
+        expected_current_slot: client.expected_current_slot(),
+        store: client.store.clone(),
+        genesis_root: client.config.chain.genesis_root,
+        forks: client.config.forks.clone(),
+        contract_storage_slots: None,
+    };
+    let mut store = client.store.clone();
+    advance_store(&mut store, &inputs)?;
+    assert_eq!(store.finalized_header.beacon().slot, finalized);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recovers_after_server_errors() -> Result<()> {
+    let _env = ENV.lock().await;
+    let fixtures = serve(&["finality_update:status=503:1"]).await?;
+
+    let error = error_message(get_latest_finalized_slot().await);
+    assert!(
+        error.starts_with("Failed to fetch finality update"),
+        "{}",
+        error
+    );
+    assert!(get_latest_finalized_slot().await.is_ok());
+    assert_eq!(fixtures.beacon.requests(Endpoint::FinalityUpdate), 2);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_faulty_responses() -> Result<()> {
+    let _env = ENV.lock().await;
+
+    // A bootstrap from another chain doesn't match the checkpoint.
+    let fixtures = serve(&["bootstrap:fork"]).await?;
+    let checkpoint = get_checkpoint(fixtures.config.checkpoint_slot).await?;
+    let bootstrap_failed = format!("Failed to bootstrap from checkpoint {}", checkpoint);
+    let error = error_message(get_client(checkpoint).await);
+    assert!(error.starts_with(&bootstrap_failed), "{}", error);
+
+    // Neither does a truncated one parse.
+    fixtures.beacon.clear_faults();
+    fixtures.beacon.inject("bootstrap:truncate".parse()?);
+    let error = error_message(get_client(checkpoint).await);
+    assert!(error.starts_with(&bootstrap_failed), "{}", error);
+
+    fixtures.beacon.clear_faults();
+    fixtures.beacon.inject("finality_update:truncate".parse()?);
+    let error = error_message(get_latest_finalized_slot().await);
+    assert!(
+        error.starts_with("Failed to fetch finality update"),
+        "{}",
+        error
+    );
+
+    // The client recovers once the faults are cleared.
+    fixtures.beacon.clear_faults();
+    assert!(get_client(checkpoint).await.is_ok());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn follows_finality_events_across_reconnects() -> Result<()> {
+    let _env = ENV.lock().await;
+    // The first subscription drops after its first event.
+    let fixtures = serve(&["events:truncate:1"]).await?;
+
+    let shutdown = CancellationToken::new();
+    let mut events =
+        FinalityEvents::subscribe(&env::var("SOURCE_CONSENSUS_RPC_URL")?, shutdown.clone())?;
+
+    // Finalize an epoch every 100ms, until the test ends.
+    let emitter = {
+        let beacon = fixtures.beacon.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            for epoch in 1u64.. {
+                beacon.emit(
+                    "finalized_checkpoint",
+                    &json!({ "block": "0x01", "state": "0x02", "epoch": epoch.to_string() }),
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+            }
+        })
+    };
+
+    // Longer than the delay before reconnecting.
+    let timeout = Duration::from_secs(60);
+    let first = tokio::time::timeout(timeout, events.next()).await?;
+    assert_eq!(first % SLOTS_PER_EPOCH, 0);
+    assert_eq!(fixtures.beacon.requests(Endpoint::Events), 1);
+
+    let second = tokio::time::timeout(timeout, events.next()).await?;
+    assert!(second > first);
+    assert_eq!(fixtures.beacon.requests(Endpoint::Events), 2);
+
+    shutdown.cancel();
+    emitter.await?;
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! End-to-end test of the operator: proves with the dev-mode prover from a synthetic source
+//! chain, and relays to an in-process EVM chain running `R0VMHelios` with a mock verifier.
+//!
+//! The contracts are built with `forge build` if `contracts/out` is missing, which needs foundry
+//! and the contracts' submodules. To test against a real source chain instead, record its
+//! responses into `E2E_FIXTURES` by setting `E2E_RECORD=1`, `E2E_GENESIS_SLOT` and the usual
+//! `SOURCE_*` and `STORAGE_*` variables. Later runs with `E2E_FIXTURES` replay them.
+
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::signers::local::PrivateKeySigner;
+use alloy_primitives::{Address, B256, U256};
+use anyhow::{Context, Result};
+use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
+use helios_ethereum::config::networks::Network;
+use helios_ethereum::rpc::{http_rpc::HttpRpc, ConsensusRpc};
+use r0vm_helios_client::{storage_key, HeliosClient, Stale};
+use r0vm_helios_e2e::deploy_light_client;
+use r0vm_helios_e2e::evm::EvmNode;
+use r0vm_helios_e2e::fixtures::{execution_url, FixtureServer, Upstream};
+use r0vm_helios_methods::R0VM_HELIOS_GUEST_ID;
+use r0vm_helios_script::consistency::{SECONDS_PER_SLOT, SLOTS_PER_EPOCH, SLOTS_PER_PERIOD};
+use r0vm_helios_script::contract::R0VMHelios;
+use r0vm_helios_script::{get_checkpoint, get_client};
+use r0vm_helios_synthetic::{ChainSpec, SyntheticChain};
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use std::{env, fs};
+use tree_hash::TreeHash;
+
+/// Chain ID of the in-process destination chain.
+const DEST_CHAIN_ID: u64 = 31337;
+/// The first development account of anvil and hardhat.
+const RELAYER_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+/// A sync committee period of the Deneb fork of mainnet, for the synthetic chain.
+const PERIOD: u64 = 1300;
+
+/// What the fixtures were recorded for.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct FixtureConfig {
+    source_chain_id: u64,
+    /// Slot of the checkpoint the contract is deployed from.
+    genesis_slot: u64,
+    #[serde(default)]
+    storage_contract: Option<Address>,
+    #[serde(default)]
+    storage_slots: Vec<B256>,
+}
+
+impl FixtureConfig {
+    fn from_env() -> Result<Self> {
+        Ok(Self {
+            source_chain_id: env::var("SOURCE_CHAIN_ID")?.parse()?,
+            genesis_slot: env::var("E2E_GENESIS_SLOT")
+                .context("E2E_GENESIS_SLOT not set")?
+                .parse()?,
+            storage_contract: env::var("STORAGE_CONTRACT_ADDRESS")
+                .ok()
+                .map(|contract| contract.parse())
+                .transpose()?,
+            storage_slots: env::var("STORAGE_SLOTS")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|slot| !slot.is_empty())
+                .map(|slot| slot.trim().parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Write the fixtures of a synthetic chain to `dir`, whose head is a period past the genesis
+/// checkpoint, with a storage slot to prove.
+fn synthetic_source(dir: &Path) -> Result<(FixtureConfig, FixtureServer)> {
+    let storage_contract = Address::repeat_byte(0x42);
+    let storage_slot = B256::with_last_byte(1);
+    let config = FixtureConfig {
+        source_chain_id: 1,
+        genesis_slot: PERIOD * SLOTS_PER_PERIOD + 2 * SLOTS_PER_EPOCH,
+        storage_contract: Some(storage_contract),
+        storage_slots: vec![storage_slot],
+    };
+
+    let mut chain = SyntheticChain::new(ChainSpec::deneb(Network::Mainnet), b"e2e");
+    chain
+        .execution_state
+        .set_storage(storage_contract, storage_slot, U256::from(7));
+    let head_slot = (PERIOD + 1) * SLOTS_PER_PERIOD + 100 * SLOTS_PER_EPOCH;
+    chain.write_fixtures(&dir.join("beacon"), config.genesis_slot, head_slot)?;
+
+    let server = FixtureServer::synthetic(dir, chain.execution_state.clone());
+    Ok((config, server))
+}
+
+/// Serve the source chain responses recorded in `dir`, recording them first if asked to.
+fn recorded_source(dir: &Path) -> Result<(FixtureConfig, FixtureServer)> {
+    if env::var("E2E_RECORD").is_ok() {
+        let config = FixtureConfig::from_env()?;
+        let upstream = Upstream {
+            consensus_rpc: env::var("SOURCE_CONSENSUS_RPC_URL")?,
+            execution_rpc: env::var("SOURCE_EXECUTION_RPC_URL")?,
+        };
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join("fixture.json"),
+            serde_json::to_vec_pretty(&config)?,
+        )?;
+        return Ok((config, FixtureServer::record(dir, upstream)));
+    }
+
+    let config: FixtureConfig = serde_json::from_slice(
+        &fs::read(dir.join("fixture.json")).context("No recorded fixtures")?,
+    )?;
+    Ok((config, FixtureServer::replay(dir)))
+}
+
+/// Initialization parameters of a contract whose head is the checkpoint at `slot`, as the
+/// `genesis` binary computes them.
+async fn init_params(
+    slot: u64,
+    chain_id: u64,
+    updater: Address,
+) -> Result<(R0VMHelios::InitParams, u64)> {
+    let client = get_client(get_checkpoint(slot).await?).await?;
+    let header = client.store.finalized_header.clone();
+    let head = header.beacon().slot;
+    let execution = header
+        .execution()
+        .map_err(|_| anyhow::anyhow!("Checkpoint has no execution payload"))?;
+
+    let params = R0VMHelios::InitParams {
+        executionStateRoot: *execution.state_root(),
+        genesisTime: U256::from(client.config.chain.genesis_time),
+        genesisValidatorsRoot: client.config.chain.genesis_root,
+        head: U256::from(head),
+        header: header.beacon().tree_hash_root(),
+        heliosImageId: B256::from_slice(Digest::from(R0VM_HELIOS_GUEST_ID).as_bytes()),
+        secondsPerSlot: U256::from(SECONDS_PER_SLOT),
+        slotsPerEpoch: U256::from(SLOTS_PER_EPOCH),
+        slotsPerPeriod: U256::from(SLOTS_PER_PERIOD),
+        sourceChainId: U256::from(chain_id),
+        syncCommitteeHash: client.store.current_sync_committee.tree_hash_root(),
+        updaters: vec![updater],
+        verifier: Address::ZERO,
+    };
+    let head_timestamp = client.config.chain.genesis_time + head * SECONDS_PER_SLOT;
+    Ok((params, head_timestamp))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn operator_relays_update() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Serve the source chain.
+    let synthetic_dir = tempfile::tempdir()?;
+    let (config, server) = match env::var("E2E_FIXTURES") {
+        Ok(dir) => recorded_source(Path::new(&dir))?,
+        Err(_) => synthetic_source(synthetic_dir.path())?,
+    };
+    let beacon_url = server.spawn().await?;
+    env::set_var("SOURCE_CHAIN_ID", config.source_chain_id.to_string());
+    env::set_var("SOURCE_CONSENSUS_RPC_URL", beacon_url.as_str());
+    env::set_var(
+        "SOURCE_EXECUTION_RPC_URL",
+        execution_url(&beacon_url).as_str(),
+    );
+
+    // Deploy the contract at the genesis checkpoint, on a chain whose clock starts an hour later.
+    let relayer: PrivateKeySigner = RELAYER_KEY.parse()?;
+    let (params, head_timestamp) = init_params(
+        config.genesis_slot,
+        config.source_chain_id,
+        relayer.address(),
+    )
+    .await?;
+    let node = EvmNode::new(DEST_CHAIN_ID, head_timestamp + 3600);
+    node.fund(relayer.address(), U256::from(10).pow(U256::from(21)));
+    let contract_address = deploy_light_client(&node, relayer.address(), params)?;
+    let dest_url = node.spawn(Duration::from_secs(1)).await?;
+
+    // Run a single round of the operator.
+    let state_dir = tempfile::tempdir()?;
+    let mut operator = Command::new(env!("CARGO_BIN_EXE_operator"));
+    operator
+        .arg("once")
+        .current_dir(state_dir.path())
+        .env_clear()
+        .env(
+            "RUST_LOG",
+            env::var("RUST_LOG").unwrap_or("info".to_string()),
+        )
+        .env("RISC0_DEV_MODE", "1")
+        .env("PROVER", "dev-mode")
+        .env("STATE_DIR", state_dir.path().join("state"))
+        .env("SOURCE_CHAIN_ID", config.source_chain_id.to_string())
+        .env("SOURCE_CONSENSUS_RPC_URL", beacon_url.as_str())
+        .env(
+            "SOURCE_EXECUTION_RPC_URL",
+            execution_url(&beacon_url).as_str(),
+        )
+        .env("DEST_RPC_URL", dest_url.as_str())
+        .env("CONTRACT_ADDRESS", contract_address.to_string())
+        .env("PRIVATE_KEY", RELAYER_KEY);
+    if let Some(storage_contract) = config.storage_contract {
+        let storage_slots: Vec<String> = config.storage_slots.iter().map(B256::to_string).collect();
+        operator
+            .env("STORAGE_CONTRACT_ADDRESS", storage_contract.to_string())
+            .env("STORAGE_SLOTS", storage_slots.join(","));
+    }
+    let status = tokio::task::spawn_blocking(move || operator.status()).await??;
+    assert!(status.success(), "operator exited with {}", status);
+
+    // The contract holds the finalized header of the latest finality update, its execution state
+    // root and the storage values at its execution block.
+    let rpc = <HttpRpc as ConsensusRpc<MainnetConsensusSpec>>::new(beacon_url.as_str());
+    let finality_update = rpc
+        .get_finality_update()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch finality update: {}", e))?;
+    let finalized = finality_update.finalized_header();
+    let expected_head = finalized.beacon().slot;
+    let execution = finalized
+        .execution()
+        .map_err(|_| anyhow::anyhow!("Finality update has no execution payload"))?;
+
+    let client = HeliosClient::connect(dest_url.as_str(), contract_address).await?;
+    let state = client.latest_verified_state().await?;
+    assert_eq!(state.head, expected_head);
+    assert_eq!(state.header, finalized.beacon().tree_hash_root());
+    assert_eq!(state.execution_state_root, *execution.state_root());
+
+    // The head is as old as its slot, whatever the destination chain's clock says.
+    let age = client.age(state.head);
+    let fresh = client
+        .fresh_verified_state(age + Duration::from_secs(3600))
+        .await?;
+    assert_eq!(fresh.head, expected_head);
+    let stale = client.fresh_verified_state(age / 2).await.unwrap_err();
+    let stale = stale
+        .downcast_ref::<Stale>()
+        .context("Expected a stale head")?;
+    assert_eq!(stale.head, expected_head);
+
+    // Nothing updates the contract anymore.
+    let reached = client
+        .wait_for_head(expected_head, Duration::from_secs(10))
+        .await?;
+    assert_eq!(reached.head, expected_head);
+    assert!(client
+        .wait_for_head(expected_head + 1, Duration::from_secs(1))
+        .await
+        .is_err());
+
+    let Some(storage_contract) = config.storage_contract else {
+        return Ok(());
+    };
+    let source = ProviderBuilder::new().on_http(execution_url(&beacon_url));
+    let proof = source
+        .get_proof(storage_contract, config.storage_slots.clone())
+        .block_id((*execution.block_number()).into())
+        .await?;
+    for storage_proof in proof.storage_proof {
+        let key = storage_proof.key.as_b256();
+        let value = client.storage_at(state.head, storage_contract, key).await?;
+        assert_eq!(
+            value,
+            B256::from(storage_proof.value),
+            "storage slot {}",
+            key
+        );
+        let contract_key = client
+            .contract()
+            .computeStorageKey(U256::from(state.head), storage_contract, key)
+            .call()
+            .await?
+            ._0;
+        assert_eq!(storage_key(state.head, storage_contract, key), contract_key);
+    }
+    Ok(())
+}
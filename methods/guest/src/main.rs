@@ -17,11 +17,14 @@ risc0_zkvm::guest::entry!(main);
 
 /// Program flow:
 /// 1. Apply sync committee updates, if any
-/// 2. Apply finality update
+/// 2. Apply finality update, if any
 /// 3. Verify execution state root proof
-/// 4. Verify storage slot proofs
+/// 4. Verify storage slot proofs, if any
 /// 5. Asset all updates are valid
 /// 6. Commit new state root, header, and sync committee for usage in the on-chain contract
+///
+/// Intermediate proofs of a catch-up sequence carry neither a finality update nor storage slots,
+/// and advance the head to the finalized header of the last sync committee update.
 pub fn main() {
     let encoded_inputs = env::read_frame();
 
@@ -56,21 +59,23 @@ pub fn main() {
         apply_update(&mut store, update);
     }
 
-    // 2. Apply finality update
-    let finality_update_is_valid = verify_finality_update(
-        &finality_update,
-        expected_current_slot,
-        &store,
-        genesis_root,
-        &forks,
-    )
-    .is_ok();
-    if !finality_update_is_valid {
-        panic!("Finality update is invalid!");
-    }
-    println!("Finality update is valid.");
+    // 2. Apply finality update, if any
+    if let Some(finality_update) = finality_update {
+        let finality_update_is_valid = verify_finality_update(
+            &finality_update,
+            expected_current_slot,
+            &store,
+            genesis_root,
+            &forks,
+        )
+        .is_ok();
+        if !finality_update_is_valid {
+            panic!("Finality update is invalid!");
+        }
+        println!("Finality update is valid.");
 
-    apply_finality_update(&mut store, &finality_update);
+        apply_finality_update(&mut store, &finality_update);
+    }
 
     // 3. Verify storage slot proofs
     let execution_state_root = *store
@@ -79,7 +84,12 @@ pub fn main() {
         .expect("Execution payload doesn't exist.")
         .state_root();
 
-    let verified_slots = verify_storage_slot_proofs(execution_state_root, contract_storage_slots);
+    let verified_slots = match contract_storage_slots {
+        Some(contract_storage) => {
+            verify_storage_slot_proofs(execution_state_root, contract_storage)
+        }
+        None => Vec::new(),
+    };
 
     // 4. Commit new state root, header, and sync committee for usage in the on-chain contract
     let header: B256 = store.finalized_header.beacon().tree_hash_root();
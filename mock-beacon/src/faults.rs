@@ -0,0 +1,281 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An endpoint of the mock beacon node.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    Bootstrap,
+    Updates,
+    FinalityUpdate,
+    OptimisticUpdate,
+    Blocks,
+    Events,
+}
+
+impl Endpoint {
+    pub const ALL: [Endpoint; 6] = [
+        Endpoint::Bootstrap,
+        Endpoint::Updates,
+        Endpoint::FinalityUpdate,
+        Endpoint::OptimisticUpdate,
+        Endpoint::Blocks,
+        Endpoint::Events,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Endpoint::Bootstrap => "bootstrap",
+            Endpoint::Updates => "updates",
+            Endpoint::FinalityUpdate => "finality_update",
+            Endpoint::OptimisticUpdate => "optimistic_update",
+            Endpoint::Blocks => "blocks",
+            Endpoint::Events => "events",
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Endpoint::ALL
+            .into_iter()
+            .find(|endpoint| endpoint.name() == s)
+            .with_context(|| format!("Unknown endpoint {}", s))
+    }
+}
+
+/// A fault to inject into responses.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// Respond after `ms` milliseconds.
+    Delay { ms: u64 },
+    /// Respond with the HTTP status `code` and no fixture.
+    Status { code: u16 },
+    /// Cut the response body off halfway, or drop an event stream after its first event.
+    Truncate,
+    /// Rewrite every `state_root` in the response, so it describes a different chain than the
+    /// rest of the fixtures.
+    Fork,
+}
+
+impl FromStr for Fault {
+    type Err = anyhow::Error;
+
+    /// Parse `delay=<ms>`, `status=<code>`, `truncate` or `fork`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let value = || -> Result<&str> {
+            value.with_context(|| format!("Fault {} needs a value, as in {}=<value>", name, name))
+        };
+        match name {
+            "delay" => Ok(Fault::Delay {
+                ms: value()?.parse().context("Invalid delay")?,
+            }),
+            "status" => Ok(Fault::Status {
+                code: value()?.parse().context("Invalid status code")?,
+            }),
+            "truncate" => Ok(Fault::Truncate),
+            "fork" => Ok(Fault::Fork),
+            _ => anyhow::bail!("Unknown fault {}", name),
+        }
+    }
+}
+
+/// Inject `fault` into the responses of `endpoint`, or of every endpoint if `None`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRule {
+    pub endpoint: Option<Endpoint>,
+    pub fault: Fault,
+    /// Number of responses to inject the fault into, all of them if `None`.
+    pub times: Option<usize>,
+}
+
+impl FromStr for FaultRule {
+    type Err = anyhow::Error;
+
+    /// Parse `<endpoint>:<fault>[:<times>]`, where the endpoint `*` matches every endpoint.
+    ///
+    /// For example `finality_update:status=503:3` fails the next three finality update requests.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+        let endpoint = match parts.next() {
+            Some("*") => None,
+            Some(endpoint) => Some(endpoint.parse()?),
+            None => anyhow::bail!("Empty fault rule"),
+        };
+        let fault = parts
+            .next()
+            .with_context(|| format!("Fault rule {} has no fault", s))?
+            .parse()?;
+        let times = parts
+            .next()
+            .map(|times| times.parse().context("Invalid number of times"))
+            .transpose()?;
+        if parts.next().is_some() {
+            anyhow::bail!("Invalid fault rule {}", s);
+        }
+        Ok(Self {
+            endpoint,
+            fault,
+            times,
+        })
+    }
+}
+
+/// The faults to inject into a response.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Faults {
+    pub delay: Duration,
+    pub status: Option<u16>,
+    pub truncate: bool,
+    pub fork: bool,
+}
+
+/// Take the faults `rules` inject into the next response of `endpoint`, counting down the rules
+/// that apply a limited number of times and dropping the exhausted ones.
+pub(crate) fn take_faults(rules: &mut Vec<FaultRule>, endpoint: Endpoint) -> Faults {
+    let mut faults = Faults::default();
+    for rule in rules.iter_mut() {
+        if rule.endpoint.is_some_and(|e| e != endpoint) {
+            continue;
+        }
+        match rule.fault {
+            Fault::Delay { ms } => faults.delay += Duration::from_millis(ms),
+            Fault::Status { code } => faults.status = faults.status.or(Some(code)),
+            Fault::Truncate => faults.truncate = true,
+            Fault::Fork => faults.fork = true,
+        }
+        if let Some(times) = &mut rule.times {
+            *times = times.saturating_sub(1);
+        }
+    }
+    rules.retain(|rule| rule.times != Some(0));
+    faults
+}
+
+/// Rewrite every `state_root` in `value` by flipping the bits of its last byte.
+pub(crate) fn fork(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::String(root) if key == "state_root" => {
+                        if let Some(forked) = flip_last_byte(root) {
+                            *root = forked;
+                        }
+                    }
+                    value => fork(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(fork),
+        _ => {}
+    }
+}
+
+fn flip_last_byte(hex: &str) -> Option<String> {
+    let split = hex.len().checked_sub(2)?;
+    let byte = u8::from_str_radix(&hex[split..], 16).ok()?;
+    Some(format!("{}{:02x}", &hex[..split], !byte))
+}
+
+/// The first half of `body`.
+pub(crate) fn truncate(body: &str) -> &str {
+    let mut end = body.len() / 2;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    &body[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_fault_rules() {
+        assert_eq!(
+            "finality_update:status=503:3".parse::<FaultRule>().unwrap(),
+            FaultRule {
+                endpoint: Some(Endpoint::FinalityUpdate),
+                fault: Fault::Status { code: 503 },
+                times: Some(3),
+            }
+        );
+        assert_eq!(
+            "*:delay=500".parse::<FaultRule>().unwrap(),
+            FaultRule {
+                endpoint: None,
+                fault: Fault::Delay { ms: 500 },
+                times: None,
+            }
+        );
+        assert!("blocks:status".parse::<FaultRule>().is_err());
+        assert!("headers:fork".parse::<FaultRule>().is_err());
+        assert!("blocks:fork:1:2".parse::<FaultRule>().is_err());
+    }
+
+    #[test]
+    fn counts_down_rules() {
+        let mut rules = vec![
+            "updates:status=500:1".parse().unwrap(),
+            "*:delay=10".parse().unwrap(),
+        ];
+
+        let faults = take_faults(&mut rules, Endpoint::Blocks);
+        assert_eq!(faults.status, None);
+        assert_eq!(faults.delay, Duration::from_millis(10));
+
+        let faults = take_faults(&mut rules, Endpoint::Updates);
+        assert_eq!(faults.status, Some(500));
+        assert_eq!(rules.len(), 1);
+
+        let faults = take_faults(&mut rules, Endpoint::Updates);
+        assert_eq!(faults.status, None);
+    }
+
+    #[test]
+    fn forks_state_roots() {
+        let mut value = json!({
+            "data": {
+                "attested_header": { "beacon": { "slot": "1", "state_root": "0x00ff" } },
+                "finalized_header": { "beacon": { "state_root": "0x0a0b" } },
+                "branch": ["0x00ff"],
+            }
+        });
+        fork(&mut value);
+        assert_eq!(
+            value["data"]["attested_header"]["beacon"]["state_root"],
+            "0x0000"
+        );
+        assert_eq!(
+            value["data"]["finalized_header"]["beacon"]["state_root"],
+            "0x0af4"
+        );
+        assert_eq!(value["data"]["attested_header"]["beacon"]["slot"], "1");
+        assert_eq!(value["data"]["branch"][0], "0x00ff");
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        assert_eq!(truncate("{\"a\":1}"), "{\"a");
+        assert_eq!(truncate("ééé"), "é");
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use log::info;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Slots of a sync committee period on mainnet and its testnets.
+const SLOTS_PER_PERIOD: u64 = 8192;
+
+/// Light client data stored in a fixture directory:
+///
+/// ```text
+/// bootstrap/<block root>.json   bootstrap response for a checkpoint
+/// updates/<period>.json         one element of the updates response, by sync committee period
+/// finality_update.json
+/// optimistic_update.json
+/// blocks/<block id>.json        block response, by slot, block root or named id like `finalized`
+/// ```
+///
+/// With an upstream beacon node, requests are forwarded to it and its responses stored, so
+/// running a client against a recording server captures exactly the fixtures it needs.
+pub(crate) struct Fixtures {
+    pub dir: PathBuf,
+    pub upstream: Option<String>,
+    pub client: reqwest::Client,
+}
+
+impl Fixtures {
+    pub async fn bootstrap(&self, block_root: &str) -> Result<Option<Value>> {
+        let file = format!("bootstrap/{}.json", block_root);
+        let path = format!("/eth/v1/beacon/light_client/bootstrap/{}", block_root);
+        self.fetch(&file, &path).await
+    }
+
+    pub async fn updates(&self, start_period: u64, count: u64) -> Result<Vec<Value>> {
+        let Some(upstream) = &self.upstream else {
+            // Serve the stored periods from `start_period` on, up to the first one missing.
+            let mut updates = Vec::new();
+            for period in start_period..start_period + count {
+                match self.read(&format!("updates/{}.json", period))? {
+                    Some(update) => updates.push(update),
+                    None => break,
+                }
+            }
+            return Ok(updates);
+        };
+
+        let path = format!(
+            "/eth/v1/beacon/light_client/updates?start_period={}&count={}",
+            start_period, count
+        );
+        let Some(Value::Array(updates)) = self.fetch_upstream(upstream, &path).await? else {
+            anyhow::bail!("Upstream updates response is not a list");
+        };
+        for update in &updates {
+            let slot: u64 = update["data"]["attested_header"]["beacon"]["slot"]
+                .as_str()
+                .context("Update has no attested slot")?
+                .parse()?;
+            self.write(&format!("updates/{}.json", slot / SLOTS_PER_PERIOD), update)?;
+        }
+        Ok(updates)
+    }
+
+    pub async fn finality_update(&self) -> Result<Option<Value>> {
+        self.fetch(
+            "finality_update.json",
+            "/eth/v1/beacon/light_client/finality_update",
+        )
+        .await
+    }
+
+    pub async fn optimistic_update(&self) -> Result<Option<Value>> {
+        self.fetch(
+            "optimistic_update.json",
+            "/eth/v1/beacon/light_client/optimistic_update",
+        )
+        .await
+    }
+
+    pub async fn block(&self, block_id: &str) -> Result<Option<Value>> {
+        let file = format!("blocks/{}.json", block_id);
+        let path = format!("/eth/v2/beacon/blocks/{}", block_id);
+        self.fetch(&file, &path).await
+    }
+
+    /// The fixture `file`, recorded from the upstream `path` when recording.
+    async fn fetch(&self, file: &str, path: &str) -> Result<Option<Value>> {
+        let Some(upstream) = &self.upstream else {
+            return self.read(file);
+        };
+        let value = self.fetch_upstream(upstream, path).await?;
+        if let Some(value) = &value {
+            self.write(file, value)?;
+        }
+        Ok(value)
+    }
+
+    async fn fetch_upstream(&self, upstream: &str, path: &str) -> Result<Option<Value>> {
+        let url = format!("{}{}", upstream.trim_end_matches('/'), path);
+        let response = self.client.get(&url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body = response.error_for_status()?.text().await?;
+        info!("Recorded {}", path);
+        Ok(Some(serde_json::from_str(&body).with_context(|| {
+            format!("Invalid response to {}", path)
+        })?))
+    }
+
+    fn read(&self, file: &str) -> Result<Option<Value>> {
+        let path = self.dir.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let value = serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("Invalid fixture {}", path.display()))?;
+        Ok(Some(value))
+    }
+
+    fn write(&self, file: &str, value: &Value) -> Result<()> {
+        write_json(&self.dir.join(file), value)
+    }
+}
+
+/// Write `value` to `path`, creating its directory.
+pub fn write_json(path: &Path, value: &Value) -> Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
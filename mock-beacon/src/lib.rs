@@ -0,0 +1,405 @@
+//! A mock beacon node serving the light client API from a fixture directory, with fault
+//! injection, for testing the host and the operator without network access.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use r0vm_helios_mock_beacon::{MockBeacon, FaultRule};
+//!
+//! let beacon = MockBeacon::replay("tests/fixtures/beacon");
+//! beacon.inject("finality_update:status=503:1".parse::<FaultRule>()?);
+//! let url = beacon.spawn().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{Stream, StreamExt};
+use log::warn;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+mod faults;
+mod fixtures;
+
+pub use faults::{Endpoint, Fault, FaultRule};
+pub use fixtures::write_json;
+use fixtures::Fixtures;
+
+/// An event of the `/eth/v1/events` stream.
+#[derive(Debug, Clone)]
+struct BeaconEvent {
+    topic: String,
+    data: String,
+}
+
+struct Beacon {
+    fixtures: Fixtures,
+    faults: Mutex<Vec<FaultRule>>,
+    requests: Mutex<HashMap<Endpoint, usize>>,
+    /// Finality update served instead of the fixture, once set.
+    finality_update: RwLock<Option<Value>>,
+    events: broadcast::Sender<BeaconEvent>,
+}
+
+/// A mock beacon node, see the crate documentation.
+///
+/// Clones share the same server state, so faults can be injected into a running server.
+#[derive(Clone)]
+pub struct MockBeacon {
+    beacon: Arc<Beacon>,
+}
+
+impl MockBeacon {
+    /// Serve the fixtures in `dir`.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self::new(dir.into(), None)
+    }
+
+    /// Forward requests to the beacon node at `upstream`, and store its responses in `dir`.
+    pub fn record(dir: impl Into<PathBuf>, upstream: impl Into<String>) -> Self {
+        Self::new(dir.into(), Some(upstream.into()))
+    }
+
+    fn new(dir: PathBuf, upstream: Option<String>) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            beacon: Arc::new(Beacon {
+                fixtures: Fixtures {
+                    dir,
+                    upstream,
+                    client: reqwest::Client::new(),
+                },
+                faults: Mutex::new(Vec::new()),
+                requests: Mutex::new(HashMap::new()),
+                finality_update: RwLock::new(None),
+                events,
+            }),
+        }
+    }
+
+    /// Inject a fault into the following responses.
+    pub fn inject(&self, rule: FaultRule) {
+        self.beacon.faults.lock().unwrap().push(rule);
+    }
+
+    /// Remove all injected faults.
+    pub fn clear_faults(&self) {
+        self.beacon.faults.lock().unwrap().clear();
+    }
+
+    /// Number of requests served by `endpoint` so far, including failed ones.
+    pub fn requests(&self, endpoint: Endpoint) -> usize {
+        self.beacon
+            .requests
+            .lock()
+            .unwrap()
+            .get(&endpoint)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Serve `update` as the finality update from now on, and announce it to event subscribers
+    /// as a `light_client_finality_update` event.
+    pub fn set_finality_update(&self, update: Value) {
+        self.emit("light_client_finality_update", &update);
+        *self.beacon.finality_update.write().unwrap() = Some(update);
+    }
+
+    /// Send an event with `topic` and `data` to the subscribers of `topic`.
+    pub fn emit(&self, topic: &str, data: &Value) {
+        // Fails only if nobody is subscribed.
+        let _ = self.beacon.events.send(BeaconEvent {
+            topic: topic.to_string(),
+            data: data.to_string(),
+        });
+    }
+
+    /// The routes of the beacon API and of the `/mock` admin API.
+    ///
+    /// The admin API takes a list of fault rules at `POST /mock/faults`, clears them at
+    /// `DELETE /mock/faults`, and takes a new finality update at `POST /mock/finality_update`.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(
+                "/eth/v1/beacon/light_client/bootstrap/:block_root",
+                get(bootstrap),
+            )
+            .route("/eth/v1/beacon/light_client/updates", get(updates))
+            .route(
+                "/eth/v1/beacon/light_client/finality_update",
+                get(finality_update),
+            )
+            .route(
+                "/eth/v1/beacon/light_client/optimistic_update",
+                get(optimistic_update),
+            )
+            .route("/eth/v2/beacon/blocks/:block_id", get(block))
+            .route("/eth/v1/events", get(events))
+            .route("/mock/faults", post(add_faults).delete(clear_faults))
+            .route("/mock/finality_update", post(set_finality_update))
+            .with_state(self.clone())
+    }
+
+    /// Serve on `listener` until the server fails.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    /// Serve on a local port in the background, and return the server's URL.
+    pub async fn spawn(&self) -> Result<Url> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?).parse()?;
+        let beacon = self.clone();
+        tokio::spawn(async move { beacon.serve(listener).await });
+        Ok(url)
+    }
+
+    /// Count a request to `endpoint`, and apply the injected delay. Returns the response to fail
+    /// with, if an error is injected.
+    async fn begin(&self, endpoint: Endpoint) -> (faults::Faults, Option<Response>) {
+        *self
+            .beacon
+            .requests
+            .lock()
+            .unwrap()
+            .entry(endpoint)
+            .or_default() += 1;
+        let injected = faults::take_faults(&mut self.beacon.faults.lock().unwrap(), endpoint);
+        tokio::time::sleep(injected.delay).await;
+
+        let failure = injected.status.map(|code| {
+            let status = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            error(status, format!("Injected {} error", code))
+        });
+        (injected, failure)
+    }
+
+    /// Respond to a request to `endpoint` with `value`, with the injected faults.
+    async fn respond(
+        &self,
+        endpoint: Endpoint,
+        value: impl Future<Output = Result<Option<Value>>>,
+    ) -> Response {
+        let (injected, failure) = self.begin(endpoint).await;
+        if let Some(failure) = failure {
+            return failure;
+        }
+
+        let mut value = match value.await {
+            Ok(Some(value)) => value,
+            Ok(None) => return error(StatusCode::NOT_FOUND, format!("No {} fixture", endpoint)),
+            Err(e) => {
+                warn!("Failed to serve {}: {:#}", endpoint, e);
+                return error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e));
+            }
+        };
+        if injected.fork {
+            faults::fork(&mut value);
+        }
+        let mut body = value.to_string();
+        if injected.truncate {
+            body = faults::truncate(&body).to_string();
+        }
+        ([(CONTENT_TYPE, "application/json")], body).into_response()
+    }
+}
+
+/// A beacon API error response.
+fn error(status: StatusCode, message: String) -> Response {
+    (
+        status,
+        Json(json!({ "code": status.as_u16(), "message": message })),
+    )
+        .into_response()
+}
+
+/// Whether `id` is a block root, slot or named block ID, and safe to use in a file name.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+async fn bootstrap(State(beacon): State<MockBeacon>, Path(block_root): Path<String>) -> Response {
+    if !valid_id(&block_root) {
+        return error(StatusCode::BAD_REQUEST, "Invalid block root".to_string());
+    }
+    let fixtures = &beacon.beacon.fixtures;
+    beacon
+        .respond(Endpoint::Bootstrap, fixtures.bootstrap(&block_root))
+        .await
+}
+
+#[derive(Deserialize)]
+struct UpdatesQuery {
+    start_period: u64,
+    count: u64,
+}
+
+async fn updates(State(beacon): State<MockBeacon>, Query(query): Query<UpdatesQuery>) -> Response {
+    let fixtures = &beacon.beacon.fixtures;
+    let updates = async {
+        let updates = fixtures.updates(query.start_period, query.count).await?;
+        Ok(Some(Value::Array(updates)))
+    };
+    beacon.respond(Endpoint::Updates, updates).await
+}
+
+async fn finality_update(State(beacon): State<MockBeacon>) -> Response {
+    let update = async {
+        let update = beacon.beacon.finality_update.read().unwrap().clone();
+        match update {
+            Some(update) => Ok(Some(update)),
+            None => beacon.beacon.fixtures.finality_update().await,
+        }
+    };
+    beacon.respond(Endpoint::FinalityUpdate, update).await
+}
+
+async fn optimistic_update(State(beacon): State<MockBeacon>) -> Response {
+    let fixtures = &beacon.beacon.fixtures;
+    beacon
+        .respond(Endpoint::OptimisticUpdate, fixtures.optimistic_update())
+        .await
+}
+
+async fn block(State(beacon): State<MockBeacon>, Path(block_id): Path<String>) -> Response {
+    if !valid_id(&block_id) {
+        return error(StatusCode::BAD_REQUEST, "Invalid block ID".to_string());
+    }
+    let fixtures = &beacon.beacon.fixtures;
+    beacon
+        .respond(Endpoint::Blocks, fixtures.block(&block_id))
+        .await
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    topics: String,
+}
+
+async fn events(State(beacon): State<MockBeacon>, Query(query): Query<EventsQuery>) -> Response {
+    let (injected, failure) = beacon.begin(Endpoint::Events).await;
+    if let Some(failure) = failure {
+        return failure;
+    }
+
+    let topics: Vec<String> = query.topics.split(',').map(str::to_string).collect();
+    let receiver = beacon.beacon.events.subscribe();
+    // A truncated stream drops after its first event.
+    let limit = if injected.truncate { 1 } else { usize::MAX };
+    Sse::new(event_stream(receiver, topics).take(limit))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// The events of `topics` sent to `receiver`, skipping any missed by a slow subscriber.
+fn event_stream(
+    receiver: broadcast::Receiver<BeaconEvent>,
+    topics: Vec<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    futures::stream::unfold(receiver, move |mut receiver| {
+        let topics = topics.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if topics.contains(&event.topic) => {
+                        let event = Event::default().event(event.topic).data(event.data);
+                        return Some((Ok(event), receiver));
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    })
+}
+
+async fn add_faults(State(beacon): State<MockBeacon>, Json(rules): Json<Vec<FaultRule>>) {
+    for rule in rules {
+        beacon.inject(rule);
+    }
+}
+
+async fn clear_faults(State(beacon): State<MockBeacon>) {
+    beacon.clear_faults();
+}
+
+async fn set_finality_update(State(beacon): State<MockBeacon>, Json(update): Json<Value>) {
+    beacon.set_finality_update(update);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(url: Url) -> (StatusCode, String) {
+        let response = reqwest::get(url).await.unwrap();
+        (response.status(), response.text().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn serves_fixtures_with_faults() {
+        let dir = tempfile::tempdir().unwrap();
+        let update = json!({ "version": "deneb", "data": { "state_root": "0x01" } });
+        write_json(&dir.path().join("finality_update.json"), &update).unwrap();
+        write_json(&dir.path().join("updates/3.json"), &update).unwrap();
+        write_json(&dir.path().join("updates/4.json"), &update).unwrap();
+
+        let beacon = MockBeacon::replay(dir.path());
+        let url = beacon.spawn().await.unwrap();
+        let finality_url = url
+            .join("eth/v1/beacon/light_client/finality_update")
+            .unwrap();
+
+        let (status, body) = get(finality_url.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), update);
+
+        let (_, body) = get(url
+            .join("eth/v1/beacon/light_client/updates?start_period=3&count=5")
+            .unwrap())
+        .await;
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!([update, update])
+        );
+
+        let (status, _) = get(url.join("eth/v2/beacon/blocks/head").unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        beacon.inject("finality_update:status=503:1".parse().unwrap());
+        let (status, _) = get(finality_url.clone()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        beacon.inject("finality_update:fork:1".parse().unwrap());
+        let (_, body) = get(finality_url.clone()).await;
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap()["data"]["state_root"],
+            "0xfe"
+        );
+
+        beacon.inject("*:truncate:1".parse().unwrap());
+        let (_, body) = get(finality_url.clone()).await;
+        assert!(serde_json::from_str::<Value>(&body).is_err());
+
+        assert_eq!(beacon.requests(Endpoint::FinalityUpdate), 4);
+        assert_eq!(beacon.requests(Endpoint::Updates), 1);
+    }
+}
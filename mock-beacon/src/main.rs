@@ -0,0 +1,49 @@
+use anyhow::Result;
+use clap::Parser;
+use log::info;
+use r0vm_helios_mock_beacon::{FaultRule, MockBeacon};
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Serve the beacon light client API from a fixture directory.")]
+pub struct MockBeaconArgs {
+    /// Fixture directory to serve, or to record into with `--upstream`.
+    #[arg(long)]
+    pub fixtures: PathBuf,
+    #[arg(long, default_value = "127.0.0.1:5052")]
+    pub addr: SocketAddr,
+    /// Beacon node to forward requests to, recording its responses as fixtures.
+    #[arg(long)]
+    pub upstream: Option<String>,
+    /// Fault to inject, as `<endpoint>:<fault>[:<times>]`. For example `*:delay=500` delays every
+    /// response and `finality_update:status=503:3` fails the next three finality update requests.
+    #[arg(long = "fault")]
+    pub faults: Vec<FaultRule>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", "info");
+    }
+    env_logger::init();
+    let args = MockBeaconArgs::parse();
+
+    let beacon = match args.upstream {
+        Some(upstream) => MockBeacon::record(&args.fixtures, upstream),
+        None => MockBeacon::replay(&args.fixtures),
+    };
+    for rule in args.faults {
+        beacon.inject(rule);
+    }
+
+    let listener = tokio::net::TcpListener::bind(args.addr).await?;
+    info!(
+        "Serving {} on http://{}",
+        args.fixtures.display(),
+        args.addr
+    );
+    beacon.serve(listener).await
+}
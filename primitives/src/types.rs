@@ -1,11 +1,13 @@
 use alloy_primitives::{Address, Bytes, B256, U256};
-use alloy_sol_types::sol;
 use alloy_trie::TrieAccount;
 use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
 use helios_consensus_core::types::Forks;
 use helios_consensus_core::types::{FinalityUpdate, LightClientStore, Update};
 use serde::{Deserialize, Serialize};
 
+/// The journal committed by the guest and decoded by the contract, generated from its ABI.
+pub use r0vm_helios_bindings::R0VMHelios::{ProofOutputs, StorageSlot as VerifiedStorageSlot};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StorageSlot {
     pub key: B256,             // raw 32 byte storage slot key e.g. for slot 0: 0x000...00
@@ -24,12 +26,12 @@ pub struct ContractStorage {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProofInputs {
     pub sync_committee_updates: Vec<Update<MainnetConsensusSpec>>,
-    pub finality_update: FinalityUpdate<MainnetConsensusSpec>,
+    pub finality_update: Option<FinalityUpdate<MainnetConsensusSpec>>, // `None` for intermediate catch-up proofs
     pub expected_current_slot: u64,
     pub store: LightClientStore<MainnetConsensusSpec>,
     pub genesis_root: B256,
     pub forks: Forks,
-    pub contract_storage_slots: ContractStorage,
+    pub contract_storage_slots: Option<ContractStorage>, // `None` for intermediate catch-up proofs
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -40,23 +42,3 @@ pub struct ExecutionStateProof {
     pub execution_state_branch: Vec<B256>,
     pub gindex: String,
 }
-
-sol! {
-    struct VerifiedStorageSlot {
-        bytes32 key;
-        bytes32 value;
-        address contractAddress;
-    }
-
-    struct ProofOutputs {
-        bytes32 executionStateRoot;
-        bytes32 newHeader;
-        bytes32 nextSyncCommitteeHash;
-        uint256 newHead;
-        bytes32 prevHeader;
-        uint256 prevHead;
-        bytes32 syncCommitteeHash;
-        bytes32 startSyncCommitteeHash;
-        VerifiedStorageSlot[] slots;
-    }
-}
@@ -0,0 +1,63 @@
+//! BLS signatures of the beacon chain, with deterministic keys.
+
+use alloy_primitives::{FixedBytes, B256};
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{G1Affine, G1Projective, G2Affine, G2Projective, Scalar};
+use sha2::{Digest, Sha256};
+
+/// Domain separation tag of the proof of possession scheme used by the beacon chain.
+const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+pub type PublicKey = FixedBytes<48>;
+pub type Signature = FixedBytes<96>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SecretKey(Scalar);
+
+impl SecretKey {
+    /// The key derived from `seed`, the same for the same seed.
+    pub fn derive(seed: &[u8]) -> Self {
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&Sha256::digest([seed, b"0"].concat()));
+        wide[32..].copy_from_slice(&Sha256::digest([seed, b"1"].concat()));
+        Self(Scalar::from_bytes_wide(&wide))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        compress_g1(G1Projective::generator() * self.0)
+    }
+}
+
+/// Sign `message` with every key of `keys`, and aggregate the signatures.
+///
+/// Aggregating the signatures of one message is signing it with the sum of the keys.
+pub fn aggregate_sign<'a>(
+    keys: impl IntoIterator<Item = &'a SecretKey>,
+    message: B256,
+) -> Signature {
+    let key = keys
+        .into_iter()
+        .fold(Scalar::zero(), |sum, key| sum + key.0);
+    if key == Scalar::zero() {
+        // The signature of no signers, the point at infinity.
+        return compress_g2(G2Projective::identity());
+    }
+    let point = <G2Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(message, DST);
+    compress_g2(point * key)
+}
+
+/// Aggregate public key of `keys`.
+pub fn aggregate_public_key<'a>(keys: impl IntoIterator<Item = &'a SecretKey>) -> PublicKey {
+    let key = keys
+        .into_iter()
+        .fold(Scalar::zero(), |sum, key| sum + key.0);
+    compress_g1(G1Projective::generator() * key)
+}
+
+fn compress_g1(point: G1Projective) -> PublicKey {
+    FixedBytes(G1Affine::from(point).to_compressed())
+}
+
+fn compress_g2(point: G2Projective) -> Signature {
+    FixedBytes(G2Affine::from(point).to_compressed())
+}
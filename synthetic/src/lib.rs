@@ -0,0 +1,881 @@
+//! A synthetic beacon chain for testing the light client: deterministic sync committees with
+//! real BLS keys, beacon headers with execution payloads committing to a generated execution
+//! state, and light client updates signed by any share of a committee, with any fork version.
+//!
+//! Objects are generated as beacon API JSON responses, which [`SyntheticChain::write_fixtures`]
+//! writes in the fixture layout of the mock beacon node, and [`parse`] turns into helios types.
+//! [`Tamper`] makes them invalid in targeted ways.
+//!
+//! Only the parts of the chain the light client commits to are generated. The beacon states
+//! are all zero except for the slot, the finalized checkpoint and the sync committees, and
+//! blocks have no operations.
+
+use alloy_primitives::{Address, FixedBytes, B256, U256};
+use anyhow::Result;
+use helios_ethereum::config::networks::Network;
+use r0vm_helios_mock_beacon::write_json;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub mod bls;
+pub mod mpt;
+pub mod ssz;
+
+use bls::{PublicKey, SecretKey, Signature};
+pub use mpt::{Account, ExecutionState};
+
+pub const SECONDS_PER_SLOT: u64 = 12;
+pub const SLOTS_PER_EPOCH: u64 = 32;
+pub const SLOTS_PER_PERIOD: u64 = SLOTS_PER_EPOCH * 256;
+pub const SYNC_COMMITTEE_SIZE: usize = 512;
+
+/// The response version of the generated objects. Execution payloads have the Deneb layout.
+const VERSION: &str = "deneb";
+const DOMAIN_SYNC_COMMITTEE: [u8; 4] = [7, 0, 0, 0];
+
+// Generalized indices in the Deneb beacon state and block body.
+const GENESIS_TIME_GINDEX: u64 = 32;
+const GENESIS_VALIDATORS_ROOT_GINDEX: u64 = 33;
+const SLOT_GINDEX: u64 = 34;
+const FINALIZED_EPOCH_GINDEX: u64 = 104;
+const FINALIZED_ROOT_GINDEX: u64 = 105;
+const CURRENT_SYNC_COMMITTEE_GINDEX: u64 = 54;
+const NEXT_SYNC_COMMITTEE_GINDEX: u64 = 55;
+const STATE_DEPTH: usize = 6;
+const EXECUTION_PAYLOAD_GINDEX: u64 = 25;
+
+/// The network a synthetic chain passes for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainSpec {
+    pub genesis_time: u64,
+    pub genesis_validators_root: B256,
+    /// Fork version updates are signed with, unless overridden.
+    pub fork_version: FixedBytes<4>,
+}
+
+impl ChainSpec {
+    /// The genesis of `network`, signing with its Deneb fork version. The chain then verifies
+    /// against the helios config of `network`, at slots of the Deneb fork.
+    pub fn deneb(network: Network) -> Self {
+        let config = network.to_base_config();
+        Self {
+            genesis_time: config.chain.genesis_time,
+            genesis_validators_root: config.chain.genesis_root,
+            fork_version: config.forks.deneb.fork_version,
+        }
+    }
+}
+
+/// A way to make a generated update invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tamper {
+    /// Sign a different header than the attested one.
+    Signature,
+    /// Sign with the sync committee of another period.
+    SignerPeriod(u64),
+    /// Use this signature slot instead.
+    SignatureSlot(u64),
+    FinalityBranch,
+    NextSyncCommitteeBranch,
+    /// Break the execution branch of the finalized header.
+    ExecutionBranch,
+    /// Pass the sync committee of another period as the next one.
+    NextSyncCommittee(u64),
+}
+
+/// How to sign a generated update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateOptions {
+    /// Number of sync committee members that sign, out of [`SYNC_COMMITTEE_SIZE`].
+    pub participation: usize,
+    /// Fork version to sign with, instead of the chain's.
+    pub fork_version: Option<FixedBytes<4>>,
+    pub tamper: Vec<Tamper>,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        Self {
+            participation: SYNC_COMMITTEE_SIZE,
+            fork_version: None,
+            tamper: Vec::new(),
+        }
+    }
+}
+
+impl UpdateOptions {
+    fn tampers(&self, tamper: Tamper) -> bool {
+        self.tamper.contains(&tamper)
+    }
+}
+
+struct SyncCommittee {
+    keys: Vec<SecretKey>,
+    pubkeys: Vec<PublicKey>,
+    aggregate_pubkey: PublicKey,
+}
+
+impl SyncCommittee {
+    fn root(&self) -> B256 {
+        let pubkeys: Vec<B256> = self
+            .pubkeys
+            .iter()
+            .map(|key| ssz::bytes(key.as_slice()))
+            .collect();
+        ssz::container(&[
+            ssz::merkleize(&pubkeys, ssz::depth_for(SYNC_COMMITTEE_SIZE)),
+            ssz::bytes(self.aggregate_pubkey.as_slice()),
+        ])
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "pubkeys": self.pubkeys,
+            "aggregate_pubkey": self.aggregate_pubkey,
+        })
+    }
+}
+
+/// A generated beacon block header, with the execution payload header of its block.
+struct Header {
+    slot: u64,
+    proposer_index: u64,
+    parent_root: B256,
+    state_root: B256,
+    body_root: B256,
+    execution: Value,
+    execution_branch: Vec<B256>,
+    /// The block body, without operations.
+    body: Value,
+}
+
+impl Header {
+    fn root(&self) -> B256 {
+        ssz::container(&[
+            ssz::uint64(self.slot),
+            ssz::uint64(self.proposer_index),
+            self.parent_root,
+            self.state_root,
+            self.body_root,
+        ])
+    }
+
+    fn beacon_json(&self) -> Value {
+        json!({
+            "slot": self.slot.to_string(),
+            "proposer_index": self.proposer_index.to_string(),
+            "parent_root": self.parent_root,
+            "state_root": self.state_root,
+            "body_root": self.body_root,
+        })
+    }
+
+    /// The header as a light client header.
+    fn to_json(&self) -> Value {
+        json!({
+            "beacon": self.beacon_json(),
+            "execution": self.execution,
+            "execution_branch": self.execution_branch,
+        })
+    }
+}
+
+/// A synthetic beacon chain, see the crate documentation.
+///
+/// Everything is derived from the seed, so chains with the same seed, spec and execution state
+/// generate the same objects.
+pub struct SyntheticChain {
+    spec: ChainSpec,
+    seed: Vec<u8>,
+    pub execution_state: ExecutionState,
+    committees: Mutex<HashMap<u64, Arc<SyncCommittee>>>,
+}
+
+impl SyntheticChain {
+    pub fn new(spec: ChainSpec, seed: &[u8]) -> Self {
+        Self {
+            spec,
+            seed: seed.to_vec(),
+            execution_state: ExecutionState::default(),
+            committees: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn spec(&self) -> ChainSpec {
+        self.spec
+    }
+
+    /// Unix timestamp of `slot`.
+    pub fn slot_timestamp(&self, slot: u64) -> u64 {
+        self.spec.genesis_time + slot * SECONDS_PER_SLOT
+    }
+
+    /// Root of the block at `slot`, to bootstrap from.
+    pub fn block_root(&self, slot: u64) -> B256 {
+        self.header(slot, None).root()
+    }
+
+    /// Root of the sync committee of `period`.
+    pub fn sync_committee_root(&self, period: u64) -> B256 {
+        self.committee(period).root()
+    }
+
+    /// Root of the execution state every execution payload commits to.
+    pub fn execution_state_root(&self) -> B256 {
+        self.execution_state.state_root()
+    }
+
+    /// Block number of the execution payload of `slot`.
+    pub fn execution_block_number(&self, slot: u64) -> u64 {
+        slot
+    }
+
+    /// The `/eth/v2/beacon/blocks/{slot}` response.
+    pub fn block(&self, slot: u64) -> Value {
+        let header = self.header(slot, None);
+        let mut message = header.beacon_json();
+        message.as_object_mut().unwrap().remove("body_root");
+        message["body"] = header.body;
+        json!({
+            "version": VERSION,
+            "execution_optimistic": false,
+            "finalized": true,
+            "data": {
+                "message": message,
+                "signature": Signature::ZERO,
+            },
+        })
+    }
+
+    /// The bootstrap response for the block at `slot`.
+    pub fn bootstrap(&self, slot: u64) -> Value {
+        let header = self.header(slot, None);
+        let period = slot / SLOTS_PER_PERIOD;
+        json!({
+            "version": VERSION,
+            "data": {
+                "header": header.to_json(),
+                "current_sync_committee": self.committee(period).to_json(),
+                "current_sync_committee_branch":
+                    self.state(slot, None).branch(CURRENT_SYNC_COMMITTEE_GINDEX),
+            },
+        })
+    }
+
+    /// The sync committee update of `period`: late in the period, it proves the sync committee
+    /// of the next period, signed by the committee of `period`.
+    pub fn update(&self, period: u64, options: &UpdateOptions) -> Value {
+        let attested_slot = (period + 1) * SLOTS_PER_PERIOD - SLOTS_PER_EPOCH;
+        let mut update = self.finality_update(attested_slot, options);
+
+        let state = self.state(attested_slot, Some(finalized_slot(attested_slot)));
+        let mut branch = state.branch(NEXT_SYNC_COMMITTEE_GINDEX);
+        if options.tampers(Tamper::NextSyncCommitteeBranch) {
+            tamper(&mut branch);
+        }
+        let next_period = options
+            .tamper
+            .iter()
+            .find_map(|tamper| match tamper {
+                Tamper::NextSyncCommittee(period) => Some(*period),
+                _ => None,
+            })
+            .unwrap_or(period + 1);
+
+        let data = &mut update["data"];
+        data["next_sync_committee"] = self.committee(next_period).to_json();
+        data["next_sync_committee_branch"] = json!(branch);
+        update
+    }
+
+    /// The finality update attested at `slot`, finalizing the start of the epoch two epochs
+    /// before it.
+    pub fn finality_update(&self, slot: u64, options: &UpdateOptions) -> Value {
+        let finalized_slot = finalized_slot(slot);
+        let mut finalized = self.header(finalized_slot, None);
+        if options.tampers(Tamper::ExecutionBranch) {
+            tamper(&mut finalized.execution_branch);
+        }
+        let mut finality_branch = self
+            .state(slot, Some(finalized_slot))
+            .branch(FINALIZED_ROOT_GINDEX);
+        if options.tampers(Tamper::FinalityBranch) {
+            tamper(&mut finality_branch);
+        }
+
+        let mut update = self.optimistic_update(slot, options);
+        let data = &mut update["data"];
+        data["finalized_header"] = finalized.to_json();
+        data["finality_branch"] = json!(finality_branch);
+        update
+    }
+
+    /// The optimistic update attested at `slot`.
+    pub fn optimistic_update(&self, slot: u64, options: &UpdateOptions) -> Value {
+        let attested = self.header(slot, Some(finalized_slot(slot)));
+        let signature_slot = options
+            .tamper
+            .iter()
+            .find_map(|tamper| match tamper {
+                Tamper::SignatureSlot(slot) => Some(*slot),
+                _ => None,
+            })
+            .unwrap_or(slot + 1);
+
+        json!({
+            "version": VERSION,
+            "data": {
+                "attested_header": attested.to_json(),
+                "sync_aggregate": self.sync_aggregate(&attested, signature_slot, options),
+                "signature_slot": signature_slot.to_string(),
+            },
+        })
+    }
+
+    /// Write the fixtures of a chain from the checkpoint at `checkpoint_slot` to a finality
+    /// update attested at `head_slot` to `dir`, in the layout the mock beacon node serves.
+    pub fn write_fixtures(&self, dir: &Path, checkpoint_slot: u64, head_slot: u64) -> Result<()> {
+        let root = self.block_root(checkpoint_slot);
+        write_json(
+            &dir.join(format!("blocks/{}.json", checkpoint_slot)),
+            &self.block(checkpoint_slot),
+        )?;
+        write_json(
+            &dir.join(format!("blocks/{}.json", root)),
+            &self.block(checkpoint_slot),
+        )?;
+        write_json(
+            &dir.join(format!("bootstrap/{}.json", root)),
+            &self.bootstrap(checkpoint_slot),
+        )?;
+
+        let options = UpdateOptions::default();
+        for period in checkpoint_slot / SLOTS_PER_PERIOD..head_slot / SLOTS_PER_PERIOD {
+            write_json(
+                &dir.join(format!("updates/{}.json", period)),
+                &self.update(period, &options),
+            )?;
+        }
+        write_json(
+            &dir.join("finality_update.json"),
+            &self.finality_update(head_slot, &options),
+        )?;
+        write_json(
+            &dir.join("optimistic_update.json"),
+            &self.optimistic_update(head_slot, &options),
+        )?;
+        Ok(())
+    }
+
+    fn sync_aggregate(
+        &self,
+        attested: &Header,
+        signature_slot: u64,
+        options: &UpdateOptions,
+    ) -> Value {
+        let signer_period = options
+            .tamper
+            .iter()
+            .find_map(|tamper| match tamper {
+                Tamper::SignerPeriod(period) => Some(*period),
+                _ => None,
+            })
+            .unwrap_or(signature_slot / SLOTS_PER_PERIOD);
+        let committee = self.committee(signer_period);
+        let participation = options.participation.min(SYNC_COMMITTEE_SIZE);
+
+        let mut bits = [0u8; SYNC_COMMITTEE_SIZE / 8];
+        for index in 0..participation {
+            bits[index / 8] |= 1 << (index % 8);
+        }
+
+        let mut header_root = attested.root();
+        if options.tampers(Tamper::Signature) {
+            header_root = ssz::sha256(header_root.as_slice());
+        }
+        let fork_version = options.fork_version.unwrap_or(self.spec.fork_version);
+        let message = signing_root(header_root, fork_version, self.spec.genesis_validators_root);
+        let signature = bls::aggregate_sign(&committee.keys[..participation], message);
+
+        json!({
+            "sync_committee_bits": FixedBytes(bits),
+            "sync_committee_signature": signature,
+        })
+    }
+
+    fn committee(&self, period: u64) -> Arc<SyncCommittee> {
+        let mut committees = self.committees.lock().unwrap();
+        committees
+            .entry(period)
+            .or_insert_with(|| {
+                let keys: Vec<SecretKey> = (0..SYNC_COMMITTEE_SIZE as u64)
+                    .map(|index| {
+                        SecretKey::derive(
+                            &[
+                                self.seed.as_slice(),
+                                b"validator",
+                                &period.to_le_bytes(),
+                                &index.to_le_bytes(),
+                            ]
+                            .concat(),
+                        )
+                    })
+                    .collect();
+                Arc::new(SyncCommittee {
+                    pubkeys: keys.iter().map(SecretKey::public_key).collect(),
+                    aggregate_pubkey: bls::aggregate_public_key(&keys),
+                    keys,
+                })
+            })
+            .clone()
+    }
+
+    /// The beacon state at `slot`, with the checkpoint of `finalized_slot` if any.
+    fn state(&self, slot: u64, finalized_slot: Option<u64>) -> ssz::Tree {
+        let period = slot / SLOTS_PER_PERIOD;
+        let mut state = ssz::Tree::new(STATE_DEPTH);
+        state.set(GENESIS_TIME_GINDEX, ssz::uint64(self.spec.genesis_time));
+        state.set(
+            GENESIS_VALIDATORS_ROOT_GINDEX,
+            self.spec.genesis_validators_root,
+        );
+        state.set(SLOT_GINDEX, ssz::uint64(slot));
+        state.set(
+            CURRENT_SYNC_COMMITTEE_GINDEX,
+            self.sync_committee_root(period),
+        );
+        state.set(
+            NEXT_SYNC_COMMITTEE_GINDEX,
+            self.sync_committee_root(period + 1),
+        );
+        if let Some(finalized_slot) = finalized_slot {
+            state.set(
+                FINALIZED_EPOCH_GINDEX,
+                ssz::uint64(finalized_slot / SLOTS_PER_EPOCH),
+            );
+            state.set(FINALIZED_ROOT_GINDEX, self.block_root(finalized_slot));
+        }
+        state
+    }
+
+    /// The header at `slot`, with the checkpoint of `finalized_slot` in its state if any.
+    fn header(&self, slot: u64, finalized_slot: Option<u64>) -> Header {
+        let derive = |label: &str| {
+            ssz::sha256(&[self.seed.as_slice(), label.as_bytes(), &slot.to_le_bytes()].concat())
+        };
+
+        let block_number = self.execution_block_number(slot);
+        let fee_recipient = Address::from_slice(&derive("fee_recipient")[..20]);
+        let logs_bloom = [0u8; 256];
+        let base_fee_per_gas = U256::from(1_000_000_000u64);
+        let gas_limit = 30_000_000u64;
+        let transactions_root = ssz::empty_list(1 << 20);
+        let withdrawals_root = ssz::empty_list(16);
+        let fields = [
+            (
+                "parent_hash",
+                json!(derive("parent_hash")),
+                derive("parent_hash"),
+            ),
+            (
+                "fee_recipient",
+                json!(fee_recipient),
+                ssz::address(fee_recipient),
+            ),
+            (
+                "state_root",
+                json!(self.execution_state_root()),
+                self.execution_state_root(),
+            ),
+            (
+                "receipts_root",
+                json!(derive("receipts_root")),
+                derive("receipts_root"),
+            ),
+            (
+                "logs_bloom",
+                json!(FixedBytes(logs_bloom)),
+                ssz::bytes(&logs_bloom),
+            ),
+            (
+                "prev_randao",
+                json!(derive("prev_randao")),
+                derive("prev_randao"),
+            ),
+            (
+                "block_number",
+                json!(block_number.to_string()),
+                ssz::uint64(block_number),
+            ),
+            (
+                "gas_limit",
+                json!(gas_limit.to_string()),
+                ssz::uint64(gas_limit),
+            ),
+            ("gas_used", json!("0"), ssz::uint64(0)),
+            (
+                "timestamp",
+                json!(self.slot_timestamp(slot).to_string()),
+                ssz::uint64(self.slot_timestamp(slot)),
+            ),
+            ("extra_data", json!("0x"), ssz::byte_list(&[])),
+            (
+                "base_fee_per_gas",
+                json!(base_fee_per_gas.to_string()),
+                ssz::uint256(base_fee_per_gas),
+            ),
+            (
+                "block_hash",
+                json!(derive("block_hash")),
+                derive("block_hash"),
+            ),
+            (
+                "transactions_root",
+                json!(transactions_root),
+                transactions_root,
+            ),
+            (
+                "withdrawals_root",
+                json!(withdrawals_root),
+                withdrawals_root,
+            ),
+            ("blob_gas_used", json!("0"), ssz::uint64(0)),
+            ("excess_blob_gas", json!("0"), ssz::uint64(0)),
+        ];
+        let execution: serde_json::Map<String, Value> = fields
+            .iter()
+            .map(|(name, value, _)| (name.to_string(), value.clone()))
+            .collect();
+        let execution_root =
+            ssz::container(&fields.iter().map(|(_, _, root)| *root).collect::<Vec<_>>());
+
+        // The full payload has the same root as its header, with no transactions or withdrawals.
+        let mut payload = execution.clone();
+        payload.remove("transactions_root");
+        payload.remove("withdrawals_root");
+        payload.insert("transactions".to_string(), json!([]));
+        payload.insert("withdrawals".to_string(), json!([]));
+
+        let randao_reveal = Signature::ZERO;
+        let eth1_data = json!({
+            "deposit_root": B256::ZERO,
+            "deposit_count": "0",
+            "block_hash": B256::ZERO,
+        });
+        let sync_aggregate = json!({
+            "sync_committee_bits": FixedBytes([0u8; SYNC_COMMITTEE_SIZE / 8]),
+            "sync_committee_signature": Signature::ZERO,
+        });
+        let body = json!({
+            "randao_reveal": randao_reveal,
+            "eth1_data": eth1_data,
+            "graffiti": B256::ZERO,
+            "proposer_slashings": [],
+            "attester_slashings": [],
+            "attestations": [],
+            "deposits": [],
+            "voluntary_exits": [],
+            "sync_aggregate": sync_aggregate,
+            "execution_payload": payload,
+            "bls_to_execution_changes": [],
+            "blob_kzg_commitments": [],
+        });
+        let body_tree = ssz::Tree::from_leaves(
+            &[
+                ssz::bytes(randao_reveal.as_slice()),
+                ssz::container(&[B256::ZERO, ssz::uint64(0), B256::ZERO]),
+                B256::ZERO,
+                ssz::empty_list(16),
+                ssz::empty_list(2),
+                ssz::empty_list(128),
+                ssz::empty_list(16),
+                ssz::empty_list(16),
+                ssz::container(&[
+                    ssz::bytes(&[0u8; SYNC_COMMITTEE_SIZE / 8]),
+                    ssz::bytes(Signature::ZERO.as_slice()),
+                ]),
+                execution_root,
+                ssz::empty_list(16),
+                ssz::empty_list(4096),
+            ],
+            4,
+        );
+
+        Header {
+            slot,
+            proposer_index: slot % 1024,
+            parent_root: derive("parent_root"),
+            state_root: self.state(slot, finalized_slot).root(),
+            body_root: body_tree.root(),
+            execution: Value::Object(execution),
+            execution_branch: body_tree.branch(EXECUTION_PAYLOAD_GINDEX),
+            body,
+        }
+    }
+}
+
+/// The slot a finality update attested at `slot` finalizes: the start of the epoch two epochs
+/// before the attested one.
+pub fn finalized_slot(slot: u64) -> u64 {
+    (slot / SLOTS_PER_EPOCH).saturating_sub(2) * SLOTS_PER_EPOCH
+}
+
+/// The root the sync committee signs for `header_root`.
+pub fn signing_root(
+    header_root: B256,
+    fork_version: FixedBytes<4>,
+    genesis_validators_root: B256,
+) -> B256 {
+    let mut version = B256::ZERO;
+    version[..4].copy_from_slice(fork_version.as_slice());
+    let fork_data_root = ssz::hash_pair(version, genesis_validators_root);
+
+    let mut domain = B256::ZERO;
+    domain[..4].copy_from_slice(&DOMAIN_SYNC_COMMITTEE);
+    domain[4..].copy_from_slice(&fork_data_root[..28]);
+    ssz::hash_pair(header_root, domain)
+}
+
+/// Parse the `data` of a generated response into a helios type, such as an `Update` for the
+/// guest's inputs.
+pub fn parse<T: DeserializeOwned>(response: &Value) -> Result<T> {
+    Ok(serde_json::from_value(response["data"].clone())?)
+}
+
+fn tamper(branch: &mut [B256]) {
+    if let Some(node) = branch.first_mut() {
+        *node = ssz::sha256(node.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helios_consensus_core::consensus_spec::MainnetConsensusSpec;
+    use helios_consensus_core::types::{
+        BeaconBlock, FinalityUpdate, LightClientHeader, LightClientStore,
+        SyncCommittee as Committee, Update,
+    };
+    use helios_consensus_core::{
+        apply_finality_update, apply_update, verify_finality_update, verify_update,
+    };
+    use tree_hash::TreeHash;
+
+    /// A Deneb period of mainnet.
+    const PERIOD: u64 = 1300;
+    const CHECKPOINT_SLOT: u64 = PERIOD * SLOTS_PER_PERIOD + 2 * SLOTS_PER_EPOCH;
+
+    fn chain() -> SyntheticChain {
+        SyntheticChain::new(ChainSpec::deneb(Network::Mainnet), b"test")
+    }
+
+    fn verify_branch(leaf: B256, branch: &[B256], gindex: u64, root: B256) -> bool {
+        let computed = branch
+            .iter()
+            .enumerate()
+            .fold(leaf, |node, (level, sibling)| {
+                if (gindex >> level) & 1 == 1 {
+                    ssz::hash_pair(*sibling, node)
+                } else {
+                    ssz::hash_pair(node, *sibling)
+                }
+            });
+        computed == root
+    }
+
+    /// The store bootstrapped from the checkpoint.
+    fn store(chain: &SyntheticChain) -> LightClientStore<MainnetConsensusSpec> {
+        let bootstrap = chain.bootstrap(CHECKPOINT_SLOT);
+        let header: LightClientHeader =
+            serde_json::from_value(bootstrap["data"]["header"].clone()).unwrap();
+        LightClientStore {
+            finalized_header: header.clone(),
+            optimistic_header: header,
+            current_sync_committee: serde_json::from_value(
+                bootstrap["data"]["current_sync_committee"].clone(),
+            )
+            .unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn verify(chain: &SyntheticChain, update: &Value) -> bool {
+        let update: Update<MainnetConsensusSpec> = parse(update).unwrap();
+        let forks = Network::Mainnet.to_base_config().forks;
+        verify_update(
+            &update,
+            u64::MAX,
+            &store(chain),
+            chain.spec().genesis_validators_root,
+            &forks,
+        )
+        .is_ok()
+    }
+
+    #[test]
+    fn roots_match_helios() {
+        let chain = chain();
+        let bootstrap = chain.bootstrap(CHECKPOINT_SLOT);
+        let data = &bootstrap["data"];
+
+        let header: LightClientHeader = serde_json::from_value(data["header"].clone()).unwrap();
+        assert_eq!(
+            header.beacon().tree_hash_root(),
+            chain.block_root(CHECKPOINT_SLOT)
+        );
+
+        let committee: Committee<MainnetConsensusSpec> =
+            serde_json::from_value(data["current_sync_committee"].clone()).unwrap();
+        assert_eq!(
+            committee.tree_hash_root(),
+            chain.sync_committee_root(PERIOD)
+        );
+
+        let block: BeaconBlock<MainnetConsensusSpec> =
+            serde_json::from_value(chain.block(CHECKPOINT_SLOT)["data"]["message"].clone())
+                .unwrap();
+        assert_eq!(block.tree_hash_root(), chain.block_root(CHECKPOINT_SLOT));
+
+        let execution = header.execution().unwrap();
+        let branch: Vec<B256> =
+            serde_json::from_value(data["header"]["execution_branch"].clone()).unwrap();
+        assert!(verify_branch(
+            execution.tree_hash_root(),
+            &branch,
+            EXECUTION_PAYLOAD_GINDEX,
+            header.beacon().body_root
+        ));
+        assert_eq!(*execution.state_root(), chain.execution_state_root());
+    }
+
+    #[test]
+    fn updates_verify() {
+        let chain = chain();
+        let forks = Network::Mainnet.to_base_config().forks;
+        let genesis_root = chain.spec().genesis_validators_root;
+        let mut store = store(&chain);
+
+        for period in PERIOD..PERIOD + 2 {
+            let update: Update<MainnetConsensusSpec> =
+                parse(&chain.update(period, &UpdateOptions::default())).unwrap();
+            verify_update(&update, u64::MAX, &store, genesis_root, &forks).unwrap();
+            apply_update(&mut store, &update);
+        }
+        assert_eq!(
+            store.current_sync_committee.tree_hash_root(),
+            chain.sync_committee_root(PERIOD + 1)
+        );
+
+        let head = (PERIOD + 2) * SLOTS_PER_PERIOD + 100 * SLOTS_PER_EPOCH;
+        let finality_update: FinalityUpdate<MainnetConsensusSpec> =
+            parse(&chain.finality_update(head, &UpdateOptions::default())).unwrap();
+        verify_finality_update(&finality_update, u64::MAX, &store, genesis_root, &forks).unwrap();
+        apply_finality_update(&mut store, &finality_update);
+        assert_eq!(store.finalized_header.beacon().slot, finalized_slot(head));
+        assert_eq!(
+            store.current_sync_committee.tree_hash_root(),
+            chain.sync_committee_root(PERIOD + 2)
+        );
+    }
+
+    #[test]
+    fn tampered_updates_fail() {
+        let chain = chain();
+        assert!(verify(
+            &chain,
+            &chain.update(PERIOD, &UpdateOptions::default())
+        ));
+
+        // The signature slot must be after the attested slot, in a period the store knows the
+        // sync committee of.
+        let attested_slot = (PERIOD + 1) * SLOTS_PER_PERIOD - SLOTS_PER_EPOCH;
+        let options = UpdateOptions {
+            tamper: vec![Tamper::SignatureSlot(attested_slot + 2)],
+            ..Default::default()
+        };
+        assert!(verify(&chain, &chain.update(PERIOD, &options)));
+
+        let tampers = [
+            Tamper::Signature,
+            Tamper::SignerPeriod(PERIOD + 1),
+            Tamper::SignatureSlot(attested_slot),
+            Tamper::SignatureSlot((PERIOD + 2) * SLOTS_PER_PERIOD),
+            Tamper::FinalityBranch,
+            Tamper::NextSyncCommitteeBranch,
+            Tamper::ExecutionBranch,
+            Tamper::NextSyncCommittee(PERIOD + 2),
+        ];
+        for tamper in tampers {
+            let options = UpdateOptions {
+                tamper: vec![tamper],
+                ..Default::default()
+            };
+            assert!(
+                !verify(&chain, &chain.update(PERIOD, &options)),
+                "{:?}",
+                tamper
+            );
+        }
+
+        let options = UpdateOptions {
+            fork_version: Some(FixedBytes([3, 0, 0, 0])),
+            ..Default::default()
+        };
+        assert!(!verify(&chain, &chain.update(PERIOD, &options)));
+
+        let options = UpdateOptions {
+            participation: 0,
+            ..Default::default()
+        };
+        assert!(!verify(&chain, &chain.update(PERIOD, &options)));
+
+        // Any participation verifies, even if too low for the store to apply the update.
+        let options = UpdateOptions {
+            participation: 1,
+            ..Default::default()
+        };
+        assert!(verify(&chain, &chain.update(PERIOD, &options)));
+    }
+
+    #[test]
+    fn storage_proofs_verify() {
+        use alloy_rlp::Encodable;
+        use alloy_trie::{proof::verify_proof, Nibbles};
+
+        let mut chain = chain();
+        let address = Address::repeat_byte(0x42);
+        let slot = B256::with_last_byte(1);
+        chain
+            .execution_state
+            .set_storage(address, slot, U256::from(7));
+        chain
+            .execution_state
+            .set_storage(Address::repeat_byte(0x43), slot, U256::from(8));
+
+        let storage = chain.execution_state.contract_storage(address, &[slot]);
+        let mut account = Vec::new();
+        storage.expected_value.encode(&mut account);
+        verify_proof(
+            chain.execution_state_root(),
+            Nibbles::unpack(alloy_primitives::keccak256(address)),
+            Some(account),
+            &storage.mpt_proof,
+        )
+        .unwrap();
+
+        let slot_proof = &storage.storage_slots[0];
+        assert_eq!(slot_proof.expected_value, U256::from(7));
+        let mut value = Vec::new();
+        slot_proof.expected_value.encode(&mut value);
+        verify_proof(
+            storage.expected_value.storage_root,
+            Nibbles::unpack(alloy_primitives::keccak256(slot)),
+            Some(value),
+            &slot_proof.mpt_proof,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+//! A generated execution state, and its Merkle Patricia Trie proofs.
+
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_rlp::Encodable;
+use alloy_trie::proof::ProofRetainer;
+use alloy_trie::{HashBuilder, Nibbles, TrieAccount, KECCAK_EMPTY};
+use r0vm_helios_primitives::types::{ContractStorage, StorageSlot};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// An account of the execution state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: U256,
+    pub code_hash: Option<B256>,
+    pub storage: BTreeMap<B256, U256>,
+}
+
+/// Execution layer accounts and storage, committed to by a state root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionState {
+    pub accounts: BTreeMap<Address, Account>,
+}
+
+impl ExecutionState {
+    /// Set storage `slot` of `address`, creating the account if needed.
+    pub fn set_storage(&mut self, address: Address, slot: B256, value: U256) {
+        self.accounts
+            .entry(address)
+            .or_default()
+            .storage
+            .insert(slot, value);
+    }
+
+    pub fn state_root(&self) -> B256 {
+        self.account_trie(&[]).0
+    }
+
+    /// Proofs of the account `address` and its storage `slots`, in the form the guest verifies.
+    ///
+    /// The proofs of slots that aren't set prove a zero value, which the guest rejects.
+    pub fn contract_storage(&self, address: Address, slots: &[B256]) -> ContractStorage {
+        let account = self.accounts.get(&address).cloned().unwrap_or_default();
+        let (_, mut account_proofs) = self.account_trie(&[address]);
+        let (_, mut storage_proofs) = storage_trie(&account.storage, slots);
+
+        ContractStorage {
+            address,
+            expected_value: trie_account(&account),
+            mpt_proof: account_proofs
+                .remove(&keccak256(address))
+                .unwrap_or_default(),
+            storage_slots: slots
+                .iter()
+                .map(|slot| StorageSlot {
+                    key: *slot,
+                    expected_value: account.storage.get(slot).copied().unwrap_or_default(),
+                    mpt_proof: storage_proofs.remove(&keccak256(slot)).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The `eth_getProof` result for the account `address` and its storage `slots`.
+    pub fn proof_response(&self, address: Address, slots: &[B256]) -> Value {
+        let storage = self.contract_storage(address, slots);
+        let account = storage.expected_value;
+        json!({
+            "address": address,
+            "balance": account.balance,
+            "codeHash": account.code_hash,
+            "nonce": format!("{:#x}", account.nonce),
+            "storageHash": account.storage_root,
+            "accountProof": storage.mpt_proof,
+            "storageProof": storage
+                .storage_slots
+                .iter()
+                .map(|slot| json!({
+                    "key": slot.key,
+                    "value": slot.expected_value,
+                    "proof": slot.mpt_proof,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn account_trie(&self, targets: &[Address]) -> (B256, BTreeMap<B256, Vec<Bytes>>) {
+        let leaves = self
+            .accounts
+            .iter()
+            .map(|(address, account)| (keccak256(address), rlp(&trie_account(account))))
+            .collect();
+        let targets: Vec<B256> = targets.iter().map(keccak256).collect();
+        trie(leaves, &targets)
+    }
+}
+
+fn trie_account(account: &Account) -> TrieAccount {
+    TrieAccount {
+        nonce: account.nonce,
+        balance: account.balance,
+        storage_root: storage_trie(&account.storage, &[]).0,
+        code_hash: account.code_hash.unwrap_or(KECCAK_EMPTY),
+    }
+}
+
+fn storage_trie(
+    storage: &BTreeMap<B256, U256>,
+    targets: &[B256],
+) -> (B256, BTreeMap<B256, Vec<Bytes>>) {
+    // Zero values are not stored.
+    let leaves = storage
+        .iter()
+        .filter(|(_, value)| !value.is_zero())
+        .map(|(slot, value)| (keccak256(slot), rlp(value)))
+        .collect();
+    let targets: Vec<B256> = targets.iter().map(keccak256).collect();
+    trie(leaves, &targets)
+}
+
+/// Root of the trie of `leaves` by hashed key, and the proofs of `targets`.
+fn trie(leaves: BTreeMap<B256, Vec<u8>>, targets: &[B256]) -> (B256, BTreeMap<B256, Vec<Bytes>>) {
+    let retainer = ProofRetainer::new(targets.iter().map(Nibbles::unpack).collect());
+    let mut builder = HashBuilder::default().with_proof_retainer(retainer);
+    for (key, value) in &leaves {
+        builder.add_leaf(Nibbles::unpack(key), value);
+    }
+    let root = builder.root();
+
+    let nodes = builder.take_proof_nodes();
+    let proofs = targets
+        .iter()
+        .map(|key| {
+            let proof = nodes
+                .matching_nodes_sorted(&Nibbles::unpack(key))
+                .into_iter()
+                .map(|(_, node)| node)
+                .collect();
+            (*key, proof)
+        })
+        .collect();
+    (root, proofs)
+}
+
+fn rlp(value: &impl Encodable) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    value.encode(&mut encoded);
+    encoded
+}
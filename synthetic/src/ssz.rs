@@ -0,0 +1,142 @@
+//! Just enough SSZ merkleization to compute the roots and branches of the beacon chain objects
+//! the light client verifies.
+
+use alloy_primitives::{Address, B256, U256};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+pub fn sha256(data: &[u8]) -> B256 {
+    B256::from_slice(&Sha256::digest(data))
+}
+
+pub fn hash_pair(left: B256, right: B256) -> B256 {
+    sha256(&[left.as_slice(), right.as_slice()].concat())
+}
+
+/// Root of a tree of `depth` levels of zero chunks.
+pub fn zero_hash(depth: usize) -> B256 {
+    (0..depth).fold(B256::ZERO, |hash, _| hash_pair(hash, hash))
+}
+
+pub fn uint64(value: u64) -> B256 {
+    let mut chunk = B256::ZERO;
+    chunk[..8].copy_from_slice(&value.to_le_bytes());
+    chunk
+}
+
+pub fn uint256(value: U256) -> B256 {
+    B256::from(value.to_le_bytes::<32>())
+}
+
+pub fn address(value: Address) -> B256 {
+    let mut chunk = B256::ZERO;
+    chunk[..20].copy_from_slice(value.as_slice());
+    chunk
+}
+
+/// Root of a fixed size byte vector.
+pub fn bytes(value: &[u8]) -> B256 {
+    let chunks: Vec<B256> = value
+        .chunks(32)
+        .map(|chunk| {
+            let mut padded = B256::ZERO;
+            padded[..chunk.len()].copy_from_slice(chunk);
+            padded
+        })
+        .collect();
+    merkleize(&chunks, depth_for(chunks.len()))
+}
+
+/// Root of a byte list of at most 32 bytes.
+pub fn byte_list(value: &[u8]) -> B256 {
+    mix_in_length(bytes(value), value.len())
+}
+
+/// Root of an empty list of at most `limit` composite elements.
+pub fn empty_list(limit: usize) -> B256 {
+    mix_in_length(zero_hash(depth_for(limit)), 0)
+}
+
+pub fn mix_in_length(root: B256, length: usize) -> B256 {
+    hash_pair(root, uint64(length as u64))
+}
+
+/// Root of `chunks` in a tree of `depth` levels, padded with zero chunks.
+pub fn merkleize(chunks: &[B256], depth: usize) -> B256 {
+    Tree::from_leaves(chunks, depth).root()
+}
+
+/// Root of a container with the field roots `fields`.
+pub fn container(fields: &[B256]) -> B256 {
+    merkleize(fields, depth_for(fields.len()))
+}
+
+/// Depth of a tree with room for `leaves` leaves.
+pub fn depth_for(leaves: usize) -> usize {
+    leaves.next_power_of_two().trailing_zeros() as usize
+}
+
+/// A merkle tree of `depth` levels, of which only some nodes are known. The other subtrees are
+/// all zero chunks.
+///
+/// Nodes are addressed by generalized index, so the root is 1 and the children of `n` are `2n`
+/// and `2n + 1`.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    depth: usize,
+    nodes: BTreeMap<u64, B256>,
+}
+
+impl Tree {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_leaves(leaves: &[B256], depth: usize) -> Self {
+        let mut tree = Self::new(depth);
+        for (index, leaf) in leaves.iter().enumerate() {
+            tree.set((1 << depth) + index as u64, *leaf);
+        }
+        tree
+    }
+
+    /// Set the node at `gindex`, which becomes a leaf of the tree.
+    pub fn set(&mut self, gindex: u64, node: B256) {
+        self.nodes.insert(gindex, node);
+    }
+
+    pub fn root(&self) -> B256 {
+        self.node(1)
+    }
+
+    pub fn node(&self, gindex: u64) -> B256 {
+        if let Some(node) = self.nodes.get(&gindex) {
+            return *node;
+        }
+        let level = 63 - gindex.leading_zeros() as usize;
+        if level >= self.depth || !self.has_descendants(gindex) {
+            return zero_hash(self.depth.saturating_sub(level));
+        }
+        hash_pair(self.node(2 * gindex), self.node(2 * gindex + 1))
+    }
+
+    /// The sibling nodes on the path from `gindex` to the root, from the bottom up.
+    pub fn branch(&self, mut gindex: u64) -> Vec<B256> {
+        let mut branch = Vec::new();
+        while gindex > 1 {
+            branch.push(self.node(gindex ^ 1));
+            gindex /= 2;
+        }
+        branch
+    }
+
+    fn has_descendants(&self, gindex: u64) -> bool {
+        self.nodes.keys().any(|&node| {
+            let shift = (63 - node.leading_zeros()) as i64 - (63 - gindex.leading_zeros()) as i64;
+            shift > 0 && node >> shift == gindex
+        })
+    }
+}